@@ -0,0 +1,312 @@
+//! Minimal reduced ordered binary decision diagrams
+//!
+//! This is only as much as is needed to compare the small functions that fit into a CPLD and to
+//! write them as sums of products.
+//! Functions are identified by [BddRef]; two functions are equal if and only if their refs are.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub(crate) struct BddRef(u32);
+
+impl BddRef {
+    pub(crate) const FALSE: BddRef = BddRef(0);
+    pub(crate) const TRUE: BddRef = BddRef(1);
+
+    pub(crate) fn constant(val: bool) -> Self {
+        if val {
+            Self::TRUE
+        } else {
+            Self::FALSE
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+enum Op {
+    And,
+    Xor,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    var: u32,
+    lo: BddRef,
+    hi: BddRef,
+}
+
+pub(crate) struct Bdd {
+    nodes: Vec<Node>,
+    unique: BTreeMap<(u32, BddRef, BddRef), BddRef>,
+    cache: BTreeMap<(Op, BddRef, BddRef), BddRef>,
+}
+
+impl Bdd {
+    pub(crate) fn new() -> Self {
+        // Terminals sort after every variable
+        let terminal = Node {
+            var: u32::MAX,
+            lo: BddRef::FALSE,
+            hi: BddRef::FALSE,
+        };
+        Self {
+            nodes: vec![terminal, terminal],
+            unique: BTreeMap::new(),
+            cache: BTreeMap::new(),
+        }
+    }
+
+    fn mk(&mut self, var: u32, lo: BddRef, hi: BddRef) -> BddRef {
+        if lo == hi {
+            return lo;
+        }
+        if let Some(&x) = self.unique.get(&(var, lo, hi)) {
+            return x;
+        }
+        let x = BddRef(self.nodes.len() as u32);
+        self.nodes.push(Node { var, lo, hi });
+        self.unique.insert((var, lo, hi), x);
+        x
+    }
+
+    pub(crate) fn var(&mut self, var: u32) -> BddRef {
+        self.mk(var, BddRef::FALSE, BddRef::TRUE)
+    }
+
+    fn apply(&mut self, op: Op, f: BddRef, g: BddRef) -> BddRef {
+        match op {
+            Op::And => {
+                if f == BddRef::FALSE || g == BddRef::FALSE {
+                    return BddRef::FALSE;
+                }
+                if f == BddRef::TRUE {
+                    return g;
+                }
+                if g == BddRef::TRUE || f == g {
+                    return f;
+                }
+            }
+            Op::Xor => {
+                if f == BddRef::FALSE {
+                    return g;
+                }
+                if g == BddRef::FALSE {
+                    return f;
+                }
+                if f == g {
+                    return BddRef::FALSE;
+                }
+            }
+        }
+        // Both operations are commutative
+        let (f, g) = if f < g { (f, g) } else { (g, f) };
+        if let Some(&x) = self.cache.get(&(op, f, g)) {
+            return x;
+        }
+
+        let fnode = self.nodes[f.0 as usize];
+        let gnode = self.nodes[g.0 as usize];
+        let var = fnode.var.min(gnode.var);
+        let (f_lo, f_hi) = if fnode.var == var {
+            (fnode.lo, fnode.hi)
+        } else {
+            (f, f)
+        };
+        let (g_lo, g_hi) = if gnode.var == var {
+            (gnode.lo, gnode.hi)
+        } else {
+            (g, g)
+        };
+        let lo = self.apply(op, f_lo, g_lo);
+        let hi = self.apply(op, f_hi, g_hi);
+        let x = self.mk(var, lo, hi);
+        self.cache.insert((op, f, g), x);
+        x
+    }
+
+    pub(crate) fn and(&mut self, f: BddRef, g: BddRef) -> BddRef {
+        self.apply(Op::And, f, g)
+    }
+
+    pub(crate) fn xor(&mut self, f: BddRef, g: BddRef) -> BddRef {
+        self.apply(Op::Xor, f, g)
+    }
+
+    pub(crate) fn not(&mut self, f: BddRef) -> BddRef {
+        self.apply(Op::Xor, f, BddRef::TRUE)
+    }
+
+    pub(crate) fn or(&mut self, f: BddRef, g: BddRef) -> BddRef {
+        let nf = self.not(f);
+        let ng = self.not(g);
+        let x = self.and(nf, ng);
+        self.not(x)
+    }
+
+    /// `if s { t } else { e }`
+    pub(crate) fn mux(&mut self, s: BddRef, t: BddRef, e: BddRef) -> BddRef {
+        let ns = self.not(s);
+        let a = self.and(s, t);
+        let b = self.and(ns, e);
+        self.or(a, b)
+    }
+
+    /// Evaluate `f` with the given variable values
+    pub(crate) fn eval(&self, mut f: BddRef, val: impl Fn(u32) -> bool) -> bool {
+        while f != BddRef::TRUE && f != BddRef::FALSE {
+            let node = self.nodes[f.0 as usize];
+            f = if val(node.var) { node.hi } else { node.lo };
+        }
+        f == BddRef::TRUE
+    }
+
+    /// Find one assignment making `f` true. Variables not listed can take any value.
+    pub(crate) fn sat_one(&self, mut f: BddRef) -> Option<Vec<(u32, bool)>> {
+        if f == BddRef::FALSE {
+            return None;
+        }
+        let mut ret = Vec::new();
+        while f != BddRef::TRUE {
+            let node = self.nodes[f.0 as usize];
+            if node.lo != BddRef::FALSE {
+                ret.push((node.var, false));
+                f = node.lo;
+            } else {
+                ret.push((node.var, true));
+                f = node.hi;
+            }
+        }
+        Some(ret)
+    }
+
+    /// The cofactors of `f` with respect to `var`, which must not be below the top of `f`
+    fn cofactors(&self, f: BddRef, var: u32) -> (BddRef, BddRef) {
+        let node = self.nodes[f.0 as usize];
+        if node.var == var {
+            (node.lo, node.hi)
+        } else {
+            (f, f)
+        }
+    }
+
+    /// An irredundant sum of products for `f`, found with the Minato-Morreale algorithm. Every
+    /// product is prime and none of them can be removed.
+    pub(crate) fn isop(&mut self, f: BddRef) -> Vec<Vec<(u32, bool)>> {
+        self.isop_between(f, f).0
+    }
+
+    /// A sum of products for some function that is true wherever `lower` is and false wherever
+    /// `upper` is, together with that function
+    fn isop_between(&mut self, lower: BddRef, upper: BddRef) -> (Vec<Vec<(u32, bool)>>, BddRef) {
+        if lower == BddRef::FALSE {
+            return (Vec::new(), BddRef::FALSE);
+        }
+        if upper == BddRef::TRUE {
+            return (vec![Vec::new()], BddRef::TRUE);
+        }
+        let var = self.nodes[lower.0 as usize].var.min(self.nodes[upper.0 as usize].var);
+        let (l0, l1) = self.cofactors(lower, var);
+        let (u0, u1) = self.cofactors(upper, var);
+
+        // Products that need `var` to be false, then true
+        let nu1 = self.not(u1);
+        let only0 = self.and(l0, nu1);
+        let (c0, f0) = self.isop_between(only0, u0);
+        let nu0 = self.not(u0);
+        let only1 = self.and(l1, nu0);
+        let (c1, f1) = self.isop_between(only1, u1);
+
+        // Products that do not need `var` for whatever is left
+        let nf0 = self.not(f0);
+        let rest0 = self.and(l0, nf0);
+        let nf1 = self.not(f1);
+        let rest1 = self.and(l1, nf1);
+        let rest = self.or(rest0, rest1);
+        let both = self.and(u0, u1);
+        let (c2, f2) = self.isop_between(rest, both);
+
+        let lo = self.or(f0, f2);
+        let hi = self.or(f1, f2);
+        let f = self.mk(var, lo, hi);
+
+        let mut cubes = Vec::with_capacity(c0.len() + c1.len() + c2.len());
+        for (c, val) in [(c0, false), (c1, true)] {
+            cubes.extend(c.into_iter().map(|mut x| {
+                x.insert(0, (var, val));
+                x
+            }));
+        }
+        cubes.extend(c2);
+        (cubes, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        let mut bdd = Bdd::new();
+        let a = bdd.var(0);
+        let b = bdd.var(1);
+
+        // De Morgan
+        let ab = bdd.and(a, b);
+        let nab = bdd.not(ab);
+        let na = bdd.not(a);
+        let nb = bdd.not(b);
+        let na_or_nb = bdd.or(na, nb);
+        assert_eq!(nab, na_or_nb);
+
+        let x = bdd.xor(a, a);
+        assert_eq!(x, BddRef::FALSE);
+
+        let sat = bdd.sat_one(ab).unwrap();
+        assert_eq!(sat, vec![(0, true), (1, true)]);
+        assert!(bdd.sat_one(BddRef::FALSE).is_none());
+
+        let a_or_b = bdd.or(a, b);
+        assert_eq!(bdd.isop(a_or_b), vec![vec![(0, true)], vec![(1, true)]]);
+        assert_eq!(bdd.isop(BddRef::TRUE), vec![Vec::new()]);
+        assert!(bdd.isop(BddRef::FALSE).is_empty());
+    }
+
+    #[test]
+    fn isop() {
+        let mut bdd = Bdd::new();
+        let v: Vec<BddRef> = (0..4).map(|i| bdd.var(i)).collect();
+
+        // a & b | a & !b & c | !a & c & d, which is a & b | a & c | c & d
+        let ab = bdd.and(v[0], v[1]);
+        let nb = bdd.not(v[1]);
+        let a_nb = bdd.and(v[0], nb);
+        let a_nb_c = bdd.and(a_nb, v[2]);
+        let na = bdd.not(v[0]);
+        let cd = bdd.and(v[2], v[3]);
+        let na_cd = bdd.and(na, cd);
+        let x = bdd.or(ab, a_nb_c);
+        let f = bdd.or(x, na_cd);
+
+        let mut cubes = bdd.isop(f);
+        cubes.sort();
+        assert_eq!(
+            cubes,
+            vec![vec![(0, true), (1, true)], vec![(0, true), (2, true)], vec![(2, true), (3, true)]]
+        );
+
+        // The cover has to be exactly `f`
+        for i in 0..16u32 {
+            let val = |var: u32| i & (1 << var) != 0;
+            let covered = cubes.iter().any(|c| c.iter().all(|&(var, x)| val(var) == x));
+            assert_eq!(covered, bdd.eval(f, val));
+        }
+
+        // Parity has no smaller cover than its minterms
+        let a_xor_b = bdd.xor(v[0], v[1]);
+        let x = bdd.xor(a_xor_b, v[2]);
+        assert_eq!(bdd.isop(x).len(), 4);
+    }
+}
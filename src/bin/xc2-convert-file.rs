@@ -1,9 +1,6 @@
 use std::{fs::File, process::ExitCode};
 
-use xc2bit::{
-    crbit::{CrbitReader, CrbitWriter},
-    jed::{JedReader, JedWriter},
-};
+use xc2bit::{crbit::CrbitWriter, jed::JedWriter};
 
 #[cfg(not(feature = "std"))]
 compile_error!("This binary requires the 'std' feature");
@@ -11,20 +8,56 @@ compile_error!("This binary requires the 'std' feature");
 fn main() -> ExitCode {
     let args = ::std::env::args_os().collect::<Vec<_>>();
 
-    if args.len() != 2 {
-        println!("Usage: {} file.crbit|file.jed", args[0].to_string_lossy());
+    let no_drc = args.iter().skip(2).any(|x| x == "--no-drc");
+    let strict = args.iter().skip(2).any(|x| x == "--strict");
+    if args.len() != 2 + no_drc as usize + strict as usize {
+        println!(
+            "Usage: {} file.crbit|file.jed [--no-drc] [--strict]",
+            args[0].to_string_lossy()
+        );
         return ExitCode::FAILURE;
     }
 
     let filename = &args[1];
     let inp = File::open(filename).unwrap();
 
-    if filename.as_encoded_bytes().ends_with(b".crbit") {
-        let bitstream = xc2bit::bitstream::Coolrunner2::read_crbit(inp).unwrap();
-        bitstream.write_jed(std::io::stdout()).unwrap();
+    let is_crbit = filename.as_encoded_bytes().ends_with(b".crbit");
+    let (bitstream, unknown_bits) = if is_crbit {
+        xc2bit::bitstream::Coolrunner2::read_crbit_with_warnings(inp).unwrap()
     } else {
-        let bitstream = xc2bit::bitstream::Coolrunner2::read_jed(inp).unwrap();
-        bitstream.write_crbit(std::io::stdout()).unwrap();
+        xc2bit::bitstream::Coolrunner2::read_jed_with_warnings(inp).unwrap()
+    };
+    for unknown_bit in &unknown_bits {
+        eprintln!("warning: {}", unknown_bit);
+    }
+
+    if !no_drc {
+        let report = bitstream.drc();
+        if !report.violations.is_empty() {
+            eprint!("{}", report);
+        }
+        if report.has_errors() {
+            eprintln!("Refusing to convert a bitstream with DRC errors (use --no-drc to override)");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let result = match (is_crbit, strict) {
+        (true, false) => bitstream.write_jed(stdout),
+        (true, true) => bitstream.write_jed_strict(stdout),
+        (false, false) => bitstream.write_crbit(stdout),
+        (false, true) => bitstream.write_crbit_strict(stdout),
+    };
+    match result {
+        Ok(()) => {}
+        // Only the strict writers refuse to write
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            eprint!("{}", e);
+            eprintln!("Refusing to convert a bitstream with uncertain encodings (--strict)");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => panic!("{}", e),
     }
 
     ExitCode::SUCCESS
@@ -0,0 +1,52 @@
+use std::{fs::File, process::ExitCode};
+
+use xc2bit::{
+    bitstream::Coolrunner2,
+    crbit::CrbitReader,
+    diff::diff,
+    jed::JedReader,
+};
+
+#[cfg(not(all(feature = "std", feature = "serde")))]
+compile_error!("This binary requires the 'std' and 'serde' features");
+
+fn read(filename: &std::ffi::OsStr) -> Coolrunner2<bitvec::boxed::BitBox> {
+    let inp = File::open(filename).unwrap();
+    if filename.as_encoded_bytes().ends_with(b".crbit") {
+        Coolrunner2::read_crbit(inp).unwrap()
+    } else {
+        Coolrunner2::read_jed(inp).unwrap()
+    }
+}
+
+fn main() -> ExitCode {
+    let args = ::std::env::args_os().collect::<Vec<_>>();
+
+    let json = args.len() == 4 && args[3] == "--json";
+    if args.len() != 3 && !json {
+        println!(
+            "Usage: {} old.crbit|old.jed new.crbit|new.jed [--json]",
+            args[0].to_string_lossy()
+        );
+        return ExitCode::from(2);
+    }
+
+    let a = read(&args[1]);
+    let b = read(&args[2]);
+    let changes = diff(&a, &b);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+    } else {
+        for change in &changes {
+            println!("{}", change);
+        }
+    }
+
+    // Same convention as diff(1)
+    if changes.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -1,6 +1,6 @@
 use std::{fs::File, process::ExitCode};
 
-use xc2bit::{crbit::CrbitReader, jed::JedReader};
+use xc2bit::equations::{EquationOptions, EquationSyntax};
 
 #[cfg(not(feature = "std"))]
 compile_error!("This binary requires the 'std' feature");
@@ -8,19 +8,48 @@ compile_error!("This binary requires the 'std' feature");
 fn main() -> ExitCode {
     let args = ::std::env::args_os().collect::<Vec<_>>();
 
-    if args.len() != 2 {
-        println!("Usage: {} file.crbit", args[0].to_string_lossy());
+    if args.len() != 2 && args.len() != 3 {
+        println!(
+            "Usage: {} file.crbit|file.jed [--equations[=native|abel|cupl]]",
+            args[0].to_string_lossy()
+        );
         return ExitCode::FAILURE;
     }
 
+    let equations = if args.len() == 3 {
+        match args[2].to_string_lossy().as_ref() {
+            "--equations" | "--equations=native" => Some(EquationSyntax::Native),
+            "--equations=abel" => Some(EquationSyntax::Abel),
+            "--equations=cupl" => Some(EquationSyntax::Cupl),
+            _ => {
+                println!("Unknown option {}", args[2].to_string_lossy());
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        None
+    };
+
     let filename = &args[1];
     let inp = File::open(filename).unwrap();
-    let bitstream = if filename.as_encoded_bytes().ends_with(b".crbit") {
-        xc2bit::bitstream::Coolrunner2::read_crbit(inp).unwrap()
+    let (bitstream, unknown_bits) = if filename.as_encoded_bytes().ends_with(b".crbit") {
+        xc2bit::bitstream::Coolrunner2::read_crbit_with_warnings(inp).unwrap()
     } else {
-        xc2bit::bitstream::Coolrunner2::read_jed(inp).unwrap()
+        xc2bit::bitstream::Coolrunner2::read_jed_with_warnings(inp).unwrap()
     };
-    bittwiddler_textfile::write(std::io::stdout(), &bitstream).unwrap();
+    for unknown_bit in &unknown_bits {
+        eprintln!("warning: {}", unknown_bit);
+    }
+
+    if let Some(syntax) = equations {
+        let options = EquationOptions {
+            syntax,
+            ..Default::default()
+        };
+        print!("{}", bitstream.netlist().equations(&options));
+    } else {
+        bittwiddler_textfile::write(std::io::stdout(), &bitstream).unwrap();
+    }
 
     ExitCode::SUCCESS
 }
@@ -0,0 +1,53 @@
+use std::process::ExitCode;
+
+use bittwiddler_core::prelude::Coordinate;
+use xc2bit::{fuse_map::FuseMap, partdb::XC2Device};
+
+#[cfg(not(feature = "std"))]
+compile_error!("This binary requires the 'std' feature");
+
+fn main() -> ExitCode {
+    let args = ::std::env::args_os().collect::<Vec<_>>();
+
+    if args.len() < 3 {
+        println!(
+            "Usage: {} <device> <x>,<y>|<jed fuse index>...",
+            args[0].to_string_lossy()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let Ok(device) = XC2Device::try_from(args[1].to_string_lossy().as_ref()) else {
+        println!("Unknown device {}", args[1].to_string_lossy());
+        return ExitCode::FAILURE;
+    };
+    let map = FuseMap::new(device);
+
+    let mut ret = ExitCode::SUCCESS;
+    for arg in &args[2..] {
+        let arg = arg.to_string_lossy();
+        let result = if let Some((x, y)) = arg.split_once(',') {
+            match (x.trim().parse(), y.trim().parse()) {
+                (Ok(x), Ok(y)) => {
+                    let c = Coordinate::new(x, y);
+                    map.owner(c).map(|owner| (c, owner))
+                }
+                _ => None,
+            }
+        } else if let Ok(jed_idx) = arg.parse() {
+            map.owner_of_jed_index(jed_idx)
+        } else {
+            None
+        };
+
+        match result {
+            Some((c, owner)) => println!("{}: ({}, {}) {}", arg, c.x, c.y, owner),
+            None => {
+                println!("{}: not a fuse of {}", arg, device);
+                ret = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ret
+}
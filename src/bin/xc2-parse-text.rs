@@ -8,9 +8,12 @@ compile_error!("This binary requires the 'std' feature");
 fn main() -> ExitCode {
     let args = ::std::env::args_os().collect::<Vec<_>>();
 
+    let strict = args.last().is_some_and(|x| x == "--strict");
+    let args = &args[..args.len() - strict as usize];
+
     if args.len() < 3 {
         println!(
-            "Usage: {} xc2cXXX-XXX-XXX file.txt [jed]",
+            "Usage: {} xc2cXXX-XXX-XXX file.txt [jed] [--strict]",
             args[0].to_string_lossy()
         );
         return ExitCode::FAILURE;
@@ -24,10 +27,23 @@ fn main() -> ExitCode {
     let mut bitstream = xc2bit::bitstream::Coolrunner2::new(part);
     bittwiddler_textfile::parse(inp, &mut bitstream).unwrap();
 
-    if args.len() < 4 || args[3] != "jed" {
-        bitstream.write_crbit(std::io::stdout()).unwrap();
-    } else {
-        bitstream.write_jed(std::io::stdout()).unwrap();
+    let jed = args.len() >= 4 && args[3] == "jed";
+    let stdout = std::io::stdout();
+    let result = match (jed, strict) {
+        (false, false) => bitstream.write_crbit(stdout),
+        (false, true) => bitstream.write_crbit_strict(stdout),
+        (true, false) => bitstream.write_jed(stdout),
+        (true, true) => bitstream.write_jed_strict(stdout),
+    };
+    match result {
+        Ok(()) => {}
+        // Only the strict writers refuse to write
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            eprint!("{}", e);
+            eprintln!("Refusing to write a bitstream with uncertain encodings (--strict)");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => panic!("{}", e),
     }
 
     ExitCode::SUCCESS
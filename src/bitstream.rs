@@ -82,45 +82,9 @@ impl<B: BitHolder> Coolrunner2<B> {
         let fuse_dims = self.part.device.fuse_array_dims();
 
         // Clear transfer bits
-        match self.part.device {
-            XC2Device::XC2C32 | XC2Device::XC2C32A => {
-                for y in 0..fuse_dims.1 - 2 {
-                    self.set(Coordinate::new(0, y), false);
-                    self.set(Coordinate::new(259, y), false);
-                }
-            }
-            XC2Device::XC2C64 | XC2Device::XC2C64A => {}
-            XC2Device::XC2C128 => {
-                for y in 0..fuse_dims.1 - 2 {
-                    self.set(Coordinate::new(0, y), false);
-                    self.set(Coordinate::new(375, y), false);
-                    self.set(Coordinate::new(376, y), false);
-                    self.set(Coordinate::new(751, y), false);
-                }
-            }
-            XC2Device::XC2C256 => {
-                for y in 0..fuse_dims.1 - 2 {
-                    self.set(Coordinate::new(0, y), false);
-                    self.set(Coordinate::new(681, y), false);
-                    self.set(Coordinate::new(682, y), false);
-                    self.set(Coordinate::new(1363, y), false);
-                }
-            }
-            XC2Device::XC2C384 => {
-                for y in 0..fuse_dims.1 - 2 {
-                    self.set(Coordinate::new(0, y), false);
-                    self.set(Coordinate::new(933, y), false);
-                    self.set(Coordinate::new(934, y), false);
-                    self.set(Coordinate::new(1867, y), false);
-                }
-            }
-            XC2Device::XC2C512 => {
-                for y in 0..fuse_dims.1 - 2 {
-                    self.set(Coordinate::new(0, y), false);
-                    self.set(Coordinate::new(989, y), false);
-                    self.set(Coordinate::new(990, y), false);
-                    self.set(Coordinate::new(1979, y), false);
-                }
+        for &x in self.part.device.transfer_columns() {
+            for y in 0..fuse_dims.1 - 2 {
+                self.set(Coordinate::new(x, y), false);
             }
         }
 
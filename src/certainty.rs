@@ -0,0 +1,146 @@
+//! Settings whose bit encoding has not been confirmed
+//!
+//! The decoded view of a bitstream is only as good as the encodings it is based on. This lists
+//! every place in a bitstream that relies on an encoding which is believed but not known to be
+//! correct, so that hardware behaving differently from the decoded view does not come as a
+//! surprise.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::{crbit::CrbitWriter, jed::JedWriter};
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    zia::ZIARow,
+    ZIA_ROWS,
+};
+
+/// A setting that uses an uncertain encoding
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum UncertainEncoding {
+    ZIARow { fb: u8, row: u8, setting: ZIARow },
+}
+impl Display for UncertainEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UncertainEncoding::ZIARow { fb, row, setting } => {
+                let setting = match setting {
+                    ZIARow::GND => "GND",
+                    ZIARow::VCC => "VCC",
+                    ZIARow::MuxChoice(_) => "mux choice",
+                    ZIARow::Invalid(_) => "invalid",
+                };
+                write!(
+                    f,
+                    "FB{} ZIA row {} is set to {}, which has an uncertain encoding",
+                    fb, row, setting
+                )
+            }
+        }
+    }
+}
+
+/// Returned by [Coolrunner2::check_certain] when any setting uses an uncertain encoding
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UncertainEncodingsError(pub Vec<UncertainEncoding>);
+impl Display for UncertainEncodingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for x in &self.0 {
+            writeln!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for UncertainEncodingsError {}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Find every setting that relies on an encoding that has not been confirmed
+    pub fn uncertain_encodings(&self) -> Vec<UncertainEncoding> {
+        let device = self.part.device;
+        let mut ret = Vec::new();
+
+        for fb in 0..device.num_fbs() as u8 {
+            for row in 0..ZIA_ROWS as u8 {
+                let setting = self.get_prop(&self.fb(fb).zia_row(row));
+                // Invalid rows are not an encoding at all and are reported by the DRC instead
+                if !matches!(setting, ZIARow::Invalid(_)) && !setting.is_certain(device) {
+                    ret.push(UncertainEncoding::ZIARow { fb, row, setting });
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Fail if any setting relies on an encoding that has not been confirmed
+    pub fn check_certain(&self) -> Result<(), UncertainEncodingsError> {
+        let uncertain = self.uncertain_encodings();
+        if uncertain.is_empty() {
+            Ok(())
+        } else {
+            Err(UncertainEncodingsError(uncertain))
+        }
+    }
+
+    /// Like [JedWriter::write_jed], but refuses to write a bitstream that fails
+    /// [Coolrunner2::check_certain]
+    #[cfg(feature = "std")]
+    pub fn write_jed_strict<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        self.check_certain()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.write_jed(w)
+    }
+
+    /// Like [CrbitWriter::write_crbit], but refuses to write a bitstream that fails
+    /// [Coolrunner2::check_certain]
+    #[cfg(feature = "std")]
+    pub fn write_crbit_strict<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        self.check_certain()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.write_crbit(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partdb::{XC2Device, XC2Part};
+
+    #[test]
+    fn zia_gnd() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C64A, None, None).unwrap());
+        assert_eq!(bitstream.uncertain_encodings(), []);
+
+        assert_eq!(bitstream.check_certain(), Ok(()));
+
+        bitstream.set_prop(&bitstream.fb(2).zia_row(7), ZIARow::GND);
+        let uncertain = [UncertainEncoding::ZIARow {
+            fb: 2,
+            row: 7,
+            setting: ZIARow::GND,
+        }];
+        assert_eq!(bitstream.uncertain_encodings(), uncertain);
+        assert_eq!(
+            bitstream.check_certain(),
+            Err(UncertainEncodingsError(uncertain.to_vec()))
+        );
+        #[cfg(feature = "std")]
+        {
+            let mut jed = Vec::new();
+            assert!(bitstream.write_jed_strict(&mut jed).is_err());
+            assert!(jed.is_empty());
+        }
+
+        // Known on the smallest device
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.fb(0).zia_row(0), ZIARow::GND);
+        assert_eq!(bitstream.uncertain_encodings(), []);
+    }
+}
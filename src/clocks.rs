@@ -0,0 +1,398 @@
+//! Clock domain and control signal analysis
+//!
+//! Every used register is assigned to a clock domain made of its clock source, polarity and DDR
+//! mode. Data paths are then followed backwards from each register to the registers feeding it,
+//! and paths between different domains are reported unless they look like a synchroniser.
+//!
+//! A crossing is considered synchronised when the receiving register takes the sending register
+//! directly, with no other registers or pads in its input logic, and the receiving register in
+//! turn feeds at least one register in its own domain that also has nothing else in its input
+//! logic. This is the usual two flip-flop synchroniser. Pads are not part of any domain.
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    fb,
+    mc::FlipFlopMode,
+    netlist::{Netlist, NodeId, NodeRef},
+    MCS_PER_FB,
+};
+
+/// Where the clock of a register comes from
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum ClockSource {
+    GlobalClock(u8),
+    /// GCK2 through the clock divider
+    DividedClock { divisor: u32, delay: bool },
+    /// A global clock that is not enabled. The register never sees an edge.
+    DisabledGlobalClock(u8),
+    /// Product term C of the macrocell
+    ProductTerm { fb: u8, mc: u8 },
+    /// The function block's control term
+    ControlTerm { fb: u8 },
+}
+impl Display for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockSource::GlobalClock(i) => write!(f, "GCK{i}"),
+            ClockSource::DividedClock { divisor, delay } => {
+                write!(f, "GCK2/{divisor}")?;
+                if *delay {
+                    write!(f, " (delayed)")?;
+                }
+                Ok(())
+            }
+            ClockSource::DisabledGlobalClock(i) => write!(f, "GCK{i} (disabled)"),
+            ClockSource::ProductTerm { fb, mc } => write!(f, "FB{fb} MC{mc} PTC"),
+            ClockSource::ControlTerm { fb } => write!(f, "FB{fb} CTC"),
+        }
+    }
+}
+
+/// Registers in the same clock domain change state at the same time
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct ClockDomain {
+    pub source: ClockSource,
+    /// Clocked on the falling edge, or transparent while the clock is low for latches
+    pub inverted: bool,
+    pub ddr: bool,
+}
+impl Display for ClockDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+        if self.ddr {
+            write!(f, " both edges")
+        } else if self.inverted {
+            write!(f, " falling")
+        } else {
+            write!(f, " rising")
+        }
+    }
+}
+
+/// Source of an asynchronous set or reset
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum AsyncControl {
+    /// Product term A of the macrocell
+    ProductTerm { fb: u8, mc: u8 },
+    /// The global set/reset net. `invert` is the setting of `gsr_invert`.
+    GlobalSetReset { enabled: bool, invert: bool },
+    /// The function block's CTS
+    ControlSet { fb: u8 },
+    /// The function block's CTR
+    ControlReset { fb: u8 },
+}
+impl Display for AsyncControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsyncControl::ProductTerm { fb, mc } => write!(f, "FB{fb} MC{mc} PTA"),
+            AsyncControl::GlobalSetReset { enabled, invert } => {
+                write!(f, "GSR")?;
+                if *invert {
+                    write!(f, " (active low)")?;
+                }
+                if !*enabled {
+                    write!(f, " (disabled)")?;
+                }
+                Ok(())
+            }
+            AsyncControl::ControlSet { fb } => write!(f, "FB{fb} CTS"),
+            AsyncControl::ControlReset { fb } => write!(f, "FB{fb} CTR"),
+        }
+    }
+}
+
+/// Clocking and control signals of one register
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RegisterClocking {
+    pub fb: u8,
+    pub mc: u8,
+    pub mode: FlipFlopMode,
+    pub domain: ClockDomain,
+    /// Product term C when in [FlipFlopMode::DFFCE]
+    pub clock_enable: Option<NodeId>,
+    pub set: Option<AsyncControl>,
+    pub reset: Option<AsyncControl>,
+}
+
+/// Data going from a register in one domain to a register in another
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct DomainCrossing {
+    /// (fb, mc) of the sending register
+    pub from: (u8, u8),
+    pub from_domain: ClockDomain,
+    /// (fb, mc) of the receiving register
+    pub to: (u8, u8),
+    pub to_domain: ClockDomain,
+    pub synchronised: bool,
+}
+impl Display for DomainCrossing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FB{} MC{} ({}) -> FB{} MC{} ({})",
+            self.from.0, self.from.1, self.from_domain, self.to.0, self.to.1, self.to_domain
+        )?;
+        if !self.synchronised {
+            write!(f, " without a synchroniser")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ClockReport {
+    /// Every used register
+    pub registers: Vec<RegisterClocking>,
+    /// The registers in each domain, as (fb, mc)
+    pub domains: BTreeMap<ClockDomain, Vec<(u8, u8)>>,
+    pub crossings: Vec<DomainCrossing>,
+}
+impl ClockReport {
+    /// Crossings that are not synchronised
+    pub fn unsynchronised_crossings(&self) -> impl Iterator<Item = &DomainCrossing> {
+        self.crossings.iter().filter(|x| !x.synchronised)
+    }
+}
+impl Display for ClockReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (domain, regs) in &self.domains {
+            write!(f, "{}:", domain)?;
+            for (fb, mc) in regs {
+                write!(f, " FB{fb} MC{mc}")?;
+            }
+            writeln!(f)?;
+        }
+        for reg in &self.registers {
+            if reg.clock_enable.is_none() && reg.set.is_none() && reg.reset.is_none() {
+                continue;
+            }
+            write!(f, "FB{} MC{}", reg.fb, reg.mc)?;
+            if reg.clock_enable.is_some() {
+                write!(f, " clock enable PTC")?;
+            }
+            if let Some(x) = reg.set {
+                write!(f, " set {}", x)?;
+            }
+            if let Some(x) = reg.reset {
+                write!(f, " reset {}", x)?;
+            }
+            writeln!(f)?;
+        }
+        for x in &self.crossings {
+            writeln!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl Netlist {
+    fn async_control(&self, id: NodeId, fb: u8, mc: u8) -> AsyncControl {
+        match id {
+            NodeId::GlobalSetReset => AsyncControl::GlobalSetReset {
+                enabled: self.gsr.enabled,
+                invert: self.gsr.invert,
+            },
+            NodeId::AndTerm { pterm: fb::CTS, .. } => AsyncControl::ControlSet { fb },
+            NodeId::AndTerm { pterm: fb::CTR, .. } => AsyncControl::ControlReset { fb },
+            _ => AsyncControl::ProductTerm { fb, mc },
+        }
+    }
+
+    fn register_clocking(&self, fb: u8, mc: u8) -> RegisterClocking {
+        let reg = &self.fbs[fb as usize].registers[mc as usize];
+        let source = match reg.clk {
+            NodeId::GlobalClock(i) => {
+                let gck = &self.gck[i as usize];
+                if !gck.enabled {
+                    ClockSource::DisabledGlobalClock(i)
+                } else if let Some(div) = gck.divider {
+                    ClockSource::DividedClock {
+                        divisor: div.ratio.divisor(),
+                        delay: div.delay,
+                    }
+                } else {
+                    ClockSource::GlobalClock(i)
+                }
+            }
+            NodeId::AndTerm { pterm, .. } if pterm == fb::CTC => ClockSource::ControlTerm { fb },
+            _ => ClockSource::ProductTerm { fb, mc },
+        };
+        RegisterClocking {
+            fb,
+            mc,
+            mode: reg.mode,
+            domain: ClockDomain {
+                source,
+                inverted: reg.clk_inv,
+                ddr: reg.is_ddr,
+            },
+            clock_enable: reg.ce,
+            set: reg.set.map(|x| self.async_control(x, fb, mc)),
+            reset: reg.reset.map(|x| self.async_control(x, fb, mc)),
+        }
+    }
+
+    /// Registers and pads that the data inputs of a register depend on.
+    /// The search stops at registers, so only the nearest ones are found.
+    fn register_sources(&self, fb: u8, mc: u8) -> BTreeSet<NodeId> {
+        let reg = &self.fbs[fb as usize].registers[mc as usize];
+        let mut stack: Vec<NodeId> = [reg.d].into_iter().chain(reg.ce).collect();
+        let mut seen = BTreeSet::new();
+        let mut ret = BTreeSet::new();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            match self.node(id) {
+                Some(NodeRef::Register(_))
+                | Some(NodeRef::IoPad(_))
+                | Some(NodeRef::DedicatedInput(_)) => {
+                    ret.insert(id);
+                }
+                _ => stack.extend(self.fanin(id)),
+            }
+        }
+        ret
+    }
+
+    /// Derive clocks and control signals of every used register and find domain crossings
+    pub fn clock_report(&self) -> ClockReport {
+        let mut registers = Vec::new();
+        let mut domains: BTreeMap<ClockDomain, Vec<(u8, u8)>> = BTreeMap::new();
+        let mut by_loc = BTreeMap::new();
+        for fb in 0..self.fbs.len() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                if self.reg_is_used(fb, mc) {
+                    let x = self.register_clocking(fb, mc);
+                    registers.push(x);
+                    domains.entry(x.domain).or_default().push((fb, mc));
+                    by_loc.insert((fb, mc), x.domain);
+                }
+            }
+        }
+
+        let sources: BTreeMap<(u8, u8), BTreeSet<NodeId>> = by_loc
+            .keys()
+            .map(|&(fb, mc)| ((fb, mc), self.register_sources(fb, mc)))
+            .collect();
+        let only_source = |loc: (u8, u8), src: (u8, u8)| {
+            let x = &sources[&loc];
+            x.len() == 1
+                && x.contains(&NodeId::Register {
+                    fb: src.0,
+                    mc: src.1,
+                })
+        };
+
+        let mut crossings = Vec::new();
+        for (&to, &to_domain) in &by_loc {
+            for &src in &sources[&to] {
+                let NodeId::Register { fb, mc } = src else {
+                    continue;
+                };
+                let from = (fb, mc);
+                let Some(&from_domain) = by_loc.get(&from) else {
+                    continue;
+                };
+                if from_domain == to_domain {
+                    continue;
+                }
+
+                let synchronised = only_source(to, from)
+                    && by_loc
+                        .iter()
+                        .any(|(&next, &d)| d == to_domain && next != to && only_source(next, to));
+                crossings.push(DomainCrossing {
+                    from,
+                    from_domain,
+                    to,
+                    to_domain,
+                    synchronised,
+                });
+            }
+        }
+
+        ClockReport {
+            registers,
+            domains,
+            crossings,
+        }
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// See [Netlist::clock_report]
+    pub fn clock_report(&self) -> ClockReport {
+        self.netlist().clock_report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mc::{MacrocellFeedbackSrc, RegClkSrc, RegResetSrc, RegSetSrc, XorMode},
+        partdb::{XC2Device, XC2Part},
+        zia::{ZIARow, ZIATableEntry},
+    };
+
+    #[test]
+    fn crossings() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        assert_eq!(bitstream.clock_report().registers, []);
+
+        // MC1 and MC2 are a two flip-flop synchroniser, MC3 uses MC0 directly
+        for (mc, src, clk) in [
+            (0, None, RegClkSrc::GCK0),
+            (1, Some(0), RegClkSrc::GCK1),
+            (2, Some(1), RegClkSrc::GCK1),
+            (3, Some(0), RegClkSrc::GCK1),
+        ] {
+            if let Some(src) = src {
+                let x = XC2Device::XC2C32A
+                    .zia_rows_for(ZIATableEntry::Macrocell { fb: 0, mc: src })
+                    .iter()
+                    .find(|x| bitstream.get_prop(&bitstream.fb(0).zia_row(x.row)) == ZIARow::VCC)
+                    .unwrap();
+                bitstream.set_prop(&bitstream.fb(0).zia_row(x.row), ZIARow::MuxChoice(x.choice));
+                bitstream.set_prop(&bitstream.fb(0).and_term(fb::ptb(mc)).inp(x.row), true);
+                bitstream.set_prop(&bitstream.fb(0).or_term(mc).inp(fb::ptb(mc)), true);
+            }
+            let acc = bitstream.fb(0).mc(mc);
+            bitstream.set_prop(&acc.xor_mode(), XorMode::_0);
+            bitstream.set_prop(&acc.use_iob(), false);
+            bitstream.set_prop(&acc.clk_src(), clk);
+            bitstream.set_prop(&acc.fb_src(), MacrocellFeedbackSrc::FlipFlop);
+        }
+
+        let report = bitstream.clock_report();
+        assert_eq!(report.registers.len(), 4);
+        assert_eq!(report.domains.len(), 2);
+        assert_eq!(report.crossings.len(), 2);
+        let unsync: Vec<_> = report.unsynchronised_crossings().collect();
+        assert_eq!(unsync.len(), 1);
+        assert_eq!(unsync[0].from, (0, 0));
+        assert_eq!(unsync[0].to, (0, 3));
+    }
+
+    #[test]
+    fn control_terms() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let acc = bitstream.fb(1).mc(4);
+        bitstream.set_prop(&acc.fb_src(), MacrocellFeedbackSrc::FlipFlop);
+        bitstream.set_prop(&acc.s_src(), RegSetSrc::CTS);
+        bitstream.set_prop(&acc.r_src(), RegResetSrc::CTR);
+
+        let report = bitstream.clock_report();
+        assert_eq!(report.registers.len(), 1);
+        assert_eq!(report.registers[0].set, Some(AsyncControl::ControlSet { fb: 1 }));
+        assert_eq!(report.registers[0].reset, Some(AsyncControl::ControlReset { fb: 1 }));
+        assert!(report.to_string().contains("FB1 MC4 set FB1 CTS reset FB1 CTR\n"));
+    }
+}
@@ -14,6 +14,7 @@ use std::vec::Vec;
 use bitvec::prelude::*;
 
 use crate::bitstream::{BitHolder, Coolrunner2};
+use crate::extra_bits::UnknownBitSet;
 use crate::partdb::{XC2Device, XC2Part};
 
 #[derive(Debug)]
@@ -80,6 +81,17 @@ pub trait CrbitReader {
     where
         Self: Sized;
 }
+impl Coolrunner2<BitBox> {
+    /// Like [CrbitReader::read_crbit], but also returns the unknown bits that are set.
+    /// See [Coolrunner2::audit_unknown_bits].
+    pub fn read_crbit_with_warnings<R: io::Read>(
+        r: R,
+    ) -> Result<(Self, Vec<UnknownBitSet>), CrbitReadError> {
+        let bitstream = Self::read_crbit(r)?;
+        let warnings = bitstream.audit_unknown_bits();
+        Ok((bitstream, warnings))
+    }
+}
 impl CrbitReader for Coolrunner2<BitBox> {
     fn read_crbit<R: io::Read>(mut r: R) -> Result<Self, CrbitReadError>
     where
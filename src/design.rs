@@ -0,0 +1,1068 @@
+//! High-level design description and builder
+//!
+//! A [Design] declares logic at macrocell granularity: the sum-of-products feeding each
+//! macrocell, how its register is clocked and reset, and how its pad is driven. Signals are
+//! referred to by name, and [Design::build] works out the ZIA selections, product terms and OR
+//! connections needed to implement them.
+//!
+//! Every input and macrocell must be given a location before building. Pin names depend on the
+//! package, which this crate knows nothing about, so locations are function block and macrocell
+//! numbers. For the same reason, the global clock, set/reset and tristate nets are used by index
+//! and it is up to the user to connect the right package pins to them.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use bitvec::boxed::BitBox;
+
+use crate::{
+    bitstream::Coolrunner2,
+    global_bits_code::GlobalTermination,
+    io::{IoFeedbackSource, IoVoltage, OutputMode, PinOutputSrc, SlewRate},
+    mc::{FlipFlopMode, MacrocellFeedbackSrc, RegClkSrc, RegResetSrc, RegSetSrc, XorMode},
+    partdb::{XC2Device, XC2Part},
+    pterms::{allocate_pterms, AndTerm, FunctionBlockTerms, PTermAllocation},
+    zia::ZIATableEntry,
+    zia_router::{route_zia, ZIARouteError, ZIARouting},
+    ANDTERMS_PER_FB, MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS,
+};
+
+/// A signal or its complement
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Literal {
+    pub signal: String,
+    pub negated: bool,
+}
+impl Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", self.signal)
+    }
+}
+
+/// The AND of some literals. An empty product term is constant one.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+pub struct ProductTerm(pub Vec<Literal>);
+impl ProductTerm {
+    /// Parse a single product term, e.g. `a & !b`
+    pub fn parse(s: &str) -> Result<Self, ExprParseError> {
+        let sop = SumOfProducts::parse(s)?;
+        match <[ProductTerm; 1]>::try_from(sop.0) {
+            Ok([x]) => Ok(x),
+            Err(_) => Err(ExprParseError {
+                pos: s.find('|').unwrap_or(0),
+                kind: ExprParseErrorKind::NotAProductTerm,
+            }),
+        }
+    }
+}
+impl Display for ProductTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, lit) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " & ")?;
+            }
+            write!(f, "{}", lit)?;
+        }
+        Ok(())
+    }
+}
+
+/// The OR of some product terms. An empty sum is constant zero.
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct SumOfProducts(pub Vec<ProductTerm>);
+impl SumOfProducts {
+    /// Parse an expression like `a & !b | c`. `0` and `1` are accepted as the entire expression.
+    pub fn parse(s: &str) -> Result<Self, ExprParseError> {
+        let trimmed = s.trim();
+        if trimmed == "0" {
+            return Ok(Self(Vec::new()));
+        }
+        if trimmed == "1" {
+            return Ok(Self(alloc::vec![ProductTerm::default()]));
+        }
+
+        let is_name_char = |c: char| !c.is_whitespace() && !"&|!()".contains(c);
+        let err = |pos, kind| Err(ExprParseError { pos, kind });
+
+        let mut terms = Vec::new();
+        let mut lits = Vec::new();
+        let mut negated = false;
+        // Whether a literal is expected next, as opposed to an operator
+        let mut want_literal = true;
+        let mut chars = s.char_indices().peekable();
+        while let Some(&(pos, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if want_literal && c == '!' {
+                negated = !negated;
+                chars.next();
+            } else if want_literal && is_name_char(c) {
+                let mut end = pos;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !is_name_char(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                lits.push(Literal {
+                    signal: s[pos..end].to_string(),
+                    negated,
+                });
+                negated = false;
+                want_literal = false;
+            } else if !want_literal && (c == '&' || c == '|') {
+                if c == '|' {
+                    terms.push(ProductTerm(core::mem::take(&mut lits)));
+                }
+                want_literal = true;
+                chars.next();
+            } else if want_literal {
+                return err(pos, ExprParseErrorKind::ExpectedSignal);
+            } else {
+                return err(pos, ExprParseErrorKind::ExpectedOperator);
+            }
+        }
+        if want_literal {
+            return err(s.len(), ExprParseErrorKind::ExpectedSignal);
+        }
+        terms.push(ProductTerm(lits));
+        Ok(Self(terms))
+    }
+
+    /// Names of all signals used
+    pub fn signals(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().flat_map(|x| x.0.iter().map(|x| x.signal.as_str()))
+    }
+}
+impl Display for SumOfProducts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, term) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}", term)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExprParseErrorKind {
+    ExpectedSignal,
+    ExpectedOperator,
+    /// An OR was found where only a single product term is allowed
+    NotAProductTerm,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ExprParseError {
+    /// Byte offset in the input
+    pub pos: usize,
+    pub kind: ExprParseErrorKind,
+}
+impl Display for ExprParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ExprParseErrorKind::ExpectedSignal => write!(f, "expected a signal")?,
+            ExprParseErrorKind::ExpectedOperator => write!(f, "expected & or |")?,
+            ExprParseErrorKind::NotAProductTerm => write!(f, "expected a single product term")?,
+        }
+        write!(f, " at offset {}", self.pos)
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for ExprParseError {}
+
+/// IO standards supported without VREF
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum IoStandard {
+    LVTTL,
+    LVCMOS33,
+    LVCMOS25,
+    LVCMOS18,
+    LVCMOS15,
+}
+impl IoStandard {
+    /// The bank voltage setting needed for this standard
+    pub fn voltage(self) -> IoVoltage {
+        match self {
+            IoStandard::LVTTL | IoStandard::LVCMOS33 | IoStandard::LVCMOS25 => IoVoltage::High,
+            IoStandard::LVCMOS18 | IoStandard::LVCMOS15 => IoVoltage::Low,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum InputLoc {
+    Pad { fb: u8, mc: u8 },
+    /// The input-only pin on the XC2C32(A)
+    DedicatedInput,
+}
+
+/// A pin used as an input
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InputDef {
+    pub name: String,
+    pub loc: Option<InputLoc>,
+    pub standard: Option<IoStandard>,
+    /// Enable the weak pull-up or bus keeper selected by [Design::global_termination]
+    pub termination: bool,
+}
+impl InputDef {
+    pub fn new(name: &str, loc: InputLoc) -> Self {
+        Self {
+            name: name.to_string(),
+            loc: Some(loc),
+            standard: None,
+            termination: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClockInput {
+    Global(u8),
+    ProductTerm(ProductTerm),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AsyncInput {
+    GlobalSetReset,
+    ProductTerm(ProductTerm),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RegisterDef {
+    pub mode: FlipFlopMode,
+    pub clock: ClockInput,
+    /// Clock on the falling edge, or be transparent while the clock is low for latches
+    pub clock_invert: bool,
+    pub ddr: bool,
+    /// Only allowed, and required, in [FlipFlopMode::DFFCE]
+    pub clock_enable: Option<ProductTerm>,
+    pub set: Option<AsyncInput>,
+    pub reset: Option<AsyncInput>,
+    pub init_state: bool,
+}
+impl RegisterDef {
+    /// A D flip-flop without set or reset that powers up as 0
+    pub fn dff(clock: ClockInput) -> Self {
+        Self {
+            mode: FlipFlopMode::DFF,
+            clock,
+            clock_invert: false,
+            ddr: false,
+            clock_enable: None,
+            set: None,
+            reset: None,
+            init_state: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OutputEnableDef {
+    Always,
+    /// Only ever drive low
+    OpenDrain,
+    GlobalTristate(u8),
+    ProductTerm(ProductTerm),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OutputDef {
+    /// Output the register rather than the combinational logic
+    pub registered: bool,
+    pub enable: OutputEnableDef,
+    pub slew: SlewRate,
+    pub standard: Option<IoStandard>,
+    /// Enable the weak pull-up or bus keeper selected by [Design::global_termination]
+    pub termination: bool,
+    /// Name for the value read back from the pad, which makes this a bidirectional pin
+    pub feedback: Option<String>,
+}
+impl OutputDef {
+    pub fn new(registered: bool) -> Self {
+        Self {
+            registered,
+            enable: OutputEnableDef::Always,
+            slew: SlewRate::Fast,
+            standard: None,
+            termination: false,
+            feedback: None,
+        }
+    }
+}
+
+/// The logic in one macrocell
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MacrocellDef {
+    /// Name of the output of the macrocell. This is the register if there is one, and the
+    /// combinational logic otherwise.
+    pub name: String,
+    /// (fb, mc)
+    pub loc: Option<(u8, u8)>,
+    pub logic: SumOfProducts,
+    /// Invert the output of the OR term
+    pub invert: bool,
+    pub register: Option<RegisterDef>,
+    pub output: Option<OutputDef>,
+}
+impl MacrocellDef {
+    pub fn new(name: &str, loc: (u8, u8), logic: SumOfProducts) -> Self {
+        Self {
+            name: name.to_string(),
+            loc: Some(loc),
+            logic,
+            invert: false,
+            register: None,
+            output: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DesignError {
+    /// The input or macrocell has no location
+    Unplaced(String),
+    /// The location does not exist, or has no pad where one is needed
+    InvalidLocation { name: String, fb: u8, mc: u8 },
+    NoDedicatedInput(String),
+    DuplicateName(String),
+    /// The input or macrocell was placed at a location that is already used
+    LocationConflict(String),
+    UnknownSignal { name: String, signal: String },
+    /// A global net index is out of range
+    InvalidGlobal(String),
+    /// A clock enable was given without [FlipFlopMode::DFFCE], or the other way around
+    InvalidClockEnable(String),
+    /// A registered output was requested for a macrocell without a register
+    NoRegister(String),
+    /// The signals needed by a function block cannot all be assigned to ZIA rows. Only a minimal
+    /// conflicting set of signals is listed.
+    ZIAUnroutable { fb: u8, signals: Vec<String> },
+    OutOfProductTerms { fb: u8 },
+    /// Macrocells need different product terms for the same control term
+    ControlTermConflict { fb: u8, term: &'static str },
+    /// A pad with an IO standard has no entry in [Design::pad_bank]
+    UnknownBank { fb: u8, mc: u8 },
+    /// Pads in the same bank have incompatible IO standards
+    BankConflict(u8),
+}
+impl Display for DesignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesignError::Unplaced(name) => write!(f, "{} has no location", name),
+            DesignError::InvalidLocation { name, fb, mc } => {
+                write!(f, "{} cannot be placed at FB{} MC{}", name, fb, mc)
+            }
+            DesignError::NoDedicatedInput(name) => {
+                write!(f, "{} uses the dedicated input, which this device lacks", name)
+            }
+            DesignError::DuplicateName(name) => write!(f, "{} is defined more than once", name),
+            DesignError::LocationConflict(name) => {
+                write!(f, "{} is placed at a location that is already used", name)
+            }
+            DesignError::UnknownSignal { name, signal } => {
+                write!(f, "{} uses {}, which is not defined", name, signal)
+            }
+            DesignError::InvalidGlobal(name) => {
+                write!(f, "{} uses a global net that does not exist", name)
+            }
+            DesignError::InvalidClockEnable(name) => write!(
+                f,
+                "{} must have a clock enable if and only if it is a DFFCE",
+                name
+            ),
+            DesignError::NoRegister(name) => {
+                write!(f, "{} has a registered output but no register", name)
+            }
+            DesignError::ZIAUnroutable { fb, signals } => {
+                write!(f, "cannot route all inputs of FB{} through the ZIA:", fb)?;
+                for x in signals {
+                    write!(f, " {}", x)?;
+                }
+                Ok(())
+            }
+            DesignError::OutOfProductTerms { fb } => {
+                write!(f, "FB{} needs more than {} product terms", fb, ANDTERMS_PER_FB)
+            }
+            DesignError::ControlTermConflict { fb, term } => {
+                write!(f, "FB{} {} is needed for more than one product term", fb, term)
+            }
+            DesignError::UnknownBank { fb, mc } => {
+                write!(f, "the IO bank of FB{} IO{} is not known", fb, mc)
+            }
+            DesignError::BankConflict(bank) => {
+                write!(f, "IO bank {} has conflicting IO standards", bank)
+            }
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for DesignError {}
+
+/// A complete design
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Design {
+    pub part: XC2Part,
+    pub inputs: Vec<InputDef>,
+    pub macrocells: Vec<MacrocellDef>,
+    /// Maps `(fb, mc)` of each pad to its IO bank. Only needed for pads with an IO standard on
+    /// devices with more than one bank. This crate has no pin-to-bank data, so the entries have
+    /// to be filled in by hand.
+    pub pad_bank: BTreeMap<(u8, u8), u8>,
+    /// Assert GSR while its pin is low
+    pub gsr_invert: bool,
+    /// Tristate outputs while the GTS pin is high instead of low
+    pub gts_invert: [bool; NUM_BUFG_GTS],
+    pub global_termination: GlobalTermination,
+}
+
+impl Design {
+    pub fn new(part: XC2Part) -> Self {
+        Self {
+            part,
+            inputs: Vec::new(),
+            macrocells: Vec::new(),
+            pad_bank: BTreeMap::new(),
+            gsr_invert: false,
+            gts_invert: [false; NUM_BUFG_GTS],
+            global_termination: GlobalTermination::default(),
+        }
+    }
+
+    /// Every product term a macrocell needs, in the order logic, set, reset, clock, clock
+    /// enable, output enable
+    pub(crate) fn product_terms(mc: &MacrocellDef) -> impl Iterator<Item = &ProductTerm> {
+        let reg = mc.register.as_ref();
+        let clk = reg.and_then(|x| match &x.clock {
+            ClockInput::ProductTerm(pt) => Some(pt),
+            ClockInput::Global(_) => None,
+        });
+        let async_pt = |x: Option<&AsyncInput>| match x {
+            Some(AsyncInput::ProductTerm(pt)) => Some(pt),
+            _ => None,
+        };
+        let oe = mc.output.as_ref().and_then(|x| match &x.enable {
+            OutputEnableDef::ProductTerm(pt) => Some(pt),
+            _ => None,
+        });
+        mc.logic
+            .0
+            .iter()
+            .chain(async_pt(reg.and_then(|x| x.set.as_ref())))
+            .chain(async_pt(reg.and_then(|x| x.reset.as_ref())))
+            .chain(clk)
+            .chain(reg.and_then(|x| x.clock_enable.as_ref()))
+            .chain(oe)
+    }
+
+    /// Check names and locations, and find where every signal comes from
+    fn resolve(&self) -> Result<BTreeMap<&str, ZIATableEntry>, DesignError> {
+        let device = self.part.device;
+        let mut signals = BTreeMap::new();
+        let mut input_pads = BTreeSet::new();
+        let mut dedicated_input_used = false;
+        let mut mc_locs = BTreeSet::new();
+        let valid = |fb: u8, mc: u8| (fb as usize) < device.num_fbs() && (mc as usize) < MCS_PER_FB;
+
+        for x in &self.inputs {
+            let entry = match x.loc {
+                None => return Err(DesignError::Unplaced(x.name.clone())),
+                Some(InputLoc::Pad { fb, mc }) => {
+                    if !valid(fb, mc) || !device.has_io_at(fb, mc) {
+                        return Err(DesignError::InvalidLocation {
+                            name: x.name.clone(),
+                            fb,
+                            mc,
+                        });
+                    }
+                    if !input_pads.insert((fb, mc)) {
+                        return Err(DesignError::LocationConflict(x.name.clone()));
+                    }
+                    ZIATableEntry::InputPin { fb, mc }
+                }
+                Some(InputLoc::DedicatedInput) => {
+                    if !matches!(device, XC2Device::XC2C32 | XC2Device::XC2C32A) {
+                        return Err(DesignError::NoDedicatedInput(x.name.clone()));
+                    }
+                    if dedicated_input_used {
+                        return Err(DesignError::LocationConflict(x.name.clone()));
+                    }
+                    dedicated_input_used = true;
+                    ZIATableEntry::DedicatedInputPin
+                }
+            };
+            if signals.insert(x.name.as_str(), entry).is_some() {
+                return Err(DesignError::DuplicateName(x.name.clone()));
+            }
+        }
+
+        for x in &self.macrocells {
+            let Some((fb, mc)) = x.loc else {
+                return Err(DesignError::Unplaced(x.name.clone()));
+            };
+            if !valid(fb, mc) || (x.output.is_some() && !device.has_io_at(fb, mc)) {
+                return Err(DesignError::InvalidLocation {
+                    name: x.name.clone(),
+                    fb,
+                    mc,
+                });
+            }
+            if !mc_locs.insert((fb, mc)) {
+                return Err(DesignError::LocationConflict(x.name.clone()));
+            }
+            // A pad used as an input cannot also be driven, use the feedback of the output instead
+            if x.output.is_some() && input_pads.contains(&(fb, mc)) {
+                return Err(DesignError::LocationConflict(x.name.clone()));
+            }
+            if signals
+                .insert(x.name.as_str(), ZIATableEntry::Macrocell { fb, mc })
+                .is_some()
+            {
+                return Err(DesignError::DuplicateName(x.name.clone()));
+            }
+            if let Some(name) = x.output.as_ref().and_then(|x| x.feedback.as_ref()) {
+                if !input_pads.insert((fb, mc)) {
+                    return Err(DesignError::LocationConflict(name.clone()));
+                }
+                if signals
+                    .insert(name.as_str(), ZIATableEntry::InputPin { fb, mc })
+                    .is_some()
+                {
+                    return Err(DesignError::DuplicateName(name.clone()));
+                }
+            }
+
+            if x.output.as_ref().is_some_and(|x| x.registered) && x.register.is_none() {
+                return Err(DesignError::NoRegister(x.name.clone()));
+            }
+            if let Some(reg) = &x.register {
+                if reg.clock_enable.is_some() != (reg.mode == FlipFlopMode::DFFCE) {
+                    return Err(DesignError::InvalidClockEnable(x.name.clone()));
+                }
+                if matches!(reg.clock, ClockInput::Global(i) if i as usize >= NUM_BUFG_CLK) {
+                    return Err(DesignError::InvalidGlobal(x.name.clone()));
+                }
+            }
+            if let Some(OutputEnableDef::GlobalTristate(i)) = x.output.as_ref().map(|x| &x.enable) {
+                if *i as usize >= NUM_BUFG_GTS {
+                    return Err(DesignError::InvalidGlobal(x.name.clone()));
+                }
+            }
+        }
+
+        for x in &self.macrocells {
+            for pt in Self::product_terms(x) {
+                for lit in &pt.0 {
+                    if !signals.contains_key(lit.signal.as_str()) {
+                        return Err(DesignError::UnknownSignal {
+                            name: x.name.clone(),
+                            signal: lit.signal.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(signals)
+    }
+
+    /// Assign each signal to a ZIA row
+    fn route_zia(
+        &self,
+        fb: u8,
+        needed: &BTreeMap<ZIATableEntry, &str>,
+    ) -> Result<ZIARouting, DesignError> {
+        let signals: BTreeMap<_, _> = needed.keys().map(|&x| (x, None)).collect();
+        route_zia(self.part.device, &signals, &ZIARouting::new()).map_err(|e| match e {
+            ZIARouteError::Unroutable(conflict) => DesignError::ZIAUnroutable {
+                fb,
+                signals: conflict.iter().map(|x| needed[x].to_string()).collect(),
+            },
+            ZIARouteError::InvalidExisting(..) => unreachable!(),
+        })
+    }
+
+    /// Decide which dedicated role each product term takes, and place them all. Set and reset
+    /// use PTA when only one of them is a product term and CTR for the reset otherwise. A clock
+    /// product term uses PTC unless PTC is needed for the clock enable, in which case it uses CTC.
+    pub(crate) fn allocate_pterms(
+        &self,
+        fb: u8,
+        mcs: &[(u8, &MacrocellDef)],
+        and_term: impl Fn(&ProductTerm) -> AndTerm,
+    ) -> Result<PTermAllocation, DesignError> {
+        let mut terms = FunctionBlockTerms::default();
+        let set_ct = |ct: &mut Option<AndTerm>, term: &'static str, pt: &ProductTerm| {
+            let pt = and_term(pt);
+            match ct {
+                Some(x) if *x != pt => Err(DesignError::ControlTermConflict { fb, term }),
+                _ => {
+                    *ct = Some(pt);
+                    Ok(())
+                }
+            }
+        };
+
+        for &(mc, def) in mcs {
+            let mc_terms = &mut terms.mcs[mc as usize];
+            mc_terms.sum = def.logic.0.iter().map(&and_term).collect();
+            if let Some(OutputEnableDef::ProductTerm(pt)) = def.output.as_ref().map(|x| &x.enable) {
+                mc_terms.ptb = Some(and_term(pt));
+            }
+
+            let Some(reg) = &def.register else {
+                continue;
+            };
+            if let Some(AsyncInput::ProductTerm(pt)) = &reg.set {
+                mc_terms.pta = Some(and_term(pt));
+            }
+            if let Some(AsyncInput::ProductTerm(pt)) = &reg.reset {
+                if mc_terms.pta.is_none() {
+                    mc_terms.pta = Some(and_term(pt));
+                } else {
+                    set_ct(&mut terms.ctr, "CTR", pt)?;
+                }
+            }
+            if let ClockInput::ProductTerm(pt) = &reg.clock {
+                if reg.clock_enable.is_some() {
+                    set_ct(&mut terms.ctc, "CTC", pt)?;
+                } else {
+                    mc_terms.ptc = Some(and_term(pt));
+                }
+            }
+            if let Some(pt) = &reg.clock_enable {
+                mc_terms.ptc = Some(and_term(pt));
+            }
+        }
+
+        allocate_pterms(&terms).map_err(|_| DesignError::OutOfProductTerms { fb })
+    }
+
+    /// Produce a bitstream implementing the design
+    pub fn build(&self) -> Result<Coolrunner2<BitBox>, DesignError> {
+        let device = self.part.device;
+        let signals = self.resolve()?;
+        let mut bitstream = Coolrunner2::new(self.part);
+
+        let mut gck_used = [false; NUM_BUFG_CLK];
+        let mut gsr_used = false;
+        let mut gts_used = [false; NUM_BUFG_GTS];
+        // Sources that go into the ZIA anywhere
+        let mut zia_sources = BTreeSet::new();
+
+        for fb in 0..device.num_fbs() as u8 {
+            let mcs: Vec<(u8, &MacrocellDef)> = self
+                .macrocells
+                .iter()
+                .filter_map(|x| match x.loc {
+                    Some((x_fb, mc)) if x_fb == fb => Some((mc, x)),
+                    _ => None,
+                })
+                .collect();
+
+            let mut needed = BTreeMap::new();
+            for (_, x) in &mcs {
+                for pt in Self::product_terms(x) {
+                    for lit in &pt.0 {
+                        needed.insert(signals[lit.signal.as_str()], lit.signal.as_str());
+                    }
+                }
+            }
+            let rows = self.route_zia(fb, &needed)?;
+            bitstream.set_zia_routing(fb, &rows);
+            zia_sources.extend(rows.keys().copied());
+
+            let and_term = |pt: &ProductTerm| {
+                let mut ret = AndTerm::default();
+                for lit in &pt.0 {
+                    ret.add(rows[&signals[lit.signal.as_str()]].row, lit.negated);
+                }
+                ret
+            };
+            let pterms = self.allocate_pterms(fb, &mcs, and_term)?;
+            bitstream.set_pterm_allocation(fb, &pterms);
+
+            for &(mc, def) in &mcs {
+                let acc = bitstream.fb(fb).mc(mc);
+                let xor_mode = if def.invert { XorMode::_1 } else { XorMode::_0 };
+                bitstream.set_prop(&acc.xor_mode(), xor_mode);
+
+                if let Some(reg) = &def.register {
+                    let set_pt = matches!(reg.set, Some(AsyncInput::ProductTerm(_)));
+                    if reg.set == Some(AsyncInput::GlobalSetReset)
+                        || reg.reset == Some(AsyncInput::GlobalSetReset)
+                    {
+                        gsr_used = true;
+                    }
+                    let s_src = match reg.set {
+                        None => RegSetSrc::Disabled,
+                        Some(AsyncInput::GlobalSetReset) => RegSetSrc::GSR,
+                        Some(AsyncInput::ProductTerm(_)) => RegSetSrc::PTA,
+                    };
+                    let r_src = match reg.reset {
+                        None => RegResetSrc::Disabled,
+                        Some(AsyncInput::GlobalSetReset) => RegResetSrc::GSR,
+                        Some(AsyncInput::ProductTerm(_)) if set_pt => RegResetSrc::CTR,
+                        Some(AsyncInput::ProductTerm(_)) => RegResetSrc::PTA,
+                    };
+                    let clk_src = match reg.clock {
+                        ClockInput::Global(i) => {
+                            gck_used[i as usize] = true;
+                            [RegClkSrc::GCK0, RegClkSrc::GCK1, RegClkSrc::GCK2][i as usize]
+                        }
+                        ClockInput::ProductTerm(_) if reg.clock_enable.is_some() => RegClkSrc::CTC,
+                        ClockInput::ProductTerm(_) => RegClkSrc::PTC,
+                    };
+
+                    bitstream.set_prop(&acc.use_iob(), false);
+                    bitstream.set_prop(&acc.ff_mode(), reg.mode);
+                    bitstream.set_prop(&acc.clk_src(), clk_src);
+                    bitstream.set_prop(&acc.clk_inv(), reg.clock_invert);
+                    bitstream.set_prop(&acc.is_ddr(), reg.ddr);
+                    bitstream.set_prop(&acc.s_src(), s_src);
+                    bitstream.set_prop(&acc.r_src(), r_src);
+                    bitstream.set_prop(&acc.init_state(), reg.init_state);
+                }
+
+                if let Some(out) = &def.output {
+                    let io = bitstream.fb(fb).io(mc);
+                    let output_src = if out.registered {
+                        PinOutputSrc::FlipFlop
+                    } else {
+                        PinOutputSrc::XorGate
+                    };
+                    let mode = match out.enable {
+                        OutputEnableDef::Always => OutputMode::PushPull,
+                        OutputEnableDef::OpenDrain => OutputMode::OpenDrain,
+                        OutputEnableDef::GlobalTristate(i) => {
+                            gts_used[i as usize] = true;
+                            [
+                                OutputMode::TriStateGTS0,
+                                OutputMode::TriStateGTS1,
+                                OutputMode::TriStateGTS2,
+                                OutputMode::TriStateGTS3,
+                            ][i as usize]
+                        }
+                        OutputEnableDef::ProductTerm(_) => OutputMode::TriStatePTB,
+                    };
+                    bitstream.set_prop(&io.output_src(), output_src);
+                    bitstream.set_prop(&io.output_pad_mode(), mode);
+                    bitstream.set_prop(&io.slew_rate(), out.slew);
+                    bitstream.set_prop(&io.termination_enabled(), out.termination);
+                }
+            }
+        }
+
+        // Feedback into the ZIA
+        for x in &self.macrocells {
+            let Some((fb, mc)) = x.loc else {
+                unreachable!()
+            };
+            if zia_sources.contains(&ZIATableEntry::Macrocell { fb, mc }) {
+                let src = if x.register.is_some() {
+                    MacrocellFeedbackSrc::FlipFlop
+                } else {
+                    MacrocellFeedbackSrc::XorGate
+                };
+                bitstream.set_prop(&bitstream.fb(fb).mc(mc).fb_src(), src);
+            }
+            if zia_sources.contains(&ZIATableEntry::InputPin { fb, mc }) {
+                bitstream.set_prop(&bitstream.fb(fb).io(mc).fb_src(), IoFeedbackSource::IO);
+            }
+        }
+        for x in &self.inputs {
+            match x.loc {
+                Some(InputLoc::Pad { fb, mc }) => {
+                    let io = bitstream.fb(fb).io(mc);
+                    if zia_sources.contains(&ZIATableEntry::InputPin { fb, mc }) {
+                        bitstream.set_prop(&io.fb_src(), IoFeedbackSource::IO);
+                    }
+                    bitstream.set_prop(&io.output_pad_mode(), OutputMode::Disabled);
+                    bitstream.set_prop(&io.termination_enabled(), x.termination);
+                }
+                Some(InputLoc::DedicatedInput) => {
+                    bitstream.set_prop(
+                        &bitstream.extra_dedicated_input().termination_enabled(),
+                        x.termination,
+                    );
+                }
+                None => unreachable!(),
+            }
+        }
+
+        for (i, &used) in gck_used.iter().enumerate() {
+            if used {
+                bitstream.set_prop(&bitstream.gck_enabled(i as u8), true);
+            }
+        }
+        if gsr_used {
+            bitstream.set_prop(&bitstream.gsr_enabled(), true);
+            bitstream.set_prop(&bitstream.gsr_invert(), self.gsr_invert);
+        }
+        for (i, &used) in gts_used.iter().enumerate() {
+            if used {
+                bitstream.set_prop(&bitstream.gts_enabled(i as u8), true);
+                bitstream.set_prop(&bitstream.gts_invert(i as u8), self.gts_invert[i]);
+            }
+        }
+        bitstream.set_prop(&bitstream.global_termination(), self.global_termination);
+
+        self.set_bank_voltages(&mut bitstream)?;
+
+        Ok(bitstream)
+    }
+
+    fn set_bank_voltages(&self, bitstream: &mut Coolrunner2<BitBox>) -> Result<(), DesignError> {
+        let device = self.part.device;
+        let bank_of = |fb: u8, mc: u8| match self.pad_bank.get(&(fb, mc)) {
+            Some(&bank) if (bank as usize) < device.num_io_banks() => Ok(bank),
+            None if device.num_io_banks() == 1 => Ok(0),
+            _ => Err(DesignError::UnknownBank { fb, mc }),
+        };
+
+        // (bank, is output) -> voltage
+        let mut voltages = BTreeMap::new();
+        let mut add = |bank: u8, output: bool, std: IoStandard| {
+            match voltages.insert((bank, output), std.voltage()) {
+                Some(x) if x != std.voltage() => Err(DesignError::BankConflict(bank)),
+                _ => Ok(()),
+            }
+        };
+        for x in &self.inputs {
+            if let (Some(InputLoc::Pad { fb, mc }), Some(std)) = (x.loc, x.standard) {
+                add(bank_of(fb, mc)?, false, std)?;
+            }
+        }
+        for x in &self.macrocells {
+            let Some(out) = &x.output else {
+                continue;
+            };
+            if let (Some((fb, mc)), Some(std)) = (x.loc, out.standard) {
+                add(bank_of(fb, mc)?, true, std)?;
+                if out.feedback.is_some() {
+                    add(bank_of(fb, mc)?, false, std)?;
+                }
+            }
+        }
+
+        for ((bank, output), voltage) in voltages {
+            if output {
+                bitstream.set_prop(&bitstream.output_voltage(bank), voltage);
+            } else {
+                bitstream.set_prop(&bitstream.input_voltage(bank), voltage);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::PinState;
+
+    #[test]
+    fn parse() {
+        let x = SumOfProducts::parse("a & !b | c").unwrap();
+        assert_eq!(x.0.len(), 2);
+        assert_eq!(x.to_string(), "a & !b | c");
+        assert_eq!(SumOfProducts::parse("0").unwrap().0.len(), 0);
+        assert_eq!(
+            SumOfProducts::parse("a & | b").unwrap_err().kind,
+            ExprParseErrorKind::ExpectedSignal
+        );
+        assert_eq!(
+            SumOfProducts::parse("a b").unwrap_err(),
+            ExprParseError {
+                pos: 2,
+                kind: ExprParseErrorKind::ExpectedOperator
+            }
+        );
+    }
+
+    #[test]
+    fn build_comb() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        for (name, mc) in [("a", 5), ("b", 6), ("c", 7)] {
+            design
+                .inputs
+                .push(InputDef::new(name, InputLoc::Pad { fb: 0, mc }));
+        }
+        let mut y = MacrocellDef::new("y", (0, 3), SumOfProducts::parse("a & !b | c").unwrap());
+        y.output = Some(OutputDef::new(false));
+        design.macrocells.push(y);
+
+        let mut sim = design.build().unwrap().simulator().unwrap();
+        for i in 0..8 {
+            let (a, b, c) = (i & 1 != 0, i & 2 != 0, i & 4 != 0);
+            sim.set_pin(0, 5, a);
+            sim.set_pin(0, 6, b);
+            sim.set_pin(0, 7, c);
+            sim.step().unwrap();
+            assert_eq!(sim.pin_state(0, 3), PinState::Driven(a && !b || c));
+        }
+
+        design.macrocells[0].logic = SumOfProducts::parse("a & d").unwrap();
+        assert_eq!(
+            design.build().unwrap_err(),
+            DesignError::UnknownSignal {
+                name: "y".into(),
+                signal: "d".into()
+            }
+        );
+    }
+
+    #[test]
+    fn build_registered() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        design
+            .inputs
+            .push(InputDef::new("a", InputLoc::Pad { fb: 0, mc: 5 }));
+        let mut q = MacrocellDef::new("q", (0, 3), SumOfProducts::parse("a").unwrap());
+        q.register = Some(RegisterDef {
+            reset: Some(AsyncInput::GlobalSetReset),
+            ..RegisterDef::dff(ClockInput::Global(0))
+        });
+        q.output = Some(OutputDef::new(true));
+        design.macrocells.push(q);
+
+        let mut sim = design.build().unwrap().simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        sim.clock(0).unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(true));
+        sim.set_gsr(true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        sim.clock(0).unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+
+        design.macrocells[0].register = None;
+        assert_eq!(
+            design.build().unwrap_err(),
+            DesignError::NoRegister("q".into())
+        );
+    }
+
+    #[test]
+    fn build_output_enable() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        for (name, mc) in [("a", 5), ("b", 6)] {
+            design
+                .inputs
+                .push(InputDef::new(name, InputLoc::Pad { fb: 0, mc }));
+        }
+        let mut y = MacrocellDef::new("y", (0, 3), SumOfProducts::parse("a").unwrap());
+        y.output = Some(OutputDef {
+            enable: OutputEnableDef::ProductTerm(ProductTerm::parse("b").unwrap()),
+            ..OutputDef::new(false)
+        });
+        design.macrocells.push(y);
+
+        let bitstream = design.build().unwrap();
+        assert_eq!(
+            bitstream.get_prop(&bitstream.fb(0).io(3).output_pad_mode()),
+            OutputMode::TriStatePTB
+        );
+        let mut sim = bitstream.simulator().unwrap();
+        for i in 0..4 {
+            let (a, b) = (i & 1 != 0, i & 2 != 0);
+            sim.set_pin(0, 5, a);
+            sim.set_pin(0, 6, b);
+            sim.step().unwrap();
+            let expected = if b { PinState::Driven(a) } else { PinState::HighZ };
+            assert_eq!(sim.pin_state(0, 3), expected);
+        }
+    }
+
+    #[test]
+    fn build_clock_enable() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        for (name, mc) in [("a", 5), ("b", 6), ("c", 7)] {
+            design
+                .inputs
+                .push(InputDef::new(name, InputLoc::Pad { fb: 0, mc }));
+        }
+        let mut q = MacrocellDef::new("q", (0, 3), SumOfProducts::parse("a").unwrap());
+        q.register = Some(RegisterDef {
+            mode: FlipFlopMode::DFFCE,
+            clock_enable: Some(ProductTerm::parse("b").unwrap()),
+            ..RegisterDef::dff(ClockInput::ProductTerm(ProductTerm::parse("c").unwrap()))
+        });
+        q.output = Some(OutputDef::new(true));
+        design.macrocells.push(q);
+
+        let bitstream = design.build().unwrap();
+        // PTC is taken by the clock enable
+        assert_eq!(
+            bitstream.get_prop(&bitstream.fb(0).mc(3).clk_src()),
+            RegClkSrc::CTC
+        );
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        for (b, expected) in [(false, false), (true, true)] {
+            sim.set_pin(0, 6, b);
+            sim.set_pin(0, 7, true);
+            sim.step().unwrap();
+            sim.set_pin(0, 7, false);
+            sim.step().unwrap();
+            assert_eq!(sim.pin_state(0, 3), PinState::Driven(expected));
+        }
+    }
+
+    #[test]
+    fn location_conflicts() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        design
+            .inputs
+            .push(InputDef::new("a", InputLoc::DedicatedInput));
+        design.build().unwrap();
+        design
+            .inputs
+            .push(InputDef::new("b", InputLoc::DedicatedInput));
+        assert_eq!(
+            design.build().unwrap_err(),
+            DesignError::LocationConflict("b".into())
+        );
+        design.inputs.pop();
+
+        // A buried macrocell can share its location with an input pad, an output cannot
+        design
+            .inputs
+            .push(InputDef::new("c", InputLoc::Pad { fb: 0, mc: 5 }));
+        design
+            .macrocells
+            .push(MacrocellDef::new("q", (0, 5), SumOfProducts::parse("a").unwrap()));
+        design.build().unwrap();
+        design.macrocells[0].output = Some(OutputDef::new(false));
+        assert_eq!(
+            design.build().unwrap_err(),
+            DesignError::LocationConflict("q".into())
+        );
+    }
+}
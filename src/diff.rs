@@ -0,0 +1,329 @@
+//! Property-level comparison of two bitstreams
+//!
+//! Rather than comparing raw fuses, this walks the same hierarchy of property accessors that the
+//! textual dump uses and reports every property whose value differs, formatted the same way.
+
+use core::fmt::{self, Display};
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use bittwiddler_core::prelude::{BitArray, PropertyAccessor, PropertyLeafWithStringConv};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2, UserCode},
+    partdb::XC2Device,
+    zia::ZIARowAccessor,
+    ANDTERMS_PER_FB, MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS, ZIA_ROWS,
+};
+
+/// One property with a different value in the two bitstreams. With the `serde` feature, a list
+/// of these serializes to JSON as an array of objects with the same four fields.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PropertyChange {
+    /// The hierarchy level containing the property, e.g. `FB2 MC5`. Empty for global properties.
+    pub path: String,
+    pub property: String,
+    pub old: String,
+    pub new: String,
+}
+impl Display for PropertyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{} ", self.path)?;
+        }
+        write!(f, "{} {}\u{2192}{}", self.property, self.old, self.new)
+    }
+}
+
+/// Number of bits in a [PropertyAccessor::BoolArray]
+pub(crate) trait BoolArrayLen {
+    const LEN: usize;
+}
+impl<const N: usize> BoolArrayLen for [bool; N] {
+    const LEN: usize = N;
+}
+
+/// Something that wants to look at every property of a bitstream, see [visit_properties]
+pub(crate) trait PropertyVisitor {
+    fn prop<P>(&mut self, path: &str, property: &str, acc: P)
+    where
+        P: PropertyAccessor,
+        P::BoolArray: BoolArrayLen,
+        P::Output: PartialEq + PropertyLeafWithStringConv<P::BoolArray, P>;
+
+    fn usercode(&mut self, acc: UserCode) {
+        self.prop("", "usercode", acc)
+    }
+
+    /// ZIA rows are `zia_width` bits wide rather than the length of their `BoolArray`
+    fn zia_row(&mut self, path: &str, property: &str, acc: ZIARowAccessor) {
+        self.prop(path, property, acc)
+    }
+}
+
+/// Call the visitor for every property of the bitstream, in the same order as the textual dump.
+///
+/// This list is maintained by hand. The `every_fuse_is_visited` test catches properties that are
+/// missing from it.
+#[allow(private_bounds)]
+pub(crate) fn visit_properties<B: BitHolder, V: PropertyVisitor>(a: &Coolrunner2<B>, d: &mut V) {
+    let device = a.part.device;
+
+    for i in 0..NUM_BUFG_CLK as u8 {
+        d.prop("", &format!("gck_enabled[{i}]"), a.gck_enabled(i));
+    }
+    d.prop("", "gsr_enabled", a.gsr_enabled());
+    d.prop("", "gsr_invert", a.gsr_invert());
+    for i in 0..NUM_BUFG_GTS as u8 {
+        d.prop("", &format!("gts_enabled[{i}]"), a.gts_enabled(i));
+        d.prop("", &format!("gts_invert[{i}]"), a.gts_invert(i));
+    }
+    d.prop("", "global_termination", a.global_termination());
+    if device == XC2Device::XC2C32 || device == XC2Device::XC2C32A {
+        let x = a.extra_dedicated_input();
+        d.prop("extra_dedicated_input", "schmitt_trigger", x.schmitt_trigger());
+        d.prop(
+            "extra_dedicated_input",
+            "termination_enabled",
+            x.termination_enabled(),
+        );
+    }
+    if device.has_large_macrocells() {
+        let x = a.clock_divider();
+        d.prop("clock_divider", "enabled", x.enabled());
+        d.prop("clock_divider", "delay", x.delay());
+        d.prop("clock_divider", "ratio", x.ratio());
+        d.prop("", "data_gate_enabled", a.data_gate_enabled());
+        d.prop("", "use_vref", a.use_vref());
+    }
+    if device == XC2Device::XC2C32A || device == XC2Device::XC2C64A {
+        d.prop("", "legacy_ivoltage", a.legacy_ivoltage());
+        d.prop("", "legacy_ovoltage", a.legacy_ovoltage());
+    }
+    for i in 0..device.num_io_banks() as u8 {
+        d.prop("", &format!("input_voltage[{i}]"), a.input_voltage(i));
+        d.prop("", &format!("output_voltage[{i}]"), a.output_voltage(i));
+    }
+    d.usercode(a.usercode());
+    d.prop("", "security", a.security());
+
+    for fb in 0..device.num_fbs() as u8 {
+        let fb_acc = a.fb(fb);
+
+        let path = format!("FB{fb}");
+        for row in 0..ZIA_ROWS as u8 {
+            d.zia_row(&path, &format!("ZIA row {row}"), fb_acc.zia_row(row));
+        }
+
+        for pterm in 0..ANDTERMS_PER_FB as u8 {
+            let path = format!("FB{fb} PT{pterm}");
+            let x = fb_acc.and_term(pterm);
+            for row in 0..ZIA_ROWS as u8 {
+                d.prop(&path, &format!("inp[{row}]"), x.inp(row));
+                d.prop(&path, &format!("inp_n[{row}]"), x.inp_n(row));
+            }
+        }
+
+        for mc in 0..MCS_PER_FB as u8 {
+            let path = format!("FB{fb} OR{mc}");
+            let x = fb_acc.or_term(mc);
+            for pterm in 0..ANDTERMS_PER_FB as u8 {
+                d.prop(&path, &format!("inp[{pterm}]"), x.inp(pterm));
+            }
+        }
+
+        for mc in 0..MCS_PER_FB as u8 {
+            let path = format!("FB{fb} MC{mc}");
+            let x = fb_acc.mc(mc);
+            d.prop(&path, "clk_src", x.clk_src());
+            d.prop(&path, "clk_inv", x.clk_inv());
+            d.prop(&path, "is_ddr", x.is_ddr());
+            d.prop(&path, "r_src", x.r_src());
+            d.prop(&path, "s_src", x.s_src());
+            d.prop(&path, "init_state", x.init_state());
+            d.prop(&path, "ff_mode", x.ff_mode());
+            d.prop(&path, "fb_src", x.fb_src());
+            d.prop(&path, "use_iob", x.use_iob());
+            d.prop(&path, "xor_mode", x.xor_mode());
+        }
+
+        for mc in 0..MCS_PER_FB as u8 {
+            if !device.has_io_at(fb, mc) {
+                continue;
+            }
+            let path = format!("FB{fb} IO{mc}");
+            let x = fb_acc.io(mc);
+            d.prop(&path, "fb_src", x.fb_src());
+            if device.has_large_macrocells() {
+                d.prop(&path, "input_pad_mode", x.input_pad_mode());
+            } else {
+                d.prop(&path, "schmitt_trigger", x.schmitt_trigger());
+            }
+            d.prop(&path, "output_src", x.output_src());
+            d.prop(&path, "output_pad_mode", x.output_pad_mode());
+            d.prop(&path, "termination_enabled", x.termination_enabled());
+            d.prop(&path, "slew_rate", x.slew_rate());
+            if device.has_large_macrocells() {
+                d.prop(&path, "use_data_gate", x.use_data_gate());
+            }
+        }
+    }
+}
+
+struct Differ<'a> {
+    a: &'a dyn BitArray,
+    b: &'a dyn BitArray,
+    changes: Vec<PropertyChange>,
+}
+impl<'a> PropertyVisitor for Differ<'a> {
+    fn prop<P>(&mut self, path: &str, property: &str, acc: P)
+    where
+        P: PropertyAccessor,
+        P::BoolArray: BoolArrayLen,
+        P::Output: PartialEq + PropertyLeafWithStringConv<P::BoolArray, P>,
+    {
+        let old = acc.get(self.a);
+        let new = acc.get(self.b);
+        if old != new {
+            self.changes.push(PropertyChange {
+                path: path.to_string(),
+                property: property.to_string(),
+                old: old.to_string(&acc).into_owned(),
+                new: new.to_string(&acc).into_owned(),
+            });
+        }
+    }
+
+    fn usercode(&mut self, acc: UserCode) {
+        let old_usercode = acc.get(self.a);
+        let new_usercode = acc.get(self.b);
+        if old_usercode != new_usercode {
+            self.changes.push(PropertyChange {
+                path: String::new(),
+                property: "usercode".into(),
+                old: format!("0x{:08X}", old_usercode),
+                new: format!("0x{:08X}", new_usercode),
+            });
+        }
+    }
+}
+
+/// Compare two bitstreams property by property.
+///
+/// If the bitstreams are for different devices, only the part is reported.
+#[allow(private_bounds)]
+pub fn diff<A: BitHolder, B: BitHolder>(
+    a: &Coolrunner2<A>,
+    b: &Coolrunner2<B>,
+) -> Vec<PropertyChange> {
+    let mut d = Differ {
+        a,
+        b,
+        changes: Vec::new(),
+    };
+
+    if a.part != b.part {
+        d.changes.push(PropertyChange {
+            path: String::new(),
+            property: "part".into(),
+            old: a.part.to_string(),
+            new: b.part.to_string(),
+        });
+        if a.part.device != b.part.device {
+            return d.changes;
+        }
+    }
+
+    visit_properties(a, &mut d);
+    d.changes
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Compare this bitstream with another one. See [diff].
+    pub fn diff<B2: BitHolder>(&self, other: &Coolrunner2<B2>) -> Vec<PropertyChange> {
+        diff(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fuse_map::{FuseMap, FuseOwner},
+        mc::FlipFlopMode,
+        partdb::XC2Part,
+        zia::ZIARow,
+    };
+    use bittwiddler_core::prelude::Coordinate;
+
+    #[test]
+    fn property_changes() {
+        let a = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let mut b = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        assert_eq!(a.diff(&b), []);
+
+        b.set_prop(&b.fb(1).mc(5).ff_mode(), FlipFlopMode::TFF);
+        b.set_prop(&b.fb(0).zia_row(12), ZIARow::GND);
+        b.set_prop(&b.usercode(), 0x12345678);
+        let changes = a.diff(&b);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().any(|x| x.path == "FB1 MC5"
+            && x.property == "ff_mode"
+            && x.old == "DFF"
+            && x.new == "TFF"));
+        assert!(changes
+            .iter()
+            .any(|x| x.to_string() == "usercode 0xFFFFFFFF\u{2192}0x12345678"));
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn json() {
+        let a = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let mut b = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        b.set_prop(&b.fb(1).mc(5).ff_mode(), FlipFlopMode::TFF);
+        assert_eq!(
+            serde_json::to_string(&a.diff(&b)).unwrap(),
+            r#"[{"path":"FB1 MC5","property":"ff_mode","old":"DFF","new":"TFF"}]"#
+        );
+    }
+
+    #[test]
+    fn different_devices() {
+        let a = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let b = Coolrunner2::new(XC2Part::new(XC2Device::XC2C64A, None, None).unwrap());
+        let changes = a.diff(&b);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].property, "part");
+    }
+
+    #[test]
+    fn every_fuse_is_visited() {
+        // A property left out of visit_properties leaves its fuses unassigned in the fuse map
+        for device in [
+            XC2Device::XC2C32,
+            XC2Device::XC2C32A,
+            XC2Device::XC2C64,
+            XC2Device::XC2C64A,
+            XC2Device::XC2C128,
+            XC2Device::XC2C256,
+            XC2Device::XC2C384,
+            XC2Device::XC2C512,
+        ] {
+            let map = FuseMap::new(device);
+            let (w, h) = device.fuse_array_dims();
+            for y in 0..h {
+                for x in 0..w {
+                    let owner = map.owner(Coordinate::new(x, y)).unwrap();
+                    assert_ne!(owner, FuseOwner::Unassigned, "{} fuse ({}, {})", device, x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Design rule checks on bitstreams
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bittwiddler_core::prelude::{BitArray, PropertyAccessor, PropertyAccessorWithDefault};
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2, DeviceSecurity, ReadbackSecurity},
+    io::{LegacyIVoltage, LegacyOVoltage, OutputMode},
+    mc::{FlipFlopMode, RegClkSrc, XorMode},
+    netlist::{Netlist, NodeId},
+    partdb::XC2Device,
+    zia::ZIARow,
+    MCS_PER_FB,
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Everything a rule is allowed to look at
+pub struct DrcContext<'a> {
+    pub device: XC2Device,
+    pub bits: &'a dyn BitArray,
+    pub netlist: &'a Netlist,
+}
+
+/// A single design rule
+#[derive(Clone, Copy)]
+pub struct DrcRule {
+    pub name: &'static str,
+    pub severity: Severity,
+    /// Appends a message to the output for every violation found
+    pub check: fn(&DrcContext, &mut Vec<String>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DrcViolation {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+impl Display for DrcViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DrcReport {
+    pub violations: Vec<DrcViolation>,
+}
+impl DrcReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.violations
+            .iter()
+            .filter(|v| v.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) != 0
+    }
+}
+impl Display for DrcReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for v in &self.violations {
+            writeln!(f, "{}", v)?;
+        }
+        writeln!(
+            f,
+            "DRC: {} error(s), {} warning(s), {} info",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info)
+        )
+    }
+}
+
+/// A set of rules to run
+#[derive(Clone)]
+pub struct Drc {
+    pub rules: Vec<DrcRule>,
+}
+impl Default for Drc {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                DrcRule {
+                    name: "invalid-output-mode",
+                    severity: Severity::Error,
+                    check: check_invalid_output_mode,
+                },
+                DrcRule {
+                    name: "invalid-security",
+                    severity: Severity::Error,
+                    check: check_invalid_security,
+                },
+                DrcRule {
+                    name: "invalid-zia-row",
+                    severity: Severity::Error,
+                    check: check_invalid_zia_row,
+                },
+                DrcRule {
+                    name: "ptc-clock-and-xor",
+                    severity: Severity::Error,
+                    check: check_ptc_clock_and_xor,
+                },
+                DrcRule {
+                    name: "combinational-loop",
+                    severity: Severity::Warning,
+                    check: check_combinational_loop,
+                },
+                DrcRule {
+                    name: "ddr-latch",
+                    severity: Severity::Warning,
+                    check: check_ddr_latch,
+                },
+                DrcRule {
+                    name: "zia-pad-feedback-disabled",
+                    severity: Severity::Warning,
+                    check: check_zia_pad_feedback_disabled,
+                },
+                DrcRule {
+                    name: "zia-mc-feedback-disabled",
+                    severity: Severity::Warning,
+                    check: check_zia_mc_feedback_disabled,
+                },
+                DrcRule {
+                    name: "gts-never-enabled",
+                    severity: Severity::Warning,
+                    check: check_gts_never_enabled,
+                },
+                DrcRule {
+                    name: "legacy-fuse-non-default",
+                    severity: Severity::Warning,
+                    check: check_legacy_fuses,
+                },
+            ],
+        }
+    }
+}
+impl Drc {
+    #[allow(private_bounds)]
+    pub fn run<B: BitHolder>(&self, bitstream: &Coolrunner2<B>) -> DrcReport {
+        let netlist = bitstream.netlist();
+        let ctx = DrcContext {
+            device: bitstream.part.device,
+            bits: bitstream,
+            netlist: &netlist,
+        };
+
+        let mut report = DrcReport::default();
+        for rule in &self.rules {
+            let mut messages = Vec::new();
+            (rule.check)(&ctx, &mut messages);
+            report
+                .violations
+                .extend(messages.into_iter().map(|message| DrcViolation {
+                    rule: rule.name,
+                    severity: rule.severity,
+                    message,
+                }));
+        }
+        report
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Run the default set of design rule checks
+    pub fn drc(&self) -> DrcReport {
+        Drc::default().run(self)
+    }
+}
+
+fn check_invalid_output_mode(ctx: &DrcContext, out: &mut Vec<String>) {
+    for (fb, fb_nodes) in ctx.netlist.fbs.iter().enumerate() {
+        for (mc, io) in fb_nodes.io_pads.iter().enumerate() {
+            if let Some(io) = io {
+                if matches!(io.output_mode, OutputMode::Invalid { .. }) {
+                    out.push(format!("FB{fb} IO{mc} has an invalid output mode encoding"));
+                }
+            }
+        }
+    }
+}
+
+fn check_invalid_security(ctx: &DrcContext, out: &mut Vec<String>) {
+    let security = DeviceSecurity { device: ctx.device }.get(ctx.bits);
+    if matches!(security, ReadbackSecurity::Invalid { .. }) {
+        out.push("readback security bits have an invalid encoding".into());
+    }
+}
+
+fn check_invalid_zia_row(ctx: &DrcContext, out: &mut Vec<String>) {
+    for (fb, fb_nodes) in ctx.netlist.fbs.iter().enumerate() {
+        for (row, x) in fb_nodes.zia_rows.iter().enumerate() {
+            if let ZIARow::Invalid(_) = x.setting {
+                out.push(format!("FB{fb} ZIA row {row} has an invalid mux encoding"));
+            }
+        }
+    }
+}
+
+fn check_ptc_clock_and_xor(ctx: &DrcContext, out: &mut Vec<String>) {
+    for (fb, fb_nodes) in ctx.netlist.fbs.iter().enumerate() {
+        for mc in 0..MCS_PER_FB {
+            let reg = &fb_nodes.registers[mc];
+            let xor = &fb_nodes.xors[mc];
+            if reg.clk_src == RegClkSrc::PTC && matches!(xor.mode, XorMode::PTC | XorMode::PTCB) {
+                out.push(format!(
+                    "FB{fb} MC{mc} is clocked by PTC, but PTC is also used as an XOR input"
+                ));
+            }
+        }
+    }
+}
+
+fn check_combinational_loop(ctx: &DrcContext, out: &mut Vec<String>) {
+    for x in ctx.netlist.combinational_loops() {
+        out.push(format!("{}", x));
+    }
+}
+
+fn check_ddr_latch(ctx: &DrcContext, out: &mut Vec<String>) {
+    for (fb, fb_nodes) in ctx.netlist.fbs.iter().enumerate() {
+        for (mc, reg) in fb_nodes.registers.iter().enumerate() {
+            if reg.is_ddr && reg.mode == FlipFlopMode::LATCH {
+                out.push(format!("FB{fb} MC{mc} is a latch but has DDR enabled"));
+            }
+        }
+    }
+}
+
+fn check_zia_pad_feedback_disabled(ctx: &DrcContext, out: &mut Vec<String>) {
+    for x in ctx.netlist.dangling_zia_references() {
+        if let NodeId::IoFeedback { .. } = x.source {
+            out.push(format!("{}", x));
+        }
+    }
+}
+
+fn check_zia_mc_feedback_disabled(ctx: &DrcContext, out: &mut Vec<String>) {
+    for x in ctx.netlist.dangling_zia_references() {
+        if let NodeId::McFeedback { .. } = x.source {
+            out.push(format!("{}", x));
+        }
+    }
+}
+
+fn check_gts_never_enabled(ctx: &DrcContext, out: &mut Vec<String>) {
+    for x in ctx.netlist.contention_risks(&BTreeMap::new()) {
+        out.push(format!("{}", x));
+    }
+}
+
+fn check_legacy_fuses(ctx: &DrcContext, out: &mut Vec<String>) {
+    let orig_part = match ctx.device {
+        XC2Device::XC2C32A => "XC2C32",
+        XC2Device::XC2C64A => "XC2C64",
+        _ => return,
+    };
+    if !LegacyIVoltage { device: ctx.device }.is_at_default(ctx.bits) {
+        out.push(format!(
+            "{orig_part}-only input voltage fuse is set on an {}",
+            ctx.device
+        ));
+    }
+    if !LegacyOVoltage { device: ctx.device }.is_at_default(ctx.bits) {
+        out.push(format!(
+            "{orig_part}-only output voltage fuse is set on an {}",
+            ctx.device
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::{IoFeedbackSource, IoVoltage},
+        mc::MacrocellFeedbackSrc,
+        partdb::XC2Part,
+        zia::ZIATableEntry,
+    };
+    use bitvec::boxed::BitBox;
+
+    fn messages(bitstream: &Coolrunner2<BitBox>, rule: &str) -> Vec<String> {
+        let report = bitstream.drc();
+        report
+            .violations
+            .into_iter()
+            .filter(|x| x.rule == rule)
+            .map(|x| x.message)
+            .collect()
+    }
+
+    #[test]
+    fn legacy_fuses() {
+        for device in [XC2Device::XC2C32A, XC2Device::XC2C64A] {
+            let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+            bitstream.set_prop(&bitstream.legacy_ivoltage(), IoVoltage::default());
+            bitstream.set_prop(&bitstream.legacy_ovoltage(), IoVoltage::default());
+            assert_eq!(messages(&bitstream, "legacy-fuse-non-default"), [] as [String; 0]);
+
+            bitstream.set_prop(&bitstream.legacy_ivoltage(), IoVoltage::High);
+            let x = messages(&bitstream, "legacy-fuse-non-default");
+            assert_eq!(x.len(), 1);
+            assert!(x[0].contains("input voltage"));
+
+            bitstream.set_prop(&bitstream.legacy_ovoltage(), IoVoltage::High);
+            assert_eq!(messages(&bitstream, "legacy-fuse-non-default").len(), 2);
+        }
+
+        let bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C128, None, None).unwrap());
+        assert_eq!(messages(&bitstream, "legacy-fuse-non-default"), [] as [String; 0]);
+    }
+
+    #[test]
+    fn dangling_zia() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        // Row 0 choice 3 is FB0 MC1
+        bitstream.set_prop(&bitstream.fb(1).zia_row(0), ZIARow::MuxChoice(3));
+        bitstream.set_prop(&bitstream.fb(1).and_term(7).inp(0), true);
+        bitstream.set_prop(&bitstream.fb(0).mc(1).fb_src(), MacrocellFeedbackSrc::XorGate);
+        assert_eq!(messages(&bitstream, "zia-mc-feedback-disabled"), [] as [String; 0]);
+        bitstream.set_prop(&bitstream.fb(0).mc(1).fb_src(), MacrocellFeedbackSrc::Disabled);
+        assert_eq!(
+            messages(&bitstream, "zia-mc-feedback-disabled"),
+            ["FB1 ZIA row 0 selects FB0 MC1, but that macrocell's feedback is disabled \
+              (used by FB1 PT7)"]
+        );
+
+        let pad = XC2Device::XC2C32A.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        bitstream.set_prop(&bitstream.fb(1).zia_row(pad.row), ZIARow::MuxChoice(pad.choice));
+        bitstream.set_prop(&bitstream.fb(0).io(5).fb_src(), IoFeedbackSource::IO);
+        assert_eq!(messages(&bitstream, "zia-pad-feedback-disabled"), [] as [String; 0]);
+        bitstream.set_prop(&bitstream.fb(0).io(5).fb_src(), IoFeedbackSource::Disabled);
+        assert_eq!(messages(&bitstream, "zia-pad-feedback-disabled").len(), 1);
+    }
+
+    #[test]
+    fn invalid_zia_row() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.fb(0).zia_row(0), ZIARow::GND);
+        assert_eq!(messages(&bitstream, "invalid-zia-row"), [] as [String; 0]);
+
+        // Choices 0 and 5 at the same time
+        let mut bits = [true; 88];
+        bits[0] = false;
+        bits[2] = false;
+        bits[7] = false;
+        bitstream.set_prop(&bitstream.fb(0).zia_row(0), ZIARow::Invalid(bits));
+        assert_eq!(
+            messages(&bitstream, "invalid-zia-row"),
+            ["FB0 ZIA row 0 has an invalid mux encoding"]
+        );
+    }
+
+    #[test]
+    fn register_modes() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let mc = bitstream.fb(0).mc(2);
+        bitstream.set_prop(&mc.clk_src(), RegClkSrc::PTC);
+        bitstream.set_prop(&mc.xor_mode(), XorMode::_0);
+        bitstream.set_prop(&mc.is_ddr(), true);
+        bitstream.set_prop(&mc.ff_mode(), FlipFlopMode::DFF);
+        assert_eq!(messages(&bitstream, "ptc-clock-and-xor"), [] as [String; 0]);
+        assert_eq!(messages(&bitstream, "ddr-latch"), [] as [String; 0]);
+
+        bitstream.set_prop(&mc.xor_mode(), XorMode::PTCB);
+        bitstream.set_prop(&mc.ff_mode(), FlipFlopMode::LATCH);
+        assert_eq!(
+            messages(&bitstream, "ptc-clock-and-xor"),
+            ["FB0 MC2 is clocked by PTC, but PTC is also used as an XOR input"]
+        );
+        assert_eq!(
+            messages(&bitstream, "ddr-latch"),
+            ["FB0 MC2 is a latch but has DDR enabled"]
+        );
+    }
+}
@@ -0,0 +1,538 @@
+//! Human-readable Boolean equations for each macrocell
+
+use core::fmt::{self, Write};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{
+    io::{IoFeedbackSource, PinOutputSrc},
+    mc::{FlipFlopMode, MacrocellFeedbackSrc, XorMode},
+    netlist::{Netlist, NodeId, NodeRef, OutputEnable, ZIAInput},
+    MCS_PER_FB,
+};
+
+/// Output syntax for equations
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum EquationSyntax {
+    /// C-like operators (`!`, `&`, `|`, `^`)
+    #[default]
+    Native,
+    /// ABEL-HDL operators and dot extensions
+    Abel,
+    /// CUPL operators and dot extensions
+    Cupl,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EquationOptions {
+    pub syntax: EquationSyntax,
+    /// Names to use for IO pads, indexed by (FB, MC), e.g. from a pinout.
+    /// Pads without an entry are named `FB<n>_IO<n>`.
+    pub pad_names: BTreeMap<(u8, u8), String>,
+}
+
+/// Simple Boolean expression tree, used only for printing
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    fn not(self) -> Self {
+        match self {
+            Expr::Const(x) => Expr::Const(!x),
+            Expr::Not(x) => *x,
+            x => Expr::Not(Box::new(x)),
+        }
+    }
+
+    fn prec(&self) -> u8 {
+        match self {
+            Expr::Or(_) => 0,
+            Expr::Xor(..) => 1,
+            Expr::And(_) => 2,
+            Expr::Not(_) | Expr::Const(_) | Expr::Var(_) => 3,
+        }
+    }
+
+    fn write<W: Write>(&self, syntax: EquationSyntax, w: &mut W) -> fmt::Result {
+        let (or_op, xor_op) = match syntax {
+            EquationSyntax::Native => (" | ", " ^ "),
+            EquationSyntax::Abel | EquationSyntax::Cupl => (" # ", " $ "),
+        };
+        let sub = |x: &Expr, w: &mut W, min_prec: u8| -> fmt::Result {
+            if x.prec() < min_prec {
+                write!(w, "(")?;
+                x.write(syntax, w)?;
+                write!(w, ")")
+            } else {
+                x.write(syntax, w)
+            }
+        };
+
+        match self {
+            Expr::Const(false) => write!(w, "0"),
+            Expr::Const(true) => write!(w, "1"),
+            Expr::Var(v) => write!(w, "{}", v),
+            Expr::Not(x) => {
+                write!(w, "!")?;
+                sub(x, w, 3)
+            }
+            Expr::And(xs) => {
+                for (i, x) in xs.iter().enumerate() {
+                    if i != 0 {
+                        write!(w, " & ")?;
+                    }
+                    sub(x, w, 3)?;
+                }
+                Ok(())
+            }
+            Expr::Or(xs) => {
+                for (i, x) in xs.iter().enumerate() {
+                    if i != 0 {
+                        write!(w, "{}", or_op)?;
+                    }
+                    // Parenthesize product terms so that the sum-of-products is easy to read
+                    sub(x, w, 3)?;
+                }
+                Ok(())
+            }
+            Expr::Xor(a, b) => {
+                // ABEL gives `$` the same precedence as `#`, so don't rely on it at all
+                sub(a, w, 3)?;
+                write!(w, "{}", xor_op)?;
+                sub(b, w, 3)
+            }
+        }
+    }
+}
+
+/// Builds [Expr]s for nodes of a [Netlist]
+pub struct EquationBuilder<'a> {
+    netlist: &'a Netlist,
+    options: &'a EquationOptions,
+}
+impl<'a> EquationBuilder<'a> {
+    pub fn new(netlist: &'a Netlist, options: &'a EquationOptions) -> Self {
+        Self { netlist, options }
+    }
+
+    pub fn pad_name(&self, fb: u8, mc: u8) -> String {
+        match self.options.pad_names.get(&(fb, mc)) {
+            Some(name) => name.clone(),
+            None => format!("FB{fb}_IO{mc}"),
+        }
+    }
+
+    /// Name of the register output of a macrocell
+    pub fn reg_name(&self, fb: u8, mc: u8) -> String {
+        format!("FB{fb}_MC{mc}")
+    }
+
+    /// Name of the combinatorial (XOR gate) output of a macrocell
+    pub fn comb_name(&self, fb: u8, mc: u8) -> String {
+        format!("FB{fb}_MC{mc}_COMB")
+    }
+
+    /// Expression for a signal, looking through ZIA rows and feedback muxes to the real source
+    pub fn signal(&self, id: NodeId) -> Expr {
+        match id {
+            NodeId::GlobalClock(i) => {
+                if self.netlist.gck.get(i as usize).is_some_and(|x| x.enabled) {
+                    Expr::Var(format!("GCK{i}"))
+                } else {
+                    // The netlist treats a disabled clock buffer as constant 0
+                    Expr::Const(false)
+                }
+            }
+            NodeId::GlobalSetReset => {
+                if self.netlist.gsr.enabled {
+                    Expr::Var("GSR".into())
+                } else {
+                    // Never asserted
+                    Expr::Const(false)
+                }
+            }
+            NodeId::GlobalTristate(i) => {
+                if self.netlist.gts.get(i as usize).is_some_and(|x| x.enabled) {
+                    Expr::Var(format!("GTS{i}"))
+                } else {
+                    // Outputs are always enabled
+                    Expr::Const(true)
+                }
+            }
+            NodeId::DedicatedInput => Expr::Var("DEDICATED_INPUT".into()),
+            NodeId::IoPad { fb, mc } => Expr::Var(self.pad_name(fb, mc)),
+            NodeId::Register { fb, mc } => Expr::Var(self.reg_name(fb, mc)),
+            NodeId::Xor { fb, mc } => Expr::Var(self.comb_name(fb, mc)),
+            NodeId::IoFeedback { .. } | NodeId::McFeedback { .. } => {
+                let input = match self.netlist.node(id) {
+                    Some(NodeRef::IoFeedback(x)) => x.input,
+                    Some(NodeRef::McFeedback(x)) => x.input,
+                    _ => None,
+                };
+                match input {
+                    Some(input) => self.signal(input),
+                    // Disabled feedback reads as a constant
+                    None => Expr::Const(false),
+                }
+            }
+            NodeId::ZIARow { fb, row } => {
+                let Some(NodeRef::ZIARow(x)) = self.netlist.node(id) else {
+                    unreachable!()
+                };
+                match x.input {
+                    ZIAInput::Const(val) => Expr::Const(val),
+                    ZIAInput::Node(n) => self.signal(n),
+                    ZIAInput::Invalid => Expr::Var(format!("FB{fb}_ZIA{row}_INVALID")),
+                }
+            }
+            NodeId::AndTerm { .. } => self.and_term(id),
+            NodeId::OrTerm { .. } => self.or_term(id),
+        }
+    }
+
+    /// Expression for a single product term
+    pub fn and_term(&self, id: NodeId) -> Expr {
+        let Some(NodeRef::AndTerm(x)) = self.netlist.node(id) else {
+            unreachable!()
+        };
+        let mut lits = Vec::new();
+        for lit in &x.literals {
+            let v = self.signal(lit.input);
+            let v = if lit.negated { v.not() } else { v };
+            match v {
+                Expr::Const(true) => {}
+                Expr::Const(false) => return Expr::Const(false),
+                v => lits.push(v),
+            }
+        }
+        match lits.len() {
+            0 => Expr::Const(true),
+            1 => lits.pop().unwrap(),
+            _ => Expr::And(lits),
+        }
+    }
+
+    /// Expression for the sum-of-products of an OR term
+    pub fn or_term(&self, id: NodeId) -> Expr {
+        let Some(NodeRef::OrTerm(x)) = self.netlist.node(id) else {
+            unreachable!()
+        };
+        let mut terms = Vec::new();
+        for &pterm in &x.inputs {
+            match self.and_term(pterm) {
+                Expr::Const(false) => {}
+                Expr::Const(true) => return Expr::Const(true),
+                t => terms.push(t),
+            }
+        }
+        match terms.len() {
+            0 => Expr::Const(false),
+            1 => terms.pop().unwrap(),
+            _ => Expr::Or(terms),
+        }
+    }
+
+    /// Expression for the output of the XOR gate of a macrocell
+    pub fn xor(&self, fb: u8, mc: u8) -> Expr {
+        let x = &self.netlist.fbs[fb as usize].xors[mc as usize];
+        let or = self.or_term(x.or);
+        match x.mode {
+            XorMode::_0 => or,
+            XorMode::_1 => or.not(),
+            XorMode::PTC => Expr::Xor(Box::new(or), Box::new(self.and_term(x.ptc))),
+            XorMode::PTCB => Expr::Xor(Box::new(or), Box::new(self.and_term(x.ptc).not())),
+        }
+    }
+}
+
+impl Netlist {
+    /// Whether the XOR gate output of a macrocell goes anywhere
+    pub fn comb_is_used(&self, fb: u8, mc: u8) -> bool {
+        let reg = &self.fbs[fb as usize].registers[mc as usize];
+        self.comb_is_used_outside_reg(fb, mc)
+            || reg.d == (NodeId::Xor { fb, mc }) && self.reg_is_used(fb, mc)
+    }
+
+    /// Whether the XOR gate output of a macrocell goes anywhere other than its own register
+    fn comb_is_used_outside_reg(&self, fb: u8, mc: u8) -> bool {
+        let fb_nodes = &self.fbs[fb as usize];
+        fb_nodes.mc_feedback[mc as usize].setting == MacrocellFeedbackSrc::XorGate
+            || fb_nodes.io_pads[mc as usize].as_ref().is_some_and(|io| {
+                io.output_src == PinOutputSrc::XorGate
+                    && !matches!(io.oe, OutputEnable::Disabled | OutputEnable::Ground)
+            })
+    }
+
+    /// Whether the register output of a macrocell goes anywhere
+    pub fn reg_is_used(&self, fb: u8, mc: u8) -> bool {
+        let fb_nodes = &self.fbs[fb as usize];
+        fb_nodes.mc_feedback[mc as usize].setting == MacrocellFeedbackSrc::FlipFlop
+            || fb_nodes.io_feedback[mc as usize]
+                .as_ref()
+                .is_some_and(|x| x.setting == IoFeedbackSource::FlipFlop)
+            || fb_nodes.io_pads[mc as usize].as_ref().is_some_and(|io| {
+                io.output_src == PinOutputSrc::FlipFlop
+                    && !matches!(io.oe, OutputEnable::Disabled | OutputEnable::Ground)
+            })
+    }
+
+    /// Write equations describing the logic of every used macrocell and pad
+    pub fn write_equations<W: Write>(&self, options: &EquationOptions, w: &mut W) -> fmt::Result {
+        let b = EquationBuilder::new(self, options);
+        let syntax = options.syntax;
+        let comment = |w: &mut W, s: &str| -> fmt::Result {
+            match syntax {
+                EquationSyntax::Native => writeln!(w, "// {}", s),
+                EquationSyntax::Abel => writeln!(w, "\" {}", s),
+                EquationSyntax::Cupl => writeln!(w, "/* {} */", s),
+            }
+        };
+        let eqn = |w: &mut W, lhs: &str, rhs: &Expr| -> fmt::Result {
+            write!(w, "{} = ", lhs)?;
+            rhs.write(syntax, w)?;
+            writeln!(w, ";")
+        };
+        let (ext_clk, ext_latch, ext_set) = match syntax {
+            EquationSyntax::Native => ("CLK", "L", "AP"),
+            EquationSyntax::Abel => ("CLK", "D", "AP"),
+            EquationSyntax::Cupl => ("CK", "L", "SP"),
+        };
+
+        for (i, gck) in self.gck.iter().enumerate() {
+            if gck.enabled {
+                if let Some(div) = gck.divider {
+                    comment(
+                        w,
+                        &format!("GCK{i} is divided by {}", div.ratio.divisor()),
+                    )?;
+                }
+            }
+        }
+        if self.gsr.enabled {
+            let pin = Expr::Var("GSR_PIN".into());
+            eqn(w, "GSR", &if self.gsr.invert { pin.not() } else { pin })?;
+        }
+        for (i, gts) in self.gts.iter().enumerate() {
+            if gts.enabled {
+                // GTSn is the enable, i.e. active when the pin is not asserted
+                let pin = Expr::Var(format!("GTS{i}_PIN"));
+                eqn(
+                    w,
+                    &format!("GTS{i}"),
+                    &if gts.invert { pin } else { pin.not() },
+                )?;
+            }
+        }
+
+        for fb in 0..self.fbs.len() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                // A register fed only by its own XOR gate gets the logic inline
+                let comb_used = self.comb_is_used_outside_reg(fb, mc);
+                let reg_used = self.reg_is_used(fb, mc);
+
+                if comb_used {
+                    eqn(w, &b.comb_name(fb, mc), &b.xor(fb, mc))?;
+                }
+
+                if reg_used {
+                    let reg = &self.fbs[fb as usize].registers[mc as usize];
+                    let name = b.reg_name(fb, mc);
+                    let d = if reg.d == (NodeId::Xor { fb, mc }) && !comb_used {
+                        b.xor(fb, mc)
+                    } else {
+                        b.signal(reg.d)
+                    };
+                    let clk = b.signal(reg.clk);
+                    let clk = if reg.clk_inv { clk.not() } else { clk };
+
+                    match reg.mode {
+                        FlipFlopMode::DFF | FlipFlopMode::DFFCE => {
+                            eqn(w, &format!("{name}.D"), &d)?;
+                            eqn(w, &format!("{name}.{ext_clk}"), &clk)?;
+                        }
+                        FlipFlopMode::TFF => {
+                            eqn(w, &format!("{name}.T"), &d)?;
+                            eqn(w, &format!("{name}.{ext_clk}"), &clk)?;
+                        }
+                        FlipFlopMode::LATCH => {
+                            eqn(w, &format!("{name}.{ext_latch}"), &d)?;
+                            eqn(w, &format!("{name}.LE"), &clk)?;
+                        }
+                    }
+                    if reg.is_ddr {
+                        comment(w, &format!("{name} is clocked on both edges"))?;
+                    }
+                    if let Some(ce) = reg.ce {
+                        eqn(w, &format!("{name}.CE"), &b.signal(ce))?;
+                    }
+                    if let Some(reset) = reg.reset {
+                        eqn(w, &format!("{name}.AR"), &b.signal(reset))?;
+                    }
+                    if let Some(set) = reg.set {
+                        eqn(w, &format!("{name}.{ext_set}"), &b.signal(set))?;
+                    }
+                    if !reg.init_state {
+                        comment(w, &format!("{name} powers up as 0"))?;
+                    }
+                }
+
+                if let Some(io) = &self.fbs[fb as usize].io_pads[mc as usize] {
+                    let name = b.pad_name(fb, mc);
+                    match io.oe {
+                        OutputEnable::Disabled => {}
+                        OutputEnable::Always => {
+                            eqn(w, &name, &b.signal(io.output_data))?;
+                        }
+                        OutputEnable::OpenDrain => {
+                            eqn(w, &name, &Expr::Const(false))?;
+                            eqn(w, &format!("{name}.OE"), &b.signal(io.output_data).not())?;
+                        }
+                        OutputEnable::Node(oe) => {
+                            eqn(w, &name, &b.signal(io.output_data))?;
+                            eqn(w, &format!("{name}.OE"), &b.signal(oe))?;
+                        }
+                        OutputEnable::Ground => {
+                            eqn(w, &name, &Expr::Const(false))?;
+                        }
+                        OutputEnable::Invalid => {
+                            comment(w, &format!("{name} has an invalid output mode"))?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Equations describing the logic of every used macrocell and pad, as a string
+    pub fn equations(&self, options: &EquationOptions) -> String {
+        let mut ret = String::new();
+        self.write_equations(options, &mut ret).unwrap();
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bitstream::Coolrunner2,
+        io::OutputMode,
+        mc::{RegClkSrc, RegResetSrc},
+        partdb::{XC2Device, XC2Part},
+        zia::{ZIARow, ZIATableEntry},
+    };
+
+    #[test]
+    fn xor_precedence() {
+        let a = Expr::Var("a".into());
+        let b = Expr::Var("b".into());
+        let x = Expr::Xor(Box::new(Expr::Or(alloc::vec![a.clone(), b])), Box::new(a.not()));
+        let mut s = String::new();
+        x.write(EquationSyntax::Abel, &mut s).unwrap();
+        assert_eq!(s, "(a # b) $ !a");
+    }
+
+    #[test]
+    fn golden() {
+        let device = XC2Device::XC2C32A;
+        let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        for fb in 0..device.num_fbs() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                let x = bitstream.fb(fb).mc(mc);
+                bitstream.set_prop(&x.fb_src(), MacrocellFeedbackSrc::Disabled);
+                bitstream.set_prop(&x.use_iob(), false);
+                if device.has_io_at(fb, mc) {
+                    let io = bitstream.fb(fb).io(mc);
+                    bitstream.set_prop(&io.output_pad_mode(), OutputMode::Disabled);
+                    bitstream.set_prop(&io.fb_src(), IoFeedbackSource::Disabled);
+                }
+            }
+        }
+        for i in 0..4 {
+            bitstream.set_prop(&bitstream.gts_enabled(i), false);
+        }
+
+        // Inputs a and b
+        let row_a = device.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        let row_b = *device
+            .zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 6 })
+            .iter()
+            .find(|x| x.row != row_a.row)
+            .unwrap();
+        for (mc, x) in [(5, row_a), (6, row_b)] {
+            bitstream.set_prop(&bitstream.fb(0).io(mc).fb_src(), IoFeedbackSource::IO);
+            bitstream.set_prop(&bitstream.fb(0).zia_row(x.row), ZIARow::MuxChoice(x.choice));
+        }
+        let fb = bitstream.fb(0);
+
+        // y = !a
+        bitstream.set_prop(&fb.and_term(0).inp_n(row_a.row), true);
+        bitstream.set_prop(&fb.or_term(3).inp(0), true);
+        bitstream.set_prop(&fb.io(3).output_src(), PinOutputSrc::XorGate);
+        bitstream.set_prop(&fb.io(3).output_pad_mode(), OutputMode::PushPull);
+
+        // q is a register of a, reset by b
+        bitstream.set_prop(&fb.and_term(1).inp(row_a.row), true);
+        bitstream.set_prop(&fb.or_term(4).inp(1), true);
+        bitstream.set_prop(&bitstream.gck_enabled(0), true);
+        bitstream.set_prop(&fb.mc(4).clk_src(), RegClkSrc::GCK0);
+        bitstream.set_prop(&fb.and_term(crate::fb::pta(4)).inp(row_b.row), true);
+        bitstream.set_prop(&fb.mc(4).r_src(), RegResetSrc::PTA);
+        bitstream.set_prop(&fb.io(4).output_src(), PinOutputSrc::FlipFlop);
+        bitstream.set_prop(&fb.io(4).output_pad_mode(), OutputMode::PushPull);
+
+        // z = !a while b
+        bitstream.set_prop(&fb.or_term(7).inp(0), true);
+        bitstream.set_prop(&fb.and_term(crate::fb::ptb(7)).inp(row_b.row), true);
+        bitstream.set_prop(&fb.io(7).output_src(), PinOutputSrc::XorGate);
+        bitstream.set_prop(&fb.io(7).output_pad_mode(), OutputMode::TriStatePTB);
+
+        let mut options = EquationOptions::default();
+        for (mc, name) in [(3, "y"), (4, "q"), (5, "a"), (6, "b"), (7, "z")] {
+            options.pad_names.insert((0, mc), name.into());
+        }
+        assert_eq!(
+            bitstream.netlist().equations(&options),
+            "FB0_MC3_COMB = !a;\n\
+             y = FB0_MC3_COMB;\n\
+             FB0_MC4.D = a;\n\
+             FB0_MC4.CLK = GCK0;\n\
+             FB0_MC4.AR = b;\n\
+             q = FB0_MC4;\n\
+             FB0_MC7_COMB = !a;\n\
+             z = FB0_MC7_COMB;\n\
+             z.OE = b;\n"
+        );
+
+        options.syntax = EquationSyntax::Cupl;
+        bitstream.set_prop(&bitstream.gck_enabled(0), false);
+        let cupl = bitstream.netlist().equations(&options);
+        assert!(cupl.contains("FB0_MC4.CK = 0;\n"));
+
+        // Disabled global nets read as never asserted, with no equation of their own
+        bitstream.set_prop(&fb.mc(4).r_src(), RegResetSrc::GSR);
+        bitstream.set_prop(&fb.io(7).output_pad_mode(), OutputMode::TriStateGTS0);
+        let cupl = bitstream.netlist().equations(&options);
+        assert!(cupl.contains("FB0_MC4.AR = 0;\n"));
+        assert!(cupl.contains("z.OE = 1;\n"));
+        assert!(!cupl.contains("GSR") && !cupl.contains("GTS"));
+
+        bitstream.set_prop(&bitstream.gsr_enabled(), true);
+        bitstream.set_prop(&bitstream.gts_enabled(0), true);
+        let cupl = bitstream.netlist().equations(&options);
+        assert!(cupl.contains("FB0_MC4.AR = GSR;\n"));
+        assert!(cupl.contains("z.OE = GTS0;\n"));
+    }
+}
@@ -0,0 +1,577 @@
+//! Semantic equivalence checking between two bitstreams
+//!
+//! Two bitstreams are compared by what they do rather than by their fuses, so permuted product
+//! terms, different ZIA row assignments and garbage in unused terms do not matter.
+//!
+//! IO pads are matched by location. Registers are matched by location as well: the state of the
+//! register in a given macrocell is the same variable in both bitstreams, and the clock,
+//! next-state, set and reset functions of registers used in both are compared in addition to
+//! everything observable at the pads. This means that moving a buried register to a different
+//! macrocell is reported as a difference even though it may not be observable.
+//!
+//! Pad inputs are treated as free variables, even if the device is itself driving the pad.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{
+    bdd::{Bdd, BddRef},
+    bitstream::{BitHolder, Coolrunner2},
+    mc::{FlipFlopMode, XorMode},
+    netlist::{Netlist, NodeId, NodeRef, OutputEnable, ZIAInput},
+    partdb::XC2Device,
+    MCS_PER_FB,
+};
+
+/// A primary input or register state that the logic depends on
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum Signal {
+    /// The value on an IO pad
+    Pad { fb: u8, mc: u8 },
+    /// The input-only pin on the XC2C32(A)
+    DedicatedInput,
+    /// A global clock pin
+    GlobalClock(u8),
+    /// The output of the clock divider
+    DividedClock { divisor: u32, delay: bool },
+    /// The global set/reset pin
+    GlobalSetReset,
+    /// A global tristate pin
+    GlobalTristate(u8),
+    /// The current state of a register
+    Register { fb: u8, mc: u8 },
+}
+impl Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signal::Pad { fb, mc } => write!(f, "FB{fb}_IO{mc}"),
+            Signal::DedicatedInput => write!(f, "DEDICATED_INPUT"),
+            Signal::GlobalClock(i) => write!(f, "GCK{i}"),
+            Signal::DividedClock { divisor, delay } => {
+                write!(f, "GCK2_DIV{divisor}")?;
+                if *delay {
+                    write!(f, "_DELAY")?;
+                }
+                Ok(())
+            }
+            Signal::GlobalSetReset => write!(f, "GSR_PIN"),
+            Signal::GlobalTristate(i) => write!(f, "GTS{i}_PIN"),
+            Signal::Register { fb, mc } => write!(f, "FB{fb}_MC{mc}"),
+        }
+    }
+}
+
+/// Something whose behavior is compared between the two bitstreams
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum Observable {
+    /// Whether the pad is being driven
+    PadOutputEnable { fb: u8, mc: u8 },
+    /// The value driven onto the pad, while both bitstreams are driving it
+    PadOutputValue { fb: u8, mc: u8 },
+    /// Latch vs. flip-flop and single vs. double data rate
+    RegisterMode { fb: u8, mc: u8 },
+    RegisterInitState { fb: u8, mc: u8 },
+    RegisterClock { fb: u8, mc: u8 },
+    /// The value the register takes when clocked (or when transparent, for latches)
+    RegisterNextState { fb: u8, mc: u8 },
+    RegisterSet { fb: u8, mc: u8 },
+    RegisterReset { fb: u8, mc: u8 },
+}
+impl Display for Observable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Observable::PadOutputEnable { fb, mc } => write!(f, "FB{fb} IO{mc} output enable"),
+            Observable::PadOutputValue { fb, mc } => write!(f, "FB{fb} IO{mc} output value"),
+            Observable::RegisterMode { fb, mc } => write!(f, "FB{fb} MC{mc} register mode"),
+            Observable::RegisterInitState { fb, mc } => {
+                write!(f, "FB{fb} MC{mc} register initial state")
+            }
+            Observable::RegisterClock { fb, mc } => write!(f, "FB{fb} MC{mc} register clock"),
+            Observable::RegisterNextState { fb, mc } => {
+                write!(f, "FB{fb} MC{mc} register next state")
+            }
+            Observable::RegisterSet { fb, mc } => write!(f, "FB{fb} MC{mc} register set"),
+            Observable::RegisterReset { fb, mc } => write!(f, "FB{fb} MC{mc} register reset"),
+        }
+    }
+}
+
+/// Input and state values under which the two bitstreams differ
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Counterexample {
+    /// Values of the relevant signals. Signals that are not listed are 0.
+    pub assignment: Vec<(Signal, bool)>,
+    /// Value of the observable in the first bitstream
+    pub a: bool,
+    /// Value of the observable in the second bitstream
+    pub b: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mismatch {
+    pub observable: Observable,
+    /// `None` if the difference is in a setting rather than a logic function
+    pub counterexample: Option<Counterexample>,
+}
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} differs", self.observable)?;
+        if let Some(cex) = &self.counterexample {
+            write!(f, " when")?;
+            if cex.assignment.is_empty() {
+                write!(f, " all inputs are 0")?;
+            }
+            for (i, (sig, val)) in cex.assignment.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, " {}={}", sig, *val as u8)?;
+            }
+            write!(f, " ({} vs {})", cex.a as u8, cex.b as u8)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EquivalenceError {
+    DeviceMismatch(XC2Device, XC2Device),
+    InvalidZIARow { fb: u8, row: u8 },
+    InvalidOutputMode { fb: u8, mc: u8 },
+    /// A loop through the XOR gate feedback path. The node is one of the nodes in the loop.
+    CombinationalLoop(NodeId),
+}
+impl Display for EquivalenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivalenceError::DeviceMismatch(a, b) => {
+                write!(f, "cannot compare an {} with an {}", a, b)
+            }
+            EquivalenceError::InvalidZIARow { fb, row } => {
+                write!(f, "FB{fb} ZIA row {row} has an invalid mux encoding")
+            }
+            EquivalenceError::InvalidOutputMode { fb, mc } => {
+                write!(f, "FB{fb} IO{mc} has an invalid output mode encoding")
+            }
+            EquivalenceError::CombinationalLoop(id) => {
+                write!(f, "combinational loop through {:?}", id)
+            }
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for EquivalenceError {}
+
+pub(crate) struct Vars {
+    map: BTreeMap<Signal, u32>,
+    /// The signal for each BDD variable
+    pub(crate) list: Vec<Signal>,
+}
+impl Vars {
+    pub(crate) fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            list: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, bdd: &mut Bdd, sig: Signal) -> BddRef {
+        let idx = *self.map.entry(sig).or_insert_with(|| {
+            self.list.push(sig);
+            (self.list.len() - 1) as u32
+        });
+        bdd.var(idx)
+    }
+}
+
+/// Builds BDDs for the nodes of one netlist
+pub(crate) struct Evaluator<'a> {
+    netlist: &'a Netlist,
+    memo: BTreeMap<NodeId, BddRef>,
+    in_progress: BTreeSet<NodeId>,
+}
+impl<'a> Evaluator<'a> {
+    pub(crate) fn new(netlist: &'a Netlist) -> Self {
+        Self {
+            netlist,
+            memo: BTreeMap::new(),
+            in_progress: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn eval(
+        &mut self,
+        bdd: &mut Bdd,
+        vars: &mut Vars,
+        id: NodeId,
+    ) -> Result<BddRef, EquivalenceError> {
+        if let Some(&x) = self.memo.get(&id) {
+            return Ok(x);
+        }
+        if !self.in_progress.insert(id) {
+            return Err(EquivalenceError::CombinationalLoop(id));
+        }
+
+        let ret = match self.netlist.node(id).unwrap() {
+            NodeRef::GlobalClock(x) => {
+                if !x.enabled {
+                    BddRef::FALSE
+                } else if let Some(div) = x.divider {
+                    vars.get(
+                        bdd,
+                        Signal::DividedClock {
+                            divisor: div.ratio.divisor(),
+                            delay: div.delay,
+                        },
+                    )
+                } else {
+                    let NodeId::GlobalClock(i) = id else {
+                        unreachable!()
+                    };
+                    vars.get(bdd, Signal::GlobalClock(i))
+                }
+            }
+            NodeRef::GlobalSetReset(x) => {
+                if x.enabled {
+                    let pin = vars.get(bdd, Signal::GlobalSetReset);
+                    let invert = BddRef::constant(x.invert);
+                    bdd.xor(pin, invert)
+                } else {
+                    BddRef::FALSE
+                }
+            }
+            NodeRef::GlobalTristate(x) => {
+                if x.enabled {
+                    let NodeId::GlobalTristate(i) = id else {
+                        unreachable!()
+                    };
+                    let pin = vars.get(bdd, Signal::GlobalTristate(i));
+                    // Outputs are enabled when the (possibly inverted) pin is low
+                    let invert = BddRef::constant(!x.invert);
+                    bdd.xor(pin, invert)
+                } else {
+                    BddRef::TRUE
+                }
+            }
+            NodeRef::DedicatedInput(_) => vars.get(bdd, Signal::DedicatedInput),
+            NodeRef::IoPad(_) => {
+                let NodeId::IoPad { fb, mc } = id else {
+                    unreachable!()
+                };
+                vars.get(bdd, Signal::Pad { fb, mc })
+            }
+            NodeRef::IoFeedback(x) => match x.input {
+                Some(input) => self.eval(bdd, vars, input)?,
+                None => BddRef::FALSE,
+            },
+            NodeRef::McFeedback(x) => match x.input {
+                Some(input) => self.eval(bdd, vars, input)?,
+                None => BddRef::FALSE,
+            },
+            NodeRef::ZIARow(x) => match x.input {
+                ZIAInput::Const(val) => BddRef::constant(val),
+                ZIAInput::Node(n) => self.eval(bdd, vars, n)?,
+                ZIAInput::Invalid => {
+                    let NodeId::ZIARow { fb, row } = id else {
+                        unreachable!()
+                    };
+                    return Err(EquivalenceError::InvalidZIARow { fb, row });
+                }
+            },
+            NodeRef::AndTerm(x) => {
+                let mut ret = BddRef::TRUE;
+                for lit in &x.literals {
+                    let mut v = self.eval(bdd, vars, lit.input)?;
+                    if lit.negated {
+                        v = bdd.not(v);
+                    }
+                    ret = bdd.and(ret, v);
+                }
+                ret
+            }
+            NodeRef::OrTerm(x) => {
+                let mut ret = BddRef::FALSE;
+                for &input in &x.inputs {
+                    let v = self.eval(bdd, vars, input)?;
+                    ret = bdd.or(ret, v);
+                }
+                ret
+            }
+            NodeRef::Xor(x) => {
+                let or = self.eval(bdd, vars, x.or)?;
+                match x.mode {
+                    XorMode::_0 => or,
+                    XorMode::_1 => bdd.not(or),
+                    XorMode::PTC => {
+                        let ptc = self.eval(bdd, vars, x.ptc)?;
+                        bdd.xor(or, ptc)
+                    }
+                    XorMode::PTCB => {
+                        let ptc = self.eval(bdd, vars, x.ptc)?;
+                        let x = bdd.xor(or, ptc);
+                        bdd.not(x)
+                    }
+                }
+            }
+            NodeRef::Register(_) => {
+                let NodeId::Register { fb, mc } = id else {
+                    unreachable!()
+                };
+                vars.get(bdd, Signal::Register { fb, mc })
+            }
+        };
+
+        self.in_progress.remove(&id);
+        self.memo.insert(id, ret);
+        Ok(ret)
+    }
+
+    fn eval_opt(
+        &mut self,
+        bdd: &mut Bdd,
+        vars: &mut Vars,
+        id: Option<NodeId>,
+    ) -> Result<BddRef, EquivalenceError> {
+        match id {
+            Some(id) => self.eval(bdd, vars, id),
+            None => Ok(BddRef::FALSE),
+        }
+    }
+
+    /// Returns (output enable, output value)
+    pub(crate) fn pad(
+        &mut self,
+        bdd: &mut Bdd,
+        vars: &mut Vars,
+        fb: u8,
+        mc: u8,
+    ) -> Result<(BddRef, BddRef), EquivalenceError> {
+        let io = self.netlist.fbs[fb as usize].io_pads[mc as usize]
+            .as_ref()
+            .unwrap();
+        Ok(match io.oe {
+            OutputEnable::Disabled => (BddRef::FALSE, BddRef::FALSE),
+            OutputEnable::Always => (BddRef::TRUE, self.eval(bdd, vars, io.output_data)?),
+            OutputEnable::OpenDrain => {
+                let data = self.eval(bdd, vars, io.output_data)?;
+                (bdd.not(data), BddRef::FALSE)
+            }
+            OutputEnable::Node(oe) => (
+                self.eval(bdd, vars, oe)?,
+                self.eval(bdd, vars, io.output_data)?,
+            ),
+            OutputEnable::Ground => (BddRef::TRUE, BddRef::FALSE),
+            OutputEnable::Invalid => return Err(EquivalenceError::InvalidOutputMode { fb, mc }),
+        })
+    }
+
+    /// Returns (clock, next state, set, reset)
+    fn register(
+        &mut self,
+        bdd: &mut Bdd,
+        vars: &mut Vars,
+        fb: u8,
+        mc: u8,
+    ) -> Result<[BddRef; 4], EquivalenceError> {
+        let reg = &self.netlist.fbs[fb as usize].registers[mc as usize];
+        let clk = self.eval(bdd, vars, reg.clk)?;
+        let clk = bdd.xor(clk, BddRef::constant(reg.clk_inv));
+        let d = self.eval(bdd, vars, reg.d)?;
+        let q = vars.get(bdd, Signal::Register { fb, mc });
+        let next = match reg.mode {
+            FlipFlopMode::DFF | FlipFlopMode::LATCH => d,
+            FlipFlopMode::TFF => bdd.xor(d, q),
+            FlipFlopMode::DFFCE => {
+                let ce = self.eval_opt(bdd, vars, reg.ce)?;
+                bdd.mux(ce, d, q)
+            }
+        };
+        let set = self.eval_opt(bdd, vars, reg.set)?;
+        let reset = self.eval_opt(bdd, vars, reg.reset)?;
+        Ok([clk, next, set, reset])
+    }
+}
+
+struct Checker {
+    bdd: Bdd,
+    vars: Vars,
+    mismatches: Vec<Mismatch>,
+}
+impl Checker {
+    fn compare(&mut self, observable: Observable, a: BddRef, b: BddRef, care: BddRef) {
+        let diff = self.bdd.xor(a, b);
+        let diff = self.bdd.and(diff, care);
+        if let Some(sat) = self.bdd.sat_one(diff) {
+            let lookup = |var| sat.iter().any(|&(v, val)| v == var && val);
+            let counterexample = Counterexample {
+                a: self.bdd.eval(a, lookup),
+                b: self.bdd.eval(b, lookup),
+                assignment: sat
+                    .iter()
+                    .map(|&(var, val)| (self.vars.list[var as usize], val))
+                    .collect(),
+            };
+            self.mismatches.push(Mismatch {
+                observable,
+                counterexample: Some(counterexample),
+            });
+        }
+    }
+}
+
+/// Compare two bitstreams for the same device.
+///
+/// Returns every difference found, so an empty list means that the bitstreams are equivalent.
+#[allow(private_bounds)]
+pub fn check_equivalence<A: BitHolder, B: BitHolder>(
+    a: &Coolrunner2<A>,
+    b: &Coolrunner2<B>,
+) -> Result<Vec<Mismatch>, EquivalenceError> {
+    let device = a.part.device;
+    if b.part.device != device {
+        return Err(EquivalenceError::DeviceMismatch(device, b.part.device));
+    }
+    let netlist_a = a.netlist();
+    let netlist_b = b.netlist();
+    let mut eval_a = Evaluator::new(&netlist_a);
+    let mut eval_b = Evaluator::new(&netlist_b);
+    let mut c = Checker {
+        bdd: Bdd::new(),
+        vars: Vars::new(),
+        mismatches: Vec::new(),
+    };
+
+    for fb in 0..device.num_fbs() as u8 {
+        for mc in 0..MCS_PER_FB as u8 {
+            if device.has_io_at(fb, mc) {
+                let (oe_a, val_a) = eval_a.pad(&mut c.bdd, &mut c.vars, fb, mc)?;
+                let (oe_b, val_b) = eval_b.pad(&mut c.bdd, &mut c.vars, fb, mc)?;
+                c.compare(
+                    Observable::PadOutputEnable { fb, mc },
+                    oe_a,
+                    oe_b,
+                    BddRef::TRUE,
+                );
+                // The value only matters while both sides are driving
+                let both = c.bdd.and(oe_a, oe_b);
+                c.compare(Observable::PadOutputValue { fb, mc }, val_a, val_b, both);
+            }
+
+            if !netlist_a.reg_is_used(fb, mc) || !netlist_b.reg_is_used(fb, mc) {
+                continue;
+            }
+            let reg_a = &netlist_a.fbs[fb as usize].registers[mc as usize];
+            let reg_b = &netlist_b.fbs[fb as usize].registers[mc as usize];
+            if (reg_a.mode == FlipFlopMode::LATCH) != (reg_b.mode == FlipFlopMode::LATCH)
+                || reg_a.is_ddr != reg_b.is_ddr
+            {
+                c.mismatches.push(Mismatch {
+                    observable: Observable::RegisterMode { fb, mc },
+                    counterexample: None,
+                });
+                continue;
+            }
+            if reg_a.init_state != reg_b.init_state {
+                c.mismatches.push(Mismatch {
+                    observable: Observable::RegisterInitState { fb, mc },
+                    counterexample: None,
+                });
+            }
+            let fns_a = eval_a.register(&mut c.bdd, &mut c.vars, fb, mc)?;
+            let fns_b = eval_b.register(&mut c.bdd, &mut c.vars, fb, mc)?;
+            let observables = [
+                Observable::RegisterClock { fb, mc },
+                Observable::RegisterNextState { fb, mc },
+                Observable::RegisterSet { fb, mc },
+                Observable::RegisterReset { fb, mc },
+            ];
+            for i in 0..4 {
+                c.compare(observables[i], fns_a[i], fns_b[i], BddRef::TRUE);
+            }
+        }
+    }
+
+    Ok(c.mismatches)
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Check whether this bitstream behaves the same as another. See [check_equivalence].
+    pub fn check_equivalence<B2: BitHolder>(
+        &self,
+        other: &Coolrunner2<B2>,
+    ) -> Result<Vec<Mismatch>, EquivalenceError> {
+        check_equivalence(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::boxed::BitBox;
+    use crate::{io::OutputMode, io::PinOutputSrc, partdb::XC2Part, zia::ZIARow};
+
+    fn inverter(pterm: u8, negated: bool) -> Coolrunner2<BitBox> {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.fb(0).zia_row(3), ZIARow::MuxChoice(0));
+        if negated {
+            bitstream.set_prop(&bitstream.fb(0).and_term(pterm).inp_n(3), true);
+        } else {
+            bitstream.set_prop(&bitstream.fb(0).and_term(pterm).inp(3), true);
+        }
+        bitstream.set_prop(&bitstream.fb(0).or_term(5).inp(pterm), true);
+        bitstream.set_prop(&bitstream.fb(0).mc(5).xor_mode(), XorMode::_0);
+        bitstream.set_prop(&bitstream.fb(0).io(5).output_src(), PinOutputSrc::XorGate);
+        bitstream.set_prop(&bitstream.fb(0).io(5).output_pad_mode(), OutputMode::PushPull);
+        bitstream
+    }
+
+    #[test]
+    fn permuted_pterms() {
+        let a = inverter(20, true);
+        let b = inverter(30, true);
+        assert_eq!(a.check_equivalence(&b), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn counterexample() {
+        let a = inverter(20, true);
+        let b = inverter(30, false);
+        let mismatches = a.check_equivalence(&b).unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            mismatches[0].observable,
+            Observable::PadOutputValue { fb: 0, mc: 5 }
+        );
+        let cex = mismatches[0].counterexample.as_ref().unwrap();
+        assert_ne!(cex.a, cex.b);
+    }
+
+    #[test]
+    fn output_enable_and_value() {
+        let a = inverter(20, true);
+        // Enabled by the input, and not inverted
+        let mut b = inverter(30, false);
+        b.set_prop(&b.fb(0).and_term(crate::fb::ptb(5)).inp(3), true);
+        b.set_prop(&b.fb(0).io(5).output_pad_mode(), OutputMode::TriStatePTB);
+
+        let mismatches = a.check_equivalence(&b).unwrap();
+        let observables: Vec<_> = mismatches.iter().map(|x| x.observable).collect();
+        assert_eq!(
+            observables,
+            [
+                Observable::PadOutputEnable { fb: 0, mc: 5 },
+                Observable::PadOutputValue { fb: 0, mc: 5 }
+            ]
+        );
+        // Both drive while the input is high, and then disagree
+        let cex = mismatches[1].counterexample.as_ref().unwrap();
+        assert!(!cex.a && cex.b);
+    }
+}
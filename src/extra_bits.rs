@@ -1,7 +1,19 @@
 //! Bits we don't know about
 
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
 use bittwiddler_core::prelude::Coordinate;
+#[cfg(feature = "alloc")]
+use bittwiddler_core::prelude::BitArray;
 
+#[cfg(feature = "alloc")]
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    jed::JedecCompat,
+};
 use crate::partdb::XC2Device;
 
 pub const fn is_unknown_bit(device: XC2Device, c: Coordinate) -> bool {
@@ -4853,3 +4865,93 @@ pub const fn is_unknown_bit(device: XC2Device, c: Coordinate) -> bool {
         },
     }
 }
+
+/// An unknown bit that does not have the value it has in a blank bitstream
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct UnknownBitSet {
+    pub coordinate: Coordinate,
+    /// The current value of the bit
+    pub value: bool,
+    /// Where the bit is in a JED file, if it is in the JED file at all
+    pub jed_index: Option<usize>,
+}
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for UnknownBitSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "unknown bit ({}, {}) is {}",
+            self.coordinate.x, self.coordinate.y, self.value as u8
+        )?;
+        if let Some(jed_index) = self.jed_index {
+            write!(f, " (JED fuse {})", jed_index)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Find all bits listed by [is_unknown_bit] that are not at their erased value.
+    ///
+    /// Anything reported here is either an undocumented setting or a sign of a corrupt file.
+    /// The `read_*_with_warnings` functions run this after reading a file.
+    pub fn audit_unknown_bits(&self) -> Vec<UnknownBitSet> {
+        let device = self.part.device;
+        let blank = Coolrunner2::new(self.part);
+        let (w, h) = device.fuse_array_dims();
+
+        let mut ret = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let coordinate = Coordinate::new(x, y);
+                if !is_unknown_bit(device, coordinate) {
+                    continue;
+                }
+                let value = self.get(coordinate);
+                if value != blank.get(coordinate) {
+                    ret.push(UnknownBitSet {
+                        coordinate,
+                        value,
+                        jed_index: None,
+                    });
+                }
+            }
+        }
+
+        if !ret.is_empty() {
+            let mut jed_indices = vec![None; w * h];
+            for jed_idx in 0..device.num_jed_fuses() {
+                let c = device.jed_index_to_crbit(jed_idx);
+                jed_indices[c.y * w + c.x] = Some(jed_idx);
+            }
+            for x in &mut ret {
+                x.jed_index = jed_indices[x.coordinate.y * w + x.coordinate.x];
+            }
+        }
+
+        ret
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::partdb::XC2Part;
+
+    #[test]
+    fn blank_has_no_unknown_bits_set() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        assert_eq!(bitstream.audit_unknown_bits(), []);
+
+        let c = Coordinate::new(130, 20);
+        assert!(is_unknown_bit(XC2Device::XC2C32A, c));
+        bitstream.set(c, false);
+        let report = bitstream.audit_unknown_bits();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].coordinate, c);
+        assert!(!report[0].value);
+    }
+}
@@ -8,6 +8,28 @@ use crate::{
     ZIA_ROWS,
 };
 
+/// Index of the product term used as the shared clock (CTC)
+pub const CTC: u8 = 4;
+/// Index of the product term used as the shared reset (CTR)
+pub const CTR: u8 = 5;
+/// Index of the product term used as the shared set (CTS)
+pub const CTS: u8 = 6;
+/// Index of the product term used as the shared output enable (CTE)
+pub const CTE: u8 = 7;
+
+/// Index of the dedicated product term A (set/reset) for the given macrocell
+pub const fn pta(mc: u8) -> u8 {
+    3 * mc + 8
+}
+/// Index of the dedicated product term B (output enable) for the given macrocell
+pub const fn ptb(mc: u8) -> u8 {
+    3 * mc + 9
+}
+/// Index of the dedicated product term C (clock, clock enable, or XOR input) for the given macrocell
+pub const fn ptc(mc: u8) -> u8 {
+    3 * mc + 10
+}
+
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct FunctionBlock {
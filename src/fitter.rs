@@ -0,0 +1,519 @@
+//! Fitting designs into a device
+//!
+//! The fitter takes a [Design] where some or all inputs and macrocells have no location yet and
+//! places them, then builds the result. Pin constraints are applied first and are never moved.
+//! Macrocells driving pads are placed first since they need both a macrocell and a pad, then
+//! inputs, then buried macrocells. Each macrocell goes to the function block that already uses the
+//! most of its inputs, as long as the ZIA can still route it and its product terms still fit.
+//!
+//! Sources that have not been placed yet cannot be checked against the ZIA, so it is still
+//! possible for building to fail after placement succeeds.
+//!
+//! Designs are built with the [crate::design] types directly or read with [crate::yosys]. Reading
+//! PLA or equation files, and a command-line fitter, are out of scope for this crate.
+//!
+//! The crate has no data on which pads are in which IO bank. On devices with more than one bank,
+//! every pad with an IO standard needs an entry in [Design::pad_bank] filled in by hand from the
+//! datasheet, and the fitter only places such pads where that map has an entry.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bitvec::boxed::BitBox;
+
+use crate::{
+    bitstream::Coolrunner2,
+    design::{Design, DesignError, InputLoc, IoStandard, MacrocellDef, ProductTerm},
+    io::IoVoltage,
+    partdb::XC2Device,
+    pterms::AndTerm,
+    zia::ZIATableEntry,
+    zia_router::{route_zia, ZIARouting},
+    MCS_PER_FB, ZIA_ROWS,
+};
+
+/// Locations that inputs and macrocells must be placed at, by name. Macrocells can only be locked
+/// to [InputLoc::Pad], which places them in the macrocell of that pad.
+pub type PinConstraints = BTreeMap<String, InputLoc>;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FitFailure {
+    /// A constraint names something that is not in the design
+    UnknownConstraint(String),
+    /// A macrocell is locked to the dedicated input
+    InvalidConstraint(String),
+    /// Inputs and outputs that could not be given a pad, either because none are left or because
+    /// every free pad is in a bank with a conflicting IO standard
+    Pads(Vec<String>),
+    /// Macrocells that could not be placed because none are left
+    Macrocells(Vec<String>),
+    /// Macrocells whose inputs could not be routed through the ZIA of any function block with
+    /// room for them
+    ZIA(Vec<String>),
+    /// Macrocells whose product terms did not fit in any function block with room for them
+    ProductTerms(Vec<String>),
+    /// Placement succeeded, but the placed design could not be built
+    Build(DesignError),
+}
+impl Display for FitFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |f: &mut fmt::Formatter<'_>, what, names: &[String]| {
+            write!(f, "out of {}:", what)?;
+            for x in names {
+                write!(f, " {}", x)?;
+            }
+            Ok(())
+        };
+        match self {
+            FitFailure::UnknownConstraint(name) => write!(f, "constraint on unknown {}", name),
+            FitFailure::InvalidConstraint(name) => {
+                write!(f, "{} is a macrocell and cannot use the dedicated input", name)
+            }
+            FitFailure::Pads(x) => names(f, "pads", x),
+            FitFailure::Macrocells(x) => names(f, "macrocells", x),
+            FitFailure::ZIA(x) => names(f, "ZIA rows", x),
+            FitFailure::ProductTerms(x) => names(f, "product terms", x),
+            FitFailure::Build(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Everything that went wrong, grouped by resource
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FitError {
+    pub failures: Vec<FitFailure>,
+}
+impl Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for x in &self.failures {
+            writeln!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for FitError {}
+
+/// The resource that ran out when placing something
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Resource {
+    Pads,
+    Macrocells,
+    ZIA,
+    ProductTerms,
+}
+
+/// Where a named signal currently comes from, if it has been placed
+fn source_of(design: &Design, name: &str) -> Option<ZIATableEntry> {
+    if let Some(x) = design.inputs.iter().find(|x| x.name == name) {
+        return match x.loc? {
+            InputLoc::Pad { fb, mc } => Some(ZIATableEntry::InputPin { fb, mc }),
+            InputLoc::DedicatedInput => Some(ZIATableEntry::DedicatedInputPin),
+        };
+    }
+    for x in &design.macrocells {
+        let feedback = x.output.as_ref().and_then(|x| x.feedback.as_deref());
+        if x.name == name {
+            let (fb, mc) = x.loc?;
+            return Some(ZIATableEntry::Macrocell { fb, mc });
+        } else if feedback == Some(name) {
+            let (fb, mc) = x.loc?;
+            return Some(ZIATableEntry::InputPin { fb, mc });
+        }
+    }
+    None
+}
+
+/// Check whether the macrocells currently placed in a function block fit
+fn check_fb(design: &Design, fb: u8) -> Result<(), Resource> {
+    let mcs: Vec<(u8, &MacrocellDef)> = design
+        .macrocells
+        .iter()
+        .filter_map(|x| match x.loc {
+            Some((x_fb, mc)) if x_fb == fb => Some((mc, x)),
+            _ => None,
+        })
+        .collect();
+
+    let mut names = BTreeMap::new();
+    for (_, x) in &mcs {
+        for pt in Design::product_terms(x) {
+            for lit in &pt.0 {
+                let idx = names.len();
+                names.entry(lit.signal.as_str()).or_insert(idx);
+            }
+        }
+    }
+    if names.len() > ZIA_ROWS {
+        return Err(Resource::ZIA);
+    }
+    let signals: BTreeMap<_, _> = names
+        .keys()
+        .filter_map(|x| source_of(design, x))
+        .map(|x| (x, None))
+        .collect();
+    if route_zia(design.part.device, &signals, &ZIARouting::new()).is_err() {
+        return Err(Resource::ZIA);
+    }
+
+    // Only whether terms are identical matters here, so any distinct number per signal will do
+    // in place of the real ZIA row
+    let and_term = |pt: &ProductTerm| {
+        let mut ret = AndTerm::default();
+        for lit in &pt.0 {
+            ret.add(names[lit.signal.as_str()] as u8, lit.negated);
+        }
+        ret
+    };
+    match design.allocate_pterms(fb, &mcs, and_term) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Resource::ProductTerms),
+    }
+}
+
+/// Bookkeeping while placing
+struct Placer {
+    device: XC2Device,
+    pad_bank: BTreeMap<(u8, u8), u8>,
+    pads_used: BTreeSet<(u8, u8)>,
+    mcs_used: BTreeSet<(u8, u8)>,
+    dedicated_used: bool,
+    /// (bank, is output) -> voltage
+    bank_voltage: BTreeMap<(u8, bool), IoVoltage>,
+}
+impl Placer {
+    fn bank_of(&self, fb: u8, mc: u8) -> Option<u8> {
+        match self.pad_bank.get(&(fb, mc)) {
+            Some(&bank) => Some(bank),
+            None if self.device.num_io_banks() == 1 => Some(0),
+            None => None,
+        }
+    }
+
+    /// Whether a pad is free and its bank can take the IO standard
+    fn pad_ok(&self, fb: u8, mc: u8, output: bool, standard: Option<IoStandard>) -> bool {
+        if !self.device.has_io_at(fb, mc) || self.pads_used.contains(&(fb, mc)) {
+            return false;
+        }
+        let Some(standard) = standard else {
+            return true;
+        };
+        match self.bank_of(fb, mc) {
+            Some(bank) => self
+                .bank_voltage
+                .get(&(bank, output))
+                .map_or(true, |&x| x == standard.voltage()),
+            None => false,
+        }
+    }
+
+    fn use_pad(&mut self, fb: u8, mc: u8, output: bool, standard: Option<IoStandard>) {
+        self.pads_used.insert((fb, mc));
+        if let (Some(bank), Some(standard)) = (self.bank_of(fb, mc), standard) {
+            self.bank_voltage.insert((bank, output), standard.voltage());
+        }
+    }
+
+    /// Every location in the device
+    fn all_locs(&self) -> impl Iterator<Item = (u8, u8)> {
+        let num_fbs = self.device.num_fbs() as u8;
+        (0..num_fbs).flat_map(|fb| (0..MCS_PER_FB as u8).map(move |mc| (fb, mc)))
+    }
+}
+
+/// Place one macrocell in the function block that already uses the most of its inputs
+fn place_mc(design: &mut Design, placer: &mut Placer, i: usize) -> Result<(), Resource> {
+    let def = &design.macrocells[i];
+    let output = def.output.is_some();
+    let standard = def.output.as_ref().and_then(|x| x.standard);
+    let candidates: Vec<(u8, u8)> = placer
+        .all_locs()
+        .filter(|&(fb, mc)| {
+            let pad_ok = !output || placer.pad_ok(fb, mc, true, standard);
+            !placer.mcs_used.contains(&(fb, mc)) && pad_ok
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Err(if output {
+            Resource::Pads
+        } else {
+            Resource::Macrocells
+        });
+    }
+
+    let inputs: BTreeSet<&str> = Design::product_terms(def)
+        .flat_map(|x| x.0.iter().map(|x| x.signal.as_str()))
+        .collect();
+    let affinity = |fb: u8| {
+        let mut used = BTreeSet::new();
+        for x in &design.macrocells {
+            if matches!(x.loc, Some((x_fb, _)) if x_fb == fb) {
+                for pt in Design::product_terms(x) {
+                    used.extend(pt.0.iter().map(|x| x.signal.as_str()));
+                }
+            }
+        }
+        inputs.intersection(&used).count()
+    };
+    let mut fbs: Vec<u8> = candidates.iter().map(|x| x.0).collect();
+    fbs.dedup();
+    let mut fbs: Vec<(usize, u8)> = fbs.into_iter().map(|fb| (affinity(fb), fb)).collect();
+    fbs.sort_by_key(|&(affinity, fb)| (core::cmp::Reverse(affinity), fb));
+
+    // If one function block ran out of ZIA rows and another ran out of product terms, report
+    // product terms since that one got further
+    let mut ret = Resource::ZIA;
+    for (_, fb) in fbs {
+        let loc = *candidates.iter().find(|x| x.0 == fb).unwrap();
+        design.macrocells[i].loc = Some(loc);
+        match check_fb(design, fb) {
+            Ok(()) => {
+                placer.mcs_used.insert(loc);
+                if output {
+                    placer.use_pad(loc.0, loc.1, true, standard);
+                }
+                return Ok(());
+            }
+            Err(resource) => ret = ret.max(resource),
+        }
+    }
+    design.macrocells[i].loc = None;
+    Err(ret)
+}
+
+impl Design {
+    /// Give a location to every input and macrocell that does not have one
+    pub fn place(&self, constraints: &PinConstraints) -> Result<Design, FitError> {
+        let mut design = self.clone();
+        let mut failures = Vec::new();
+
+        for (name, &lock) in constraints {
+            if let Some(x) = design.inputs.iter_mut().find(|x| x.name == *name) {
+                x.loc = Some(lock);
+            } else if let Some(x) = design.macrocells.iter_mut().find(|x| x.name == *name) {
+                match lock {
+                    InputLoc::Pad { fb, mc } => x.loc = Some((fb, mc)),
+                    InputLoc::DedicatedInput => {
+                        failures.push(FitFailure::InvalidConstraint(name.clone()))
+                    }
+                }
+            } else {
+                failures.push(FitFailure::UnknownConstraint(name.clone()));
+            }
+        }
+
+        let mut placer = Placer {
+            device: design.part.device,
+            pad_bank: design.pad_bank.clone(),
+            pads_used: BTreeSet::new(),
+            mcs_used: BTreeSet::new(),
+            dedicated_used: false,
+            bank_voltage: BTreeMap::new(),
+        };
+        for x in &design.inputs {
+            match x.loc {
+                Some(InputLoc::Pad { fb, mc }) => placer.use_pad(fb, mc, false, x.standard),
+                Some(InputLoc::DedicatedInput) => placer.dedicated_used = true,
+                None => {}
+            }
+        }
+        for x in &design.macrocells {
+            if let Some((fb, mc)) = x.loc {
+                placer.mcs_used.insert((fb, mc));
+                if let Some(out) = &x.output {
+                    placer.use_pad(fb, mc, true, out.standard);
+                }
+            }
+        }
+
+        let mut failed: BTreeMap<Resource, Vec<String>> = BTreeMap::new();
+
+        // Macrocells with pads
+        for i in 0..design.macrocells.len() {
+            let x = &design.macrocells[i];
+            if x.loc.is_none() && x.output.is_some() {
+                if let Err(resource) = place_mc(&mut design, &mut placer, i) {
+                    failed.entry(resource).or_default().push(design.macrocells[i].name.clone());
+                }
+            }
+        }
+
+        // Inputs, using the dedicated input first where possible
+        let has_dedicated = matches!(placer.device, XC2Device::XC2C32 | XC2Device::XC2C32A);
+        for x in &mut design.inputs {
+            if x.loc.is_some() {
+                continue;
+            }
+            if has_dedicated && !placer.dedicated_used && x.standard.is_none() {
+                placer.dedicated_used = true;
+                x.loc = Some(InputLoc::DedicatedInput);
+                continue;
+            }
+            let pad = placer
+                .all_locs()
+                .find(|&(fb, mc)| placer.pad_ok(fb, mc, false, x.standard));
+            match pad {
+                Some((fb, mc)) => {
+                    placer.use_pad(fb, mc, false, x.standard);
+                    x.loc = Some(InputLoc::Pad { fb, mc });
+                }
+                None => failed.entry(Resource::Pads).or_default().push(x.name.clone()),
+            }
+        }
+
+        // Buried macrocells
+        for i in 0..design.macrocells.len() {
+            if design.macrocells[i].loc.is_none() {
+                if let Err(resource) = place_mc(&mut design, &mut placer, i) {
+                    failed.entry(resource).or_default().push(design.macrocells[i].name.clone());
+                }
+            }
+        }
+
+        failures.extend(failed.into_iter().map(|(resource, names)| match resource {
+            Resource::Pads => FitFailure::Pads(names),
+            Resource::Macrocells => FitFailure::Macrocells(names),
+            Resource::ZIA => FitFailure::ZIA(names),
+            Resource::ProductTerms => FitFailure::ProductTerms(names),
+        }));
+        if failures.is_empty() {
+            Ok(design)
+        } else {
+            Err(FitError { failures })
+        }
+    }
+}
+
+impl Coolrunner2<BitBox> {
+    /// Place and build a design
+    pub fn fit(design: &Design, constraints: &PinConstraints) -> Result<Self, FitError> {
+        design.place(constraints)?.build().map_err(|e| FitError {
+            failures: vec![FitFailure::Build(e)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        design::{InputDef, OutputDef, SumOfProducts},
+        partdb::XC2Part,
+        sim::{PinState, Simulator},
+    };
+
+    #[test]
+    fn fit_comb() {
+        let part = XC2Part::new(XC2Device::XC2C64A, None, None).unwrap();
+        let mut design = Design::new(part);
+        for name in ["a", "b", "c"] {
+            design.inputs.push(InputDef {
+                name: name.into(),
+                loc: None,
+                standard: None,
+                termination: false,
+            });
+        }
+        let mut y = MacrocellDef::new("y", (0, 0), SumOfProducts::parse("a & !b | c").unwrap());
+        y.loc = None;
+        y.output = Some(OutputDef::new(false));
+        design.macrocells.push(y);
+
+        let constraints = PinConstraints::from([("y".into(), InputLoc::Pad { fb: 1, mc: 4 })]);
+        let placed = design.place(&constraints).unwrap();
+        assert_eq!(placed.macrocells[0].loc, Some((1, 4)));
+        let pads: Vec<(u8, u8)> = placed
+            .inputs
+            .iter()
+            .map(|x| match x.loc {
+                Some(InputLoc::Pad { fb, mc }) => (fb, mc),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let mut sim = Coolrunner2::fit(&design, &constraints)
+            .unwrap()
+            .simulator()
+            .unwrap();
+        for i in 0..8 {
+            let (a, b, c) = (i & 1 != 0, i & 2 != 0, i & 4 != 0);
+            for (&(fb, mc), v) in pads.iter().zip([a, b, c]) {
+                sim.set_pin(fb, mc, v);
+            }
+            sim.step().unwrap();
+            assert_eq!(sim.pin_state(1, 4), PinState::Driven(a && !b || c));
+        }
+    }
+
+    #[test]
+    fn out_of_macrocells() {
+        let part = XC2Part::new(XC2Device::XC2C32A, None, None).unwrap();
+        let mut design = Design::new(part);
+        design.inputs.push(InputDef::new("a", InputLoc::DedicatedInput));
+        for i in 0..MCS_PER_FB * 2 + 1 {
+            let name = alloc::format!("m{}", i);
+            let mut x = MacrocellDef::new(&name, (0, 0), SumOfProducts::parse("a").unwrap());
+            x.loc = None;
+            design.macrocells.push(x);
+        }
+        assert_eq!(
+            design.place(&PinConstraints::new()).unwrap_err().failures,
+            vec![FitFailure::Macrocells(vec!["m32".into()])]
+        );
+    }
+
+    #[test]
+    fn fit_all_devices() {
+        for device in [
+            XC2Device::XC2C32,
+            XC2Device::XC2C32A,
+            XC2Device::XC2C64,
+            XC2Device::XC2C64A,
+            XC2Device::XC2C128,
+            XC2Device::XC2C256,
+            XC2Device::XC2C384,
+            XC2Device::XC2C512,
+        ] {
+            let part = XC2Part::new(device, None, None).unwrap();
+            let mut design = Design::new(part);
+            for name in ["a", "b"] {
+                design.inputs.push(InputDef {
+                    name: name.into(),
+                    loc: None,
+                    standard: None,
+                    termination: false,
+                });
+            }
+            let logic = SumOfProducts::parse("a & !b | !a & b").unwrap();
+            let mut y = MacrocellDef::new("y", (0, 0), logic);
+            y.loc = None;
+            y.output = Some(OutputDef::new(false));
+            design.macrocells.push(y);
+
+            let placed = design.place(&PinConstraints::new()).unwrap();
+            let (y_fb, y_mc) = placed.macrocells[0].loc.unwrap();
+            // The XC2C32(A) puts one of the inputs on the dedicated input
+            let set_input = |sim: &mut Simulator, i: usize, val| match placed.inputs[i].loc {
+                Some(InputLoc::Pad { fb, mc }) => sim.set_pin(fb, mc, val),
+                Some(InputLoc::DedicatedInput) => sim.set_dedicated_input(val),
+                None => unreachable!(),
+            };
+
+            let mut sim = placed.build().unwrap().simulator().unwrap();
+            for i in 0..4 {
+                let (a, b) = (i & 1 != 0, i & 2 != 0);
+                set_input(&mut sim, 0, a);
+                set_input(&mut sim, 1, b);
+                sim.step().unwrap();
+                assert_eq!(sim.pin_state(y_fb, y_mc), PinState::Driven(a ^ b));
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Reverse lookup from fuses to the properties that own them
+//!
+//! The index is built by asking every property accessor where its bits are, in the same way that
+//! reading and writing properties does, so it is exactly as complete as the accessors are.
+//! Fuses that no accessor claims are classified using [is_unknown_bit] and the transfer columns.
+
+use core::fmt::{self, Display};
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bittwiddler_core::prelude::{Coordinate, PropertyAccessor, PropertyLeafWithStringConv};
+
+use crate::{
+    bitstream::Coolrunner2,
+    diff::{visit_properties, BoolArrayLen, PropertyVisitor},
+    extra_bits::is_unknown_bit,
+    global_fuses::GlobalFuses,
+    jed::JedecCompat,
+    partdb::{XC2Device, XC2Part},
+    zia::ZIARowAccessor,
+};
+
+/// What a fuse belongs to
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FuseOwner<'a> {
+    Property {
+        /// The hierarchy level containing the property, as in [PropertyChange](crate::diff::PropertyChange)
+        path: &'a str,
+        property: &'a str,
+        /// Which bit of the property this is, counting from 0
+        bit: usize,
+        num_bits: usize,
+        /// Whether the fuse stores the inverse of the bit
+        inverted: bool,
+    },
+    TransferBit,
+    Done1,
+    /// Listed by [is_unknown_bit]
+    Unknown,
+    /// Not claimed by anything, which means that the tables in this crate are incomplete
+    Unassigned,
+}
+impl<'a> Display for FuseOwner<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuseOwner::Property {
+                path,
+                property,
+                bit,
+                num_bits,
+                inverted,
+            } => {
+                if !path.is_empty() {
+                    write!(f, "{} ", path)?;
+                }
+                write!(f, "{}", property)?;
+                if *num_bits > 1 {
+                    write!(f, " bit {}", bit)?;
+                }
+                if *inverted {
+                    write!(f, " (inverted)")?;
+                }
+                Ok(())
+            }
+            FuseOwner::TransferBit => write!(f, "transfer bit"),
+            FuseOwner::Done1 => write!(f, "done1"),
+            FuseOwner::Unknown => write!(f, "unknown bit"),
+            FuseOwner::Unassigned => write!(f, "unassigned"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Slot {
+    Property {
+        prop_idx: usize,
+        bit: usize,
+        inverted: bool,
+    },
+    TransferBit,
+    Done1,
+    Unknown,
+    Unassigned,
+}
+
+struct PropertyInfo {
+    path: String,
+    property: String,
+    num_bits: usize,
+}
+
+/// Index from every fuse of a device to its owner
+pub struct FuseMap {
+    device: XC2Device,
+    properties: Vec<PropertyInfo>,
+    slots: Vec<Slot>,
+    /// Fuses claimed by more than one property, with the claim that lost
+    collisions: Vec<(Coordinate, Slot)>,
+}
+
+struct Builder<'a> {
+    map: &'a mut FuseMap,
+    width: usize,
+}
+impl<'a> Builder<'a> {
+    fn add<P: PropertyAccessor>(&mut self, path: &str, property: &str, acc: P, num_bits: usize) {
+        let prop_idx = self.map.properties.len();
+        self.map.properties.push(PropertyInfo {
+            path: path.to_string(),
+            property: property.to_string(),
+            num_bits,
+        });
+
+        for bit in 0..num_bits {
+            let (c, inverted) = acc.get_bit_pos(bit);
+            let new_slot = Slot::Property {
+                prop_idx,
+                bit,
+                inverted,
+            };
+            let slot = &mut self.map.slots[c.y * self.width + c.x];
+            if let Slot::Property { .. } = *slot {
+                self.map.collisions.push((c, new_slot));
+            } else {
+                *slot = new_slot;
+            }
+        }
+    }
+}
+impl<'a> PropertyVisitor for Builder<'a> {
+    fn prop<P>(&mut self, path: &str, property: &str, acc: P)
+    where
+        P: PropertyAccessor,
+        P::BoolArray: BoolArrayLen,
+        P::Output: PartialEq + PropertyLeafWithStringConv<P::BoolArray, P>,
+    {
+        self.add(path, property, acc, <P::BoolArray as BoolArrayLen>::LEN);
+    }
+
+    fn zia_row(&mut self, path: &str, property: &str, acc: ZIARowAccessor) {
+        self.add(path, property, acc, self.map.device.zia_width());
+    }
+}
+
+impl FuseMap {
+    pub fn new(device: XC2Device) -> Self {
+        let (w, h) = device.fuse_array_dims();
+        let mut slots = vec![Slot::Unassigned; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                if is_unknown_bit(device, Coordinate::new(x, y)) {
+                    slots[y * w + x] = Slot::Unknown;
+                }
+            }
+        }
+        for &x in device.transfer_columns() {
+            for y in 0..h - 2 {
+                slots[y * w + x] = Slot::TransferBit;
+            }
+        }
+        let done1 = device.done1();
+        slots[done1.y * w + done1.x] = Slot::Done1;
+
+        let mut ret = Self {
+            device,
+            properties: Vec::new(),
+            slots,
+            collisions: Vec::new(),
+        };
+
+        let bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        visit_properties(
+            &bitstream,
+            &mut Builder {
+                map: &mut ret,
+                width: w,
+            },
+        );
+
+        ret
+    }
+
+    pub fn device(&self) -> XC2Device {
+        self.device
+    }
+
+    fn owner_of_slot(&self, slot: Slot) -> FuseOwner {
+        match slot {
+            Slot::Property {
+                prop_idx,
+                bit,
+                inverted,
+            } => {
+                let info = &self.properties[prop_idx];
+                FuseOwner::Property {
+                    path: &info.path,
+                    property: &info.property,
+                    bit,
+                    num_bits: info.num_bits,
+                    inverted,
+                }
+            }
+            Slot::TransferBit => FuseOwner::TransferBit,
+            Slot::Done1 => FuseOwner::Done1,
+            Slot::Unknown => FuseOwner::Unknown,
+            Slot::Unassigned => FuseOwner::Unassigned,
+        }
+    }
+
+    /// Look up the owner of a fuse, or `None` if the coordinate is outside the fuse array
+    pub fn owner(&self, c: Coordinate) -> Option<FuseOwner> {
+        let (w, h) = self.device.fuse_array_dims();
+        if c.x >= w || c.y >= h {
+            return None;
+        }
+        Some(self.owner_of_slot(self.slots[c.y * w + c.x]))
+    }
+
+    /// Look up the owner of a fuse by its index in a JED file.
+    /// Also returns the coordinate of the fuse.
+    pub fn owner_of_jed_index(&self, jed_idx: usize) -> Option<(Coordinate, FuseOwner)> {
+        if jed_idx >= self.device.num_jed_fuses() {
+            return None;
+        }
+        let c = self.device.jed_index_to_crbit(jed_idx);
+        Some((c, self.owner(c)?))
+    }
+
+    /// Fuses that are claimed by more than one property. This should always be empty.
+    pub fn collisions(&self) -> impl Iterator<Item = (Coordinate, FuseOwner, FuseOwner)> {
+        let (w, _) = self.device.fuse_array_dims();
+        self.collisions.iter().map(move |&(c, second)| {
+            (
+                c,
+                self.owner_of_slot(self.slots[c.y * w + c.x]),
+                self.owner_of_slot(second),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners() {
+        let map = FuseMap::new(XC2Device::XC2C32A);
+        assert_eq!(map.collisions().count(), 0);
+
+        assert_eq!(map.owner(Coordinate::new(0, 5)), Some(FuseOwner::TransferBit));
+        assert_eq!(map.owner(Coordinate::new(9, 48)), Some(FuseOwner::Done1));
+        assert_eq!(map.owner(Coordinate::new(130, 20)), Some(FuseOwner::Unknown));
+        assert_eq!(map.owner(Coordinate::new(260, 0)), None);
+
+        let owner = map.owner(Coordinate::new(249, 49)).unwrap();
+        assert_eq!(owner.to_string(), "usercode bit 0");
+    }
+}
@@ -6,6 +6,7 @@ use bittwiddler_macros::*;
 use crate::{global_fuses::GlobalFuses, partdb::XC2Device};
 
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct GCKEn {
     #[bittwiddler::skip]
     pub(crate) device: XC2Device,
@@ -16,12 +17,14 @@ crate::bitstream::single_bool_impl!(GCKEn, self, {
 });
 
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct GSREn {
     #[bittwiddler::skip]
     pub(crate) device: XC2Device,
 }
 crate::bitstream::single_bool_impl!(GSREn, self, { (self.device.gsr_enable(), false) });
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct GSRInv {
     #[bittwiddler::skip]
     pub(crate) device: XC2Device,
@@ -29,6 +32,7 @@ pub struct GSRInv {
 crate::bitstream::single_bool_impl!(GSRInv, self, { (self.device.gsr_invert(), false) });
 
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct GTSEn {
     #[bittwiddler::skip]
     pub(crate) device: XC2Device,
@@ -38,6 +42,7 @@ crate::bitstream::single_bool_impl!(GTSEn, self, {
     (self.device.gts_enable()[self.gts_idx as usize], true)
 });
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct GTSInv {
     #[bittwiddler::skip]
     pub(crate) device: XC2Device,
@@ -76,6 +81,21 @@ impl PropertyAccessorWithDefault for GlobalTermAccessor {}
 impl PropertyAccessorWithStringConv for GlobalTermAccessor {}
 
 include!(concat!(env!("OUT_DIR"), "/clk-div.rs"));
+impl ClockDivRatio {
+    /// The number of input clock cycles per output clock cycle
+    pub const fn divisor(self) -> u32 {
+        match self {
+            ClockDivRatio::_2 => 2,
+            ClockDivRatio::_4 => 4,
+            ClockDivRatio::_6 => 6,
+            ClockDivRatio::_8 => 8,
+            ClockDivRatio::_10 => 10,
+            ClockDivRatio::_12 => 12,
+            ClockDivRatio::_14 => 14,
+            ClockDivRatio::_16 => 16,
+        }
+    }
+}
 
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -9,6 +9,10 @@ use bittwiddler_core::prelude::{BitArray as BittwiddlerBitArray, Coordinate, Pro
 use bitvec::prelude::*;
 use jedec::*;
 
+#[cfg(feature = "std")]
+use crate::extra_bits::UnknownBitSet;
+#[cfg(feature = "std")]
+use crate::vectors::{TestVectors, VectorParseError};
 use crate::{
     bitstream::{BitHolder, Coolrunner2},
     fb::{AndTerm, CompInput, FunctionBlock, OrInput, OrTerm, TrueInput},
@@ -773,6 +777,7 @@ pub enum JedReadError {
     IoError(std::io::Error),
     ParseError(JedParserError),
     UnknownPart,
+    VectorError(VectorParseError),
 }
 #[cfg(feature = "std")]
 impl Display for JedReadError {
@@ -781,6 +786,7 @@ impl Display for JedReadError {
             JedReadError::IoError(e) => e.fmt(f),
             JedReadError::ParseError(e) => e.fmt(f),
             JedReadError::UnknownPart => write!(f, "unknown part, wrong fuse count"),
+            JedReadError::VectorError(e) => e.fmt(f),
         }
     }
 }
@@ -791,6 +797,7 @@ impl std::error::Error for JedReadError {
             JedReadError::IoError(e) => Some(e),
             JedReadError::ParseError(e) => Some(e),
             JedReadError::UnknownPart => None,
+            JedReadError::VectorError(_) => None,
         }
     }
 }
@@ -806,12 +813,31 @@ impl From<JedParserError> for JedReadError {
         Self::ParseError(value)
     }
 }
+#[cfg(feature = "std")]
+impl From<VectorParseError> for JedReadError {
+    fn from(value: VectorParseError) -> Self {
+        Self::VectorError(value)
+    }
+}
 
 #[cfg(feature = "std")]
 pub trait JedReader {
     fn read_jed<R: std::io::Read>(r: R) -> Result<Self, JedReadError>
     where
         Self: Sized;
+    /// Also returns the test vectors stored in the file, if any
+    fn read_jed_with_vectors<R: std::io::Read>(
+        mut r: R,
+    ) -> Result<(Self, TestVectors), JedReadError>
+    where
+        Self: Sized,
+    {
+        let mut inp_bytes = std::vec::Vec::new();
+        r.read_to_end(&mut inp_bytes)?;
+        let vectors = TestVectors::parse(&inp_bytes)?;
+        let bitstream = Self::read_jed(&inp_bytes[..])?;
+        Ok((bitstream, vectors))
+    }
 }
 #[cfg(feature = "std")]
 impl JedReader for Coolrunner2<BitBox> {
@@ -858,9 +884,49 @@ impl JedReader for Coolrunner2<BitBox> {
     }
 }
 
+#[cfg(feature = "std")]
+impl Coolrunner2<BitBox> {
+    /// Like [JedReader::read_jed], but also returns the unknown bits that are set.
+    /// See [Coolrunner2::audit_unknown_bits].
+    pub fn read_jed_with_warnings<R: std::io::Read>(
+        r: R,
+    ) -> Result<(Self, std::vec::Vec<UnknownBitSet>), JedReadError> {
+        let bitstream = Self::read_jed(r)?;
+        let warnings = bitstream.audit_unknown_bits();
+        Ok((bitstream, warnings))
+    }
+}
+
 #[cfg(feature = "std")]
 pub trait JedWriter {
     fn write_jed<W: std::io::Write>(&self, w: W) -> std::io::Result<()>;
+    /// Write a JED file that also contains the given test vectors
+    fn write_jed_with_vectors<W: std::io::Write>(
+        &self,
+        mut w: W,
+        vectors: &TestVectors,
+    ) -> std::io::Result<()> {
+        let mut jed = std::vec::Vec::new();
+        self.write_jed(&mut jed)?;
+
+        // Vector fields go at the end, just before ETX
+        let mut fields = std::string::String::new();
+        vectors.write_fields(&mut fields).unwrap();
+        let etx = jed.iter().position(|&x| x == 0x03).unwrap();
+        jed.splice(etx..etx, fields.bytes());
+        let etx = etx + fields.len();
+
+        // Fix up the transmission checksum, which covers STX through ETX
+        if jed.len() >= etx + 5 && jed[etx + 1..etx + 5].iter().all(u8::is_ascii_hexdigit) {
+            let stx = jed.iter().position(|&x| x == 0x02).unwrap();
+            let sum = jed[stx..=etx]
+                .iter()
+                .fold(0u16, |a, &x| a.wrapping_add(x as u16));
+            jed[etx + 1..etx + 5].copy_from_slice(std::format!("{:04X}", sum).as_bytes());
+        }
+
+        w.write_all(&jed)
+    }
 }
 #[cfg(feature = "std")]
 impl<B: BitHolder> JedWriter for Coolrunner2<B> {
@@ -940,6 +1006,7 @@ impl<B: BitHolder> JedWriter for Coolrunner2<B> {
 #[cfg(all(test, feature = "std"))]
 mod tests {
     use crate::bitstream::{DeviceSecurity, UserCode};
+    use crate::vectors::TestVector;
 
     use super::*;
     extern crate std;
@@ -1077,4 +1144,44 @@ mod tests {
         p.push("tests/xc2c512.map");
         check_map(XC2Device::XC2C512, p);
     }
+
+    #[test]
+    fn vectors_round_trip() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.usercode(), 0x12345678);
+        let vectors = TestVectors {
+            num_pins: Some(4),
+            default_x: Some(false),
+            vectors: std::vec![
+                TestVector {
+                    number: 1,
+                    pins: b"01HL".to_vec(),
+                },
+                TestVector {
+                    number: 2,
+                    pins: b"CXZN".to_vec(),
+                },
+            ],
+        };
+
+        let mut jed = Vec::new();
+        bitstream.write_jed_with_vectors(&mut jed, &vectors).unwrap();
+
+        let stx = jed.iter().position(|&x| x == 0x02).unwrap();
+        let etx = jed.iter().position(|&x| x == 0x03).unwrap();
+        let sum = jed[stx..=etx]
+            .iter()
+            .fold(0u16, |a, &x| a.wrapping_add(x as u16));
+        assert_eq!(&jed[etx + 1..etx + 5], std::format!("{:04X}", sum).as_bytes());
+
+        assert_eq!(TestVectors::parse(&jed).unwrap(), vectors);
+        let (read_back, read_vectors) =
+            Coolrunner2::<BitBox>::read_jed_with_vectors(&jed[..]).unwrap();
+        assert_eq!(read_vectors, vectors);
+        assert_eq!(read_back.get_prop(&read_back.usercode()), 0x12345678);
+        let mut jed2 = Vec::new();
+        read_back.write_jed_with_vectors(&mut jed2, &read_vectors).unwrap();
+        assert_eq!(jed, jed2);
+    }
 }
@@ -20,18 +20,62 @@ pub const NUM_BUFG_GTS: usize = 4;
 /// This is an unchangeable property of the architecture of the CPLD.
 pub const NUM_BUFG_GSR: usize = 1;
 
+#[cfg(feature = "alloc")]
+mod bdd;
 pub mod bitstream;
+#[cfg(feature = "alloc")]
+pub mod certainty;
+#[cfg(feature = "alloc")]
+pub mod clocks;
 #[cfg(feature = "std")]
 pub mod crbit;
+#[cfg(feature = "alloc")]
+pub mod design;
+#[cfg(feature = "alloc")]
+pub mod diff;
+#[cfg(feature = "alloc")]
+pub mod drc;
+#[cfg(feature = "alloc")]
+pub mod equations;
+#[cfg(feature = "alloc")]
+pub mod equivalence;
 pub mod extra_bits;
 pub mod fb;
+#[cfg(feature = "alloc")]
+pub mod fitter;
+#[cfg(feature = "alloc")]
+pub mod fuse_map;
 pub mod global_bits_code;
 pub mod global_fuses;
 pub mod io;
 pub mod jed;
+#[cfg(feature = "alloc")]
+pub mod loops;
 pub mod mc;
+#[cfg(feature = "alloc")]
+pub mod netlist;
 pub mod partdb;
+#[cfg(feature = "alloc")]
+pub mod power;
+#[cfg(feature = "alloc")]
+pub mod powerup;
+#[cfg(feature = "alloc")]
+pub mod pterms;
+#[cfg(feature = "alloc")]
+pub mod sim;
+#[cfg(feature = "alloc")]
+pub mod tristate;
+#[cfg(feature = "alloc")]
+pub mod utilization;
+#[cfg(feature = "alloc")]
+pub mod vcd;
+#[cfg(feature = "alloc")]
+pub mod vectors;
+#[cfg(feature = "alloc")]
+pub mod yosys;
 pub mod zia;
+#[cfg(feature = "alloc")]
+pub mod zia_router;
 
 pub mod spreadsheet_magic {
     include!(concat!(env!("OUT_DIR"), "/tiles-out.rs"));
@@ -0,0 +1,231 @@
+//! Combinational loop detection
+//!
+//! Registers break the combinational graph, except for their asynchronous set and reset and for
+//! transparent latches, which pass both the data and the gate through. Everything else, including
+//! XOR gate feedback into the ZIA and pads read back while they are being driven, is
+//! combinational.
+//!
+//! Loops that share nodes are reported together as a single strongly connected component.
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    mc::FlipFlopMode,
+    netlist::{Netlist, NodeId, NodeRef},
+};
+
+/// A set of nodes that depend on each other without going through a flip-flop
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CombinationalLoop {
+    /// Every node in the loop, sorted
+    pub nodes: Vec<NodeId>,
+    /// The macrocells whose XOR gate or latch is in the loop, as (fb, mc), sorted
+    pub macrocells: Vec<(u8, u8)>,
+    /// The pads that are both driven and read back as part of the loop, as (fb, mc), sorted
+    pub pads: Vec<(u8, u8)>,
+}
+impl Display for CombinationalLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "combinational loop through")?;
+        for (fb, mc) in &self.macrocells {
+            write!(f, " FB{fb} MC{mc}")?;
+        }
+        for (fb, mc) in &self.pads {
+            write!(f, " FB{fb} IO{mc}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Inputs that the node depends on combinationally
+fn comb_fanin(netlist: &Netlist, id: NodeId) -> Vec<NodeId> {
+    match netlist.node(id) {
+        Some(NodeRef::Register(x)) => {
+            // Set and reset act immediately whatever the mode
+            let mut ret: Vec<NodeId> = x.set.into_iter().chain(x.reset).collect();
+            if x.mode == FlipFlopMode::LATCH {
+                ret.extend([x.d, x.clk]);
+            }
+            ret
+        }
+        _ => netlist.fanin(id),
+    }
+}
+
+impl Netlist {
+    /// Find all combinational loops, using Tarjan's strongly connected components algorithm
+    pub fn combinational_loops(&self) -> Vec<CombinationalLoop> {
+        let ids: Vec<NodeId> = self.node_ids().collect();
+        let idx_of: BTreeMap<NodeId, usize> =
+            ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let succs: Vec<Vec<usize>> = ids
+            .iter()
+            .map(|&id| {
+                comb_fanin(self, id)
+                    .into_iter()
+                    .filter_map(|x| idx_of.get(&x).copied())
+                    .collect()
+            })
+            .collect();
+
+        const UNVISITED: usize = usize::MAX;
+        let mut index = vec![UNVISITED; ids.len()];
+        let mut lowlink = vec![0; ids.len()];
+        let mut on_stack = vec![false; ids.len()];
+        let mut stack = Vec::new();
+        let mut next_index = 0;
+        let mut ret = Vec::new();
+
+        for root in 0..ids.len() {
+            if index[root] != UNVISITED {
+                continue;
+            }
+
+            // (node, position in its successor list)
+            let mut call_stack = vec![(root, 0)];
+            index[root] = next_index;
+            lowlink[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&mut (v, ref mut pos)) = call_stack.last_mut() {
+                if let Some(&w) = succs[v].get(*pos) {
+                    *pos += 1;
+                    if index[w] == UNVISITED {
+                        index[w] = next_index;
+                        lowlink[w] = next_index;
+                        next_index += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        call_stack.push((w, 0));
+                    } else if on_stack[w] {
+                        lowlink[v] = lowlink[v].min(index[w]);
+                    }
+                    continue;
+                }
+
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[v]);
+                }
+                if lowlink[v] != index[v] {
+                    continue;
+                }
+
+                let mut component = Vec::new();
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || succs[v].contains(&v) {
+                    ret.push(Self::make_loop(component.iter().map(|&i| ids[i])));
+                }
+            }
+        }
+
+        ret
+    }
+
+    fn make_loop(nodes: impl Iterator<Item = NodeId>) -> CombinationalLoop {
+        let mut nodes: Vec<NodeId> = nodes.collect();
+        nodes.sort();
+        let mut macrocells = Vec::new();
+        let mut pads = Vec::new();
+        for &id in &nodes {
+            match id {
+                NodeId::Xor { fb, mc } | NodeId::Register { fb, mc } => macrocells.push((fb, mc)),
+                NodeId::IoPad { fb, mc } => pads.push((fb, mc)),
+                _ => {}
+            }
+        }
+        macrocells.sort();
+        macrocells.dedup();
+        CombinationalLoop {
+            nodes,
+            macrocells,
+            pads,
+        }
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Find all combinational loops. See [Netlist::combinational_loops].
+    pub fn combinational_loops(&self) -> Vec<CombinationalLoop> {
+        self.netlist().combinational_loops()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mc::{MacrocellFeedbackSrc, RegResetSrc},
+        partdb::{XC2Device, XC2Part},
+        zia::ZIARow,
+    };
+
+    #[test]
+    fn xor_feedback_loop() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        assert_eq!(bitstream.combinational_loops(), []);
+
+        // Row 0 choice 3 is FB0 MC1, feed it back into its own OR term
+        bitstream.set_prop(&bitstream.fb(0).zia_row(0), ZIARow::MuxChoice(3));
+        bitstream.set_prop(&bitstream.fb(0).and_term(10).inp_n(0), true);
+        bitstream.set_prop(&bitstream.fb(0).or_term(1).inp(10), true);
+        bitstream.set_prop(
+            &bitstream.fb(0).mc(1).fb_src(),
+            MacrocellFeedbackSrc::FlipFlop,
+        );
+        // Through the register, so not a loop
+        assert_eq!(bitstream.combinational_loops(), []);
+
+        bitstream.set_prop(
+            &bitstream.fb(0).mc(1).fb_src(),
+            MacrocellFeedbackSrc::XorGate,
+        );
+        let loops = bitstream.combinational_loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].macrocells, [(0, 1)]);
+        assert_eq!(loops[0].to_string(), "combinational loop through FB0 MC1");
+
+        // A transparent latch passes its input through as well
+        bitstream.set_prop(
+            &bitstream.fb(0).mc(1).fb_src(),
+            MacrocellFeedbackSrc::FlipFlop,
+        );
+        bitstream.set_prop(&bitstream.fb(0).mc(1).ff_mode(), FlipFlopMode::LATCH);
+        assert_eq!(bitstream.combinational_loops().len(), 1);
+    }
+
+    #[test]
+    fn self_reset() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+
+        // Row 0 choice 3 is FB0 MC1, which resets its own register through PTA
+        bitstream.set_prop(&bitstream.fb(0).zia_row(0), ZIARow::MuxChoice(3));
+        bitstream.set_prop(
+            &bitstream.fb(0).mc(1).fb_src(),
+            MacrocellFeedbackSrc::FlipFlop,
+        );
+        bitstream.set_prop(&bitstream.fb(0).and_term(crate::fb::pta(1)).inp(0), true);
+        assert_eq!(bitstream.combinational_loops(), []);
+
+        bitstream.set_prop(&bitstream.fb(0).mc(1).r_src(), RegResetSrc::PTA);
+        let loops = bitstream.combinational_loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].macrocells, [(0, 1)]);
+        assert!(loops[0].nodes.contains(&NodeId::Register { fb: 0, mc: 1 }));
+    }
+}
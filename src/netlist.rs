@@ -0,0 +1,853 @@
+//! Decoded logic graph of a bitstream
+//!
+//! This turns the raw property accessors into a graph of typed nodes, one for each piece of
+//! logic in the device. Every node is identified by a [NodeId] and keeps a copy of the accessor
+//! that it was decoded from.
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    fb::{self, AndTerm, FunctionBlock, OrTerm},
+    global_bits_code::{ClockDivRatio, GCKEn, GSREn, GTSEn},
+    io::{ExtraDedicatedInput, IoFeedbackSource, IoPad, OutputMode, PinOutputSrc},
+    mc::{
+        FlipFlopMode, Macrocell, MacrocellFeedbackSrc, RegClkSrc, RegResetSrc, RegSetSrc, XorMode,
+    },
+    partdb::XC2Device,
+    zia::{ZIARow, ZIARowAccessor, ZIATableEntry},
+    ANDTERMS_PER_FB, MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS, ZIA_ROWS,
+};
+
+/// Identifies one node in a [Netlist]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum NodeId {
+    /// Global clock net
+    GlobalClock(u8),
+    /// Global set/reset net
+    GlobalSetReset,
+    /// Global tristate net
+    GlobalTristate(u8),
+    /// The input-only pin on the XC2C32(A)
+    DedicatedInput,
+    /// An IO pad
+    IoPad { fb: u8, mc: u8 },
+    /// The mux selecting what an IO pad feeds into the ZIA
+    IoFeedback { fb: u8, mc: u8 },
+    /// The mux selecting what a macrocell feeds into the ZIA
+    McFeedback { fb: u8, mc: u8 },
+    /// One of the 40 rows of the ZIA going into a function block
+    ZIARow { fb: u8, row: u8 },
+    /// An AND term in the PLA
+    AndTerm { fb: u8, pterm: u8 },
+    /// An OR term in the PLA
+    OrTerm { fb: u8, mc: u8 },
+    /// The XOR gate in a macrocell
+    Xor { fb: u8, mc: u8 },
+    /// The register in a macrocell
+    Register { fb: u8, mc: u8 },
+}
+
+/// Global clock net. Its value is the clock pin when enabled and constant zero otherwise.
+#[derive(Clone, Debug)]
+pub struct GlobalClockNode {
+    pub src: GCKEn,
+    pub enabled: bool,
+    /// Settings of the clock divider, if this net is driven through it
+    pub divider: Option<ClockDividerSettings>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ClockDividerSettings {
+    pub ratio: ClockDivRatio,
+    pub delay: bool,
+}
+
+/// Global set/reset net. Its value is true when registers using it should be set/reset.
+///
+/// When enabled, this is the GSR pin XOR `invert`. When not enabled, it is never asserted.
+#[derive(Clone, Debug)]
+pub struct GlobalSetResetNode {
+    pub src: GSREn,
+    pub enabled: bool,
+    pub invert: bool,
+}
+
+/// Global tristate net. Its value is true when outputs using it should be driving.
+///
+/// When enabled, outputs are tristated when the GTS pin XOR `invert` is high.
+/// When not enabled, outputs using it are always driving.
+#[derive(Clone, Debug)]
+pub struct GlobalTristateNode {
+    pub src: GTSEn,
+    pub enabled: bool,
+    pub invert: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct DedicatedInputNode {
+    pub src: ExtraDedicatedInput,
+}
+
+/// What actually controls the output driver of an IO pad
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OutputEnable {
+    /// The output driver is off
+    Disabled,
+    /// The output driver is always on
+    Always,
+    /// The output driver only ever pulls low
+    OpenDrain,
+    /// The output is enabled whenever the given node is true
+    Node(NodeId),
+    /// The pad is driven low as a "virtual ground"
+    Ground,
+    /// The output mode bits do not correspond to any known mode
+    Invalid,
+}
+
+#[derive(Clone, Debug)]
+pub struct IoPadNode {
+    pub src: IoPad,
+    pub output_src: PinOutputSrc,
+    /// The node that provides the data to be output
+    pub output_data: NodeId,
+    pub output_mode: OutputMode,
+    pub oe: OutputEnable,
+}
+
+#[derive(Clone, Debug)]
+pub struct IoFeedbackNode {
+    pub src: IoPad,
+    pub setting: IoFeedbackSource,
+    /// The node that is being fed back, or `None` if disabled
+    pub input: Option<NodeId>,
+}
+
+#[derive(Clone, Debug)]
+pub struct McFeedbackNode {
+    pub src: Macrocell,
+    pub setting: MacrocellFeedbackSrc,
+    /// The node that is being fed back, or `None` if disabled
+    pub input: Option<NodeId>,
+}
+
+/// What a ZIA row is connected to
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ZIAInput {
+    Const(bool),
+    Node(NodeId),
+    /// The row bits do not correspond to any known mux setting
+    Invalid,
+}
+
+#[derive(Clone, Debug)]
+pub struct ZIARowNode {
+    pub src: ZIARowAccessor,
+    pub setting: ZIARow,
+    pub input: ZIAInput,
+}
+
+/// One input into an AND term
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct Literal {
+    /// The ZIA row node
+    pub input: NodeId,
+    pub negated: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AndTermNode {
+    pub src: AndTerm,
+    /// All of the connected inputs. An empty list means that this term is constant one.
+    pub literals: Vec<Literal>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OrTermNode {
+    pub src: OrTerm,
+    /// All of the connected AND terms. An empty list means that this term is constant zero.
+    pub inputs: Vec<NodeId>,
+}
+
+#[derive(Clone, Debug)]
+pub struct XorNode {
+    pub src: Macrocell,
+    pub mode: XorMode,
+    /// The OR term
+    pub or: NodeId,
+    /// Product term C, only used for some values of `mode`
+    pub ptc: NodeId,
+}
+
+#[derive(Clone, Debug)]
+pub struct RegisterNode {
+    pub src: Macrocell,
+    pub mode: FlipFlopMode,
+    /// Data input, either the XOR gate or the IO pad
+    pub d: NodeId,
+    pub clk_src: RegClkSrc,
+    pub clk: NodeId,
+    pub clk_inv: bool,
+    pub is_ddr: bool,
+    /// Clock enable, only present in [FlipFlopMode::DFFCE]
+    pub ce: Option<NodeId>,
+    pub s_src: RegSetSrc,
+    pub set: Option<NodeId>,
+    pub r_src: RegResetSrc,
+    pub reset: Option<NodeId>,
+    pub init_state: bool,
+}
+
+/// All of the nodes belonging to one function block
+#[derive(Clone, Debug)]
+pub struct FunctionBlockNodes {
+    pub src: FunctionBlock,
+    pub zia_rows: Vec<ZIARowNode>,
+    pub and_terms: Vec<AndTermNode>,
+    pub or_terms: Vec<OrTermNode>,
+    pub xors: Vec<XorNode>,
+    pub registers: Vec<RegisterNode>,
+    pub mc_feedback: Vec<McFeedbackNode>,
+    /// Indexed by macrocell, `None` where there is no IO pad
+    pub io_pads: Vec<Option<IoPadNode>>,
+    /// Indexed by macrocell, `None` where there is no IO pad
+    pub io_feedback: Vec<Option<IoFeedbackNode>>,
+}
+
+/// Borrowed reference to any node in a [Netlist]
+#[derive(Copy, Clone, Debug)]
+pub enum NodeRef<'a> {
+    GlobalClock(&'a GlobalClockNode),
+    GlobalSetReset(&'a GlobalSetResetNode),
+    GlobalTristate(&'a GlobalTristateNode),
+    DedicatedInput(&'a DedicatedInputNode),
+    IoPad(&'a IoPadNode),
+    IoFeedback(&'a IoFeedbackNode),
+    McFeedback(&'a McFeedbackNode),
+    ZIARow(&'a ZIARowNode),
+    AndTerm(&'a AndTermNode),
+    OrTerm(&'a OrTermNode),
+    Xor(&'a XorNode),
+    Register(&'a RegisterNode),
+}
+
+/// The decoded logic graph of an entire device
+#[derive(Clone, Debug)]
+pub struct Netlist {
+    pub device: XC2Device,
+    pub gck: Vec<GlobalClockNode>,
+    pub gsr: GlobalSetResetNode,
+    pub gts: Vec<GlobalTristateNode>,
+    /// Only present on the XC2C32(A)
+    pub dedicated_input: Option<DedicatedInputNode>,
+    pub fbs: Vec<FunctionBlockNodes>,
+}
+
+/// A ZIA row that selects a macrocell or pad whose feedback mux is disabled.
+/// The row reads as constant zero instead of the intended signal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DanglingZIAReference {
+    pub fb: u8,
+    pub row: u8,
+    /// The [NodeId::McFeedback] or [NodeId::IoFeedback] node that is selected
+    pub source: NodeId,
+    /// The AND terms that use the row
+    pub consumers: Vec<NodeId>,
+}
+impl Display for DanglingZIAReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FB{} ZIA row {} selects ", self.fb, self.row)?;
+        match self.source {
+            NodeId::McFeedback { fb, mc } => {
+                write!(f, "FB{fb} MC{mc}, but that macrocell's feedback is disabled")?
+            }
+            NodeId::IoFeedback { fb, mc } => {
+                write!(f, "FB{fb} IO{mc}, but that pad's feedback is disabled")?
+            }
+            x => write!(f, "{:?}", x)?,
+        }
+        if self.consumers.is_empty() {
+            write!(f, " (unused)")
+        } else {
+            write!(f, " (used by")?;
+            for x in &self.consumers {
+                if let NodeId::AndTerm { fb, pterm } = x {
+                    write!(f, " FB{fb} PT{pterm}")?;
+                }
+            }
+            write!(f, ")")
+        }
+    }
+}
+
+fn zia_entry_node(ent: ZIATableEntry) -> NodeId {
+    match ent {
+        ZIATableEntry::Macrocell { fb, mc } => NodeId::McFeedback { fb, mc },
+        ZIATableEntry::InputPin { fb, mc } => NodeId::IoFeedback { fb, mc },
+        ZIATableEntry::DedicatedInputPin => NodeId::DedicatedInput,
+    }
+}
+
+impl Netlist {
+    #[allow(private_bounds)]
+    pub fn new<B: BitHolder>(bitstream: &Coolrunner2<B>) -> Self {
+        let device = bitstream.part.device;
+
+        let clock_divider = if device.has_large_macrocells()
+            && bitstream.get_prop(&bitstream.clock_divider().enabled())
+        {
+            Some(ClockDividerSettings {
+                ratio: bitstream.get_prop(&bitstream.clock_divider().ratio()),
+                delay: bitstream.get_prop(&bitstream.clock_divider().delay()),
+            })
+        } else {
+            None
+        };
+        let gck = (0..NUM_BUFG_CLK as u8)
+            .map(|gck_idx| GlobalClockNode {
+                src: bitstream.gck_enabled(gck_idx),
+                enabled: bitstream.get_prop(&bitstream.gck_enabled(gck_idx)),
+                divider: if gck_idx == 2 { clock_divider } else { None },
+            })
+            .collect();
+        let gsr = GlobalSetResetNode {
+            src: bitstream.gsr_enabled(),
+            enabled: bitstream.get_prop(&bitstream.gsr_enabled()),
+            invert: bitstream.get_prop(&bitstream.gsr_invert()),
+        };
+        let gts = (0..NUM_BUFG_GTS as u8)
+            .map(|gts_idx| GlobalTristateNode {
+                src: bitstream.gts_enabled(gts_idx),
+                enabled: bitstream.get_prop(&bitstream.gts_enabled(gts_idx)),
+                invert: bitstream.get_prop(&bitstream.gts_invert(gts_idx)),
+            })
+            .collect();
+        let dedicated_input = if device == XC2Device::XC2C32 || device == XC2Device::XC2C32A {
+            Some(DedicatedInputNode {
+                src: bitstream.extra_dedicated_input(),
+            })
+        } else {
+            None
+        };
+
+        let fbs = (0..device.num_fbs() as u8)
+            .map(|fb| Self::decode_fb(bitstream, fb))
+            .collect();
+
+        Self {
+            device,
+            gck,
+            gsr,
+            gts,
+            dedicated_input,
+            fbs,
+        }
+    }
+
+    fn decode_fb<B: BitHolder>(bitstream: &Coolrunner2<B>, fb: u8) -> FunctionBlockNodes {
+        let device = bitstream.part.device;
+        let fb_acc = bitstream.fb(fb);
+
+        let zia_rows = (0..ZIA_ROWS as u8)
+            .map(|row| {
+                let src = fb_acc.zia_row(row);
+                let setting = bitstream.get_prop(&src);
+                let input = match setting {
+                    ZIARow::GND => ZIAInput::Const(false),
+                    ZIARow::VCC => ZIAInput::Const(true),
+                    ZIARow::MuxChoice(choice) => ZIAInput::Node(zia_entry_node(
+                        device.zia_table_get_row(row)[choice as usize],
+                    )),
+                    ZIARow::Invalid(_) => ZIAInput::Invalid,
+                };
+                ZIARowNode {
+                    src,
+                    setting,
+                    input,
+                }
+            })
+            .collect();
+
+        let and_terms = (0..ANDTERMS_PER_FB as u8)
+            .map(|pterm| {
+                let src = fb_acc.and_term(pterm);
+                let mut literals = Vec::new();
+                for row in 0..ZIA_ROWS as u8 {
+                    if bitstream.get_prop(&src.inp(row)) {
+                        literals.push(Literal {
+                            input: NodeId::ZIARow { fb, row },
+                            negated: false,
+                        });
+                    }
+                    if bitstream.get_prop(&src.inp_n(row)) {
+                        literals.push(Literal {
+                            input: NodeId::ZIARow { fb, row },
+                            negated: true,
+                        });
+                    }
+                }
+                AndTermNode { src, literals }
+            })
+            .collect();
+
+        let or_terms = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                let src = fb_acc.or_term(mc);
+                let inputs = (0..ANDTERMS_PER_FB as u8)
+                    .filter(|&pterm| bitstream.get_prop(&src.inp(pterm)))
+                    .map(|pterm| NodeId::AndTerm { fb, pterm })
+                    .collect();
+                OrTermNode { src, inputs }
+            })
+            .collect();
+
+        let xors = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                let src = fb_acc.mc(mc);
+                XorNode {
+                    src,
+                    mode: bitstream.get_prop(&src.xor_mode()),
+                    or: NodeId::OrTerm { fb, mc },
+                    ptc: NodeId::AndTerm {
+                        fb,
+                        pterm: fb::ptc(mc),
+                    },
+                }
+            })
+            .collect();
+
+        let registers = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                let src = fb_acc.mc(mc);
+                let mode = bitstream.get_prop(&src.ff_mode());
+
+                let d = if device.has_io_at(fb, mc) && bitstream.get_prop(&src.use_iob()) {
+                    NodeId::IoPad { fb, mc }
+                } else {
+                    NodeId::Xor { fb, mc }
+                };
+
+                let clk_src = bitstream.get_prop(&src.clk_src());
+                let clk = match clk_src {
+                    RegClkSrc::GCK0 => NodeId::GlobalClock(0),
+                    RegClkSrc::GCK1 => NodeId::GlobalClock(1),
+                    RegClkSrc::GCK2 => NodeId::GlobalClock(2),
+                    RegClkSrc::PTC => NodeId::AndTerm {
+                        fb,
+                        pterm: fb::ptc(mc),
+                    },
+                    RegClkSrc::CTC => NodeId::AndTerm { fb, pterm: fb::CTC },
+                };
+
+                let ce = if mode == FlipFlopMode::DFFCE {
+                    Some(NodeId::AndTerm {
+                        fb,
+                        pterm: fb::ptc(mc),
+                    })
+                } else {
+                    None
+                };
+
+                let s_src = bitstream.get_prop(&src.s_src());
+                let set = match s_src {
+                    RegSetSrc::Disabled => None,
+                    RegSetSrc::PTA => Some(NodeId::AndTerm {
+                        fb,
+                        pterm: fb::pta(mc),
+                    }),
+                    RegSetSrc::GSR => Some(NodeId::GlobalSetReset),
+                    RegSetSrc::CTS => Some(NodeId::AndTerm { fb, pterm: fb::CTS }),
+                };
+                let r_src = bitstream.get_prop(&src.r_src());
+                let reset = match r_src {
+                    RegResetSrc::Disabled => None,
+                    RegResetSrc::PTA => Some(NodeId::AndTerm {
+                        fb,
+                        pterm: fb::pta(mc),
+                    }),
+                    RegResetSrc::GSR => Some(NodeId::GlobalSetReset),
+                    RegResetSrc::CTR => Some(NodeId::AndTerm { fb, pterm: fb::CTR }),
+                };
+
+                RegisterNode {
+                    src,
+                    mode,
+                    d,
+                    clk_src,
+                    clk,
+                    clk_inv: bitstream.get_prop(&src.clk_inv()),
+                    is_ddr: bitstream.get_prop(&src.is_ddr()),
+                    ce,
+                    s_src,
+                    set,
+                    r_src,
+                    reset,
+                    init_state: bitstream.get_prop(&src.init_state()),
+                }
+            })
+            .collect();
+
+        let mc_feedback = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                let src = fb_acc.mc(mc);
+                let setting = bitstream.get_prop(&src.fb_src());
+                let input = match setting {
+                    MacrocellFeedbackSrc::Disabled => None,
+                    MacrocellFeedbackSrc::XorGate => Some(NodeId::Xor { fb, mc }),
+                    MacrocellFeedbackSrc::FlipFlop => Some(NodeId::Register { fb, mc }),
+                };
+                McFeedbackNode {
+                    src,
+                    setting,
+                    input,
+                }
+            })
+            .collect();
+
+        let io_pads = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                if !device.has_io_at(fb, mc) {
+                    return None;
+                }
+                let src = fb_acc.io(mc);
+                let output_src = bitstream.get_prop(&src.output_src());
+                let output_data = match output_src {
+                    PinOutputSrc::FlipFlop => NodeId::Register { fb, mc },
+                    PinOutputSrc::XorGate => NodeId::Xor { fb, mc },
+                };
+                let output_mode = bitstream.get_prop(&src.output_pad_mode());
+                let oe = match output_mode {
+                    OutputMode::Disabled => OutputEnable::Disabled,
+                    OutputMode::PushPull => OutputEnable::Always,
+                    OutputMode::OpenDrain => OutputEnable::OpenDrain,
+                    OutputMode::TriStateGTS0 => OutputEnable::Node(NodeId::GlobalTristate(0)),
+                    OutputMode::TriStateGTS1 => OutputEnable::Node(NodeId::GlobalTristate(1)),
+                    OutputMode::TriStateGTS2 => OutputEnable::Node(NodeId::GlobalTristate(2)),
+                    OutputMode::TriStateGTS3 => OutputEnable::Node(NodeId::GlobalTristate(3)),
+                    OutputMode::TriStatePTB => OutputEnable::Node(NodeId::AndTerm {
+                        fb,
+                        pterm: fb::ptb(mc),
+                    }),
+                    OutputMode::TriStateCTE => {
+                        OutputEnable::Node(NodeId::AndTerm { fb, pterm: fb::CTE })
+                    }
+                    OutputMode::CGND => OutputEnable::Ground,
+                    OutputMode::Invalid { .. } => OutputEnable::Invalid,
+                };
+                Some(IoPadNode {
+                    src,
+                    output_src,
+                    output_data,
+                    output_mode,
+                    oe,
+                })
+            })
+            .collect();
+
+        let io_feedback = (0..MCS_PER_FB as u8)
+            .map(|mc| {
+                if !device.has_io_at(fb, mc) {
+                    return None;
+                }
+                let src = fb_acc.io(mc);
+                let setting = bitstream.get_prop(&src.fb_src());
+                let input = match setting {
+                    IoFeedbackSource::Disabled => None,
+                    IoFeedbackSource::IO => Some(NodeId::IoPad { fb, mc }),
+                    IoFeedbackSource::FlipFlop => Some(NodeId::Register { fb, mc }),
+                };
+                Some(IoFeedbackNode {
+                    src,
+                    setting,
+                    input,
+                })
+            })
+            .collect();
+
+        FunctionBlockNodes {
+            src: fb_acc,
+            zia_rows,
+            and_terms,
+            or_terms,
+            xors,
+            registers,
+            mc_feedback,
+            io_pads,
+            io_feedback,
+        }
+    }
+
+    /// Look up a node. Returns `None` if the node does not exist in this device.
+    pub fn node(&self, id: NodeId) -> Option<NodeRef<'_>> {
+        Some(match id {
+            NodeId::GlobalClock(i) => NodeRef::GlobalClock(self.gck.get(i as usize)?),
+            NodeId::GlobalSetReset => NodeRef::GlobalSetReset(&self.gsr),
+            NodeId::GlobalTristate(i) => NodeRef::GlobalTristate(self.gts.get(i as usize)?),
+            NodeId::DedicatedInput => NodeRef::DedicatedInput(self.dedicated_input.as_ref()?),
+            NodeId::IoPad { fb, mc } => NodeRef::IoPad(
+                self.fbs
+                    .get(fb as usize)?
+                    .io_pads
+                    .get(mc as usize)?
+                    .as_ref()?,
+            ),
+            NodeId::IoFeedback { fb, mc } => NodeRef::IoFeedback(
+                self.fbs
+                    .get(fb as usize)?
+                    .io_feedback
+                    .get(mc as usize)?
+                    .as_ref()?,
+            ),
+            NodeId::McFeedback { fb, mc } => {
+                NodeRef::McFeedback(self.fbs.get(fb as usize)?.mc_feedback.get(mc as usize)?)
+            }
+            NodeId::ZIARow { fb, row } => {
+                NodeRef::ZIARow(self.fbs.get(fb as usize)?.zia_rows.get(row as usize)?)
+            }
+            NodeId::AndTerm { fb, pterm } => {
+                NodeRef::AndTerm(self.fbs.get(fb as usize)?.and_terms.get(pterm as usize)?)
+            }
+            NodeId::OrTerm { fb, mc } => {
+                NodeRef::OrTerm(self.fbs.get(fb as usize)?.or_terms.get(mc as usize)?)
+            }
+            NodeId::Xor { fb, mc } => NodeRef::Xor(self.fbs.get(fb as usize)?.xors.get(mc as usize)?),
+            NodeId::Register { fb, mc } => {
+                NodeRef::Register(self.fbs.get(fb as usize)?.registers.get(mc as usize)?)
+            }
+        })
+    }
+
+    /// Iterate over the ids of every node in the netlist
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let globals = (0..self.gck.len() as u8)
+            .map(NodeId::GlobalClock)
+            .chain([NodeId::GlobalSetReset])
+            .chain((0..self.gts.len() as u8).map(NodeId::GlobalTristate))
+            .chain(self.dedicated_input.iter().map(|_| NodeId::DedicatedInput));
+
+        let per_fb = self.fbs.iter().enumerate().flat_map(|(fb, fb_nodes)| {
+            let fb = fb as u8;
+            let pads = fb_nodes
+                .io_pads
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| x.is_some())
+                .flat_map(move |(mc, _)| {
+                    let mc = mc as u8;
+                    [NodeId::IoPad { fb, mc }, NodeId::IoFeedback { fb, mc }]
+                });
+            let rows = (0..ZIA_ROWS as u8).map(move |row| NodeId::ZIARow { fb, row });
+            let pterms = (0..ANDTERMS_PER_FB as u8).map(move |pterm| NodeId::AndTerm { fb, pterm });
+            let mcs = (0..MCS_PER_FB as u8).flat_map(move |mc| {
+                [
+                    NodeId::OrTerm { fb, mc },
+                    NodeId::Xor { fb, mc },
+                    NodeId::Register { fb, mc },
+                    NodeId::McFeedback { fb, mc },
+                ]
+            });
+            pads.chain(rows).chain(pterms).chain(mcs)
+        });
+
+        globals.chain(per_fb)
+    }
+
+    /// The nodes that the given node takes as inputs
+    pub fn fanin(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ret = Vec::new();
+        match self.node(id) {
+            None
+            | Some(NodeRef::GlobalClock(_))
+            | Some(NodeRef::GlobalSetReset(_))
+            | Some(NodeRef::GlobalTristate(_))
+            | Some(NodeRef::DedicatedInput(_)) => {}
+            Some(NodeRef::IoPad(x)) => {
+                if !matches!(
+                    x.oe,
+                    OutputEnable::Disabled | OutputEnable::Ground | OutputEnable::Invalid
+                ) {
+                    ret.push(x.output_data);
+                }
+                if let OutputEnable::Node(oe) = x.oe {
+                    ret.push(oe);
+                }
+            }
+            Some(NodeRef::IoFeedback(x)) => ret.extend(x.input),
+            Some(NodeRef::McFeedback(x)) => ret.extend(x.input),
+            Some(NodeRef::ZIARow(x)) => {
+                if let ZIAInput::Node(n) = x.input {
+                    ret.push(n);
+                }
+            }
+            Some(NodeRef::AndTerm(x)) => {
+                for lit in &x.literals {
+                    if !ret.contains(&lit.input) {
+                        ret.push(lit.input);
+                    }
+                }
+            }
+            Some(NodeRef::OrTerm(x)) => ret.extend_from_slice(&x.inputs),
+            Some(NodeRef::Xor(x)) => {
+                ret.push(x.or);
+                if matches!(x.mode, XorMode::PTC | XorMode::PTCB) {
+                    ret.push(x.ptc);
+                }
+            }
+            Some(NodeRef::Register(x)) => {
+                ret.push(x.d);
+                ret.push(x.clk);
+                ret.extend(x.ce);
+                ret.extend(x.set);
+                if x.reset != x.set {
+                    ret.extend(x.reset);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Compute, for every node, the list of nodes that take it as an input
+    pub fn fanout_map(&self) -> BTreeMap<NodeId, Vec<NodeId>> {
+        let mut ret: BTreeMap<NodeId, Vec<NodeId>> = BTreeMap::new();
+        for id in self.node_ids() {
+            for inp in self.fanin(id) {
+                ret.entry(inp).or_default().push(id);
+            }
+        }
+        ret
+    }
+
+    /// Find every ZIA row that selects a macrocell or pad with its feedback disabled
+    pub fn dangling_zia_references(&self) -> Vec<DanglingZIAReference> {
+        let mut ret = Vec::new();
+        for (fb, fb_nodes) in self.fbs.iter().enumerate() {
+            for (row, x) in fb_nodes.zia_rows.iter().enumerate() {
+                let ZIAInput::Node(source) = x.input else {
+                    continue;
+                };
+                let disabled = match self.node(source) {
+                    Some(NodeRef::McFeedback(x)) => x.setting == MacrocellFeedbackSrc::Disabled,
+                    Some(NodeRef::IoFeedback(x)) => x.setting == IoFeedbackSource::Disabled,
+                    _ => false,
+                };
+                if !disabled {
+                    continue;
+                }
+
+                let row_id = NodeId::ZIARow {
+                    fb: fb as u8,
+                    row: row as u8,
+                };
+                let consumers = fb_nodes
+                    .and_terms
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pt)| pt.literals.iter().any(|lit| lit.input == row_id))
+                    .map(|(pterm, _)| NodeId::AndTerm {
+                        fb: fb as u8,
+                        pterm: pterm as u8,
+                    })
+                    .collect();
+                ret.push(DanglingZIAReference {
+                    fb: fb as u8,
+                    row: row as u8,
+                    source,
+                    consumers,
+                });
+            }
+        }
+        ret
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Decode this bitstream into a [Netlist]
+    pub fn netlist(&self) -> Netlist {
+        Netlist::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partdb::XC2Part;
+
+    #[test]
+    fn smoke_test_all_devices() {
+        for device in [
+            XC2Device::XC2C32,
+            XC2Device::XC2C32A,
+            XC2Device::XC2C64,
+            XC2Device::XC2C64A,
+            XC2Device::XC2C128,
+            XC2Device::XC2C256,
+            XC2Device::XC2C384,
+            XC2Device::XC2C512,
+        ] {
+            let bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+            let netlist = bitstream.netlist();
+            assert_eq!(netlist.fbs.len(), device.num_fbs());
+            for id in netlist.node_ids() {
+                assert!(netlist.node(id).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn decode_and_or() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.fb(1).zia_row(3), ZIARow::MuxChoice(0));
+        bitstream.set_prop(&bitstream.fb(1).and_term(20).inp_n(3), true);
+        bitstream.set_prop(&bitstream.fb(1).or_term(5).inp(20), true);
+
+        let netlist = bitstream.netlist();
+        assert_eq!(
+            netlist.fbs[1].and_terms[20].literals,
+            [Literal {
+                input: NodeId::ZIARow { fb: 1, row: 3 },
+                negated: true
+            }]
+        );
+        assert_eq!(
+            netlist.fbs[1].or_terms[5].inputs,
+            [NodeId::AndTerm { fb: 1, pterm: 20 }]
+        );
+        assert_eq!(
+            netlist.fanin(NodeId::ZIARow { fb: 1, row: 3 }),
+            [zia_entry_node(XC2Device::XC2C32A.zia_table_get_row(3)[0])]
+        );
+    }
+
+    #[test]
+    fn dangling_zia_references() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        // Row 0 choice 3 is FB0 MC1
+        bitstream.set_prop(&bitstream.fb(1).zia_row(0), ZIARow::MuxChoice(3));
+        bitstream.set_prop(&bitstream.fb(1).and_term(7).inp(0), true);
+        bitstream.set_prop(
+            &bitstream.fb(0).mc(1).fb_src(),
+            MacrocellFeedbackSrc::Disabled,
+        );
+
+        let dangling = bitstream.netlist().dangling_zia_references();
+        assert_eq!(
+            dangling,
+            [DanglingZIAReference {
+                fb: 1,
+                row: 0,
+                source: NodeId::McFeedback { fb: 0, mc: 1 },
+                consumers: alloc::vec![NodeId::AndTerm { fb: 1, pterm: 7 }],
+            }]
+        );
+
+        bitstream.set_prop(&bitstream.fb(0).mc(1).fb_src(), MacrocellFeedbackSrc::XorGate);
+        assert_eq!(bitstream.netlist().dangling_zia_references(), []);
+    }
+}
@@ -11,7 +11,8 @@ use bittwiddler_core::prelude::Coordinate;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::zia::{table as zia_table, ZIATableEntry};
+use crate::zia::{table as zia_table, ZIARowChoice, ZIATableEntry};
+use crate::MCS_PER_FB;
 
 /// Coolrunner-II devices
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
@@ -127,6 +128,33 @@ impl XC2Device {
         }
     }
 
+    /// Every ZIA row and mux choice that selects the given source, in order of row.
+    /// Empty if the source does not exist in this device.
+    pub fn zia_rows_for(self, source: ZIATableEntry) -> &'static [ZIARowChoice] {
+        if let ZIATableEntry::Macrocell { fb, mc } | ZIATableEntry::InputPin { fb, mc } = source {
+            if fb as usize >= self.num_fbs() || mc as usize >= MCS_PER_FB {
+                return &[];
+            }
+        }
+        let k = zia_table::zia_source_index(source, self.num_fbs());
+        match self {
+            XC2Device::XC2C32 | XC2Device::XC2C32A => zia_table::ZIA_INV_32.get(k),
+            XC2Device::XC2C64 | XC2Device::XC2C64A => zia_table::ZIA_INV_64.get(k),
+            XC2Device::XC2C128 => zia_table::ZIA_INV_128.get(k),
+            XC2Device::XC2C256 => zia_table::ZIA_INV_256.get(k),
+            XC2Device::XC2C384 => zia_table::ZIA_INV_384.get(k),
+            XC2Device::XC2C512 => zia_table::ZIA_INV_512.get(k),
+        }
+    }
+
+    /// The mux choice that selects the given source in a ZIA row, if there is one
+    pub fn zia_choice_for(self, row: u8, source: ZIATableEntry) -> Option<u8> {
+        self.zia_rows_for(source)
+            .iter()
+            .find(|x| x.row == row)
+            .map(|x| x.choice)
+    }
+
     pub const fn num_zia_choices(self) -> usize {
         match self {
             XC2Device::XC2C32 | XC2Device::XC2C32A => 6,
@@ -246,6 +274,18 @@ impl XC2Device {
         }
     }
 
+    /// Columns of transfer bits, which are cleared in every row except the last two
+    pub const fn transfer_columns(self) -> &'static [usize] {
+        match self {
+            XC2Device::XC2C32 | XC2Device::XC2C32A => &[0, 259],
+            XC2Device::XC2C64 | XC2Device::XC2C64A => &[],
+            XC2Device::XC2C128 => &[0, 375, 376, 751],
+            XC2Device::XC2C256 => &[0, 681, 682, 1363],
+            XC2Device::XC2C384 => &[0, 933, 934, 1867],
+            XC2Device::XC2C512 => &[0, 989, 990, 1979],
+        }
+    }
+
     pub const fn num_io_banks(self) -> usize {
         match self {
             XC2Device::XC2C32 | XC2Device::XC2C64 => 1,
@@ -583,6 +623,45 @@ impl alloc::string::ToString for XC2Part {
 mod tests {
     use super::*;
 
+    #[test]
+    fn zia_inverse_tables() {
+        for device in [
+            XC2Device::XC2C32A,
+            XC2Device::XC2C64A,
+            XC2Device::XC2C128,
+            XC2Device::XC2C256,
+            XC2Device::XC2C384,
+            XC2Device::XC2C512,
+        ] {
+            let mut total = 0;
+            for row in 0..crate::ZIA_ROWS as u8 {
+                for (choice, &ent) in device.zia_table_get_row(row).iter().enumerate() {
+                    assert_eq!(device.zia_choice_for(row, ent), Some(choice as u8));
+                    assert!(device
+                        .zia_rows_for(ent)
+                        .iter()
+                        .all(|x| device.zia_table_get_row(x.row)[x.choice as usize] == ent));
+                }
+            }
+            for fb in 0..device.num_fbs() as u8 {
+                for mc in 0..MCS_PER_FB as u8 {
+                    total += device
+                        .zia_rows_for(ZIATableEntry::Macrocell { fb, mc })
+                        .len();
+                    total += device.zia_rows_for(ZIATableEntry::InputPin { fb, mc }).len();
+                }
+            }
+            total += device
+                .zia_rows_for(ZIATableEntry::DedicatedInputPin)
+                .len();
+            assert_eq!(total, crate::ZIA_ROWS * device.num_zia_choices());
+        }
+        assert_eq!(
+            XC2Device::XC2C32A.zia_rows_for(ZIATableEntry::Macrocell { fb: 2, mc: 0 }),
+            []
+        );
+    }
+
     #[test]
     fn spot_test_valid_part() {
         assert_eq!(
@@ -0,0 +1,374 @@
+//! Power consumption estimation
+//!
+//! This follows the structure of the Xilinx XPower / XAPP317 methodology: a static current
+//! for the core plus a dynamic current which is linear in the frequency of each used resource.
+//! The coefficients are approximate and can be overridden if better numbers are available.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    global_bits_code::ClockDivRatio,
+    io::{InputBufMode, OutputMode},
+    mc::{RegClkSrc, RegResetSrc, RegSetSrc, XorMode},
+    partdb::XC2Device,
+    utilization::UtilizationError,
+    ANDTERMS_PER_FB, MCS_PER_FB, NUM_BUFG_CLK,
+};
+
+/// Coefficients of the linear power model. Currents are in mA, frequencies in MHz.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PowerCoefficients {
+    /// Quiescent core current with nothing toggling
+    pub static_ma: f32,
+    /// Additional static current when the VREF input mode is in use
+    pub vref_static_ma: f32,
+    /// Additional static current per input buffer with a Schmitt trigger enabled
+    pub schmitt_static_ma: f32,
+    /// Core current per used macrocell per MHz of toggle rate
+    pub mc_ma_per_mhz: f32,
+    /// Core current per used product term per MHz of toggle rate of the macrocell it feeds
+    pub pt_ma_per_mhz: f32,
+    /// Core current per clocked register per MHz of clock frequency
+    pub clk_load_ma_per_mhz: f32,
+    /// Core current of the clock divider per MHz of input clock
+    pub clk_div_ma_per_mhz: f32,
+    /// Core current per input buffer per MHz of toggle rate
+    pub ibuf_ma_per_mhz: f32,
+}
+impl PowerCoefficients {
+    /// Default coefficients for the given device
+    pub const fn for_device(device: XC2Device) -> Self {
+        let static_ma = match device {
+            XC2Device::XC2C32 | XC2Device::XC2C32A => 0.022,
+            XC2Device::XC2C64 | XC2Device::XC2C64A => 0.025,
+            XC2Device::XC2C128 => 0.030,
+            XC2Device::XC2C256 => 0.035,
+            XC2Device::XC2C384 => 0.040,
+            XC2Device::XC2C512 => 0.045,
+        };
+        Self {
+            static_ma,
+            vref_static_ma: 0.100,
+            schmitt_static_ma: 0.002,
+            mc_ma_per_mhz: 0.0087,
+            pt_ma_per_mhz: 0.0045,
+            clk_load_ma_per_mhz: 0.0016,
+            clk_div_ma_per_mhz: 0.0040,
+            ibuf_ma_per_mhz: 0.0020,
+        }
+    }
+}
+
+/// User-supplied operating conditions for the power estimate
+#[derive(Clone, PartialEq, Debug)]
+pub struct PowerSettings {
+    /// Core supply voltage
+    pub vccint: f32,
+    /// Output supply voltage of each IO bank
+    pub vccio: Vec<f32>,
+    /// Frequency of each global clock input, in MHz
+    pub gck_mhz: [f32; NUM_BUFG_CLK],
+    /// Toggle rate, in MHz, used for any signal without an explicit entry in `toggle_mhz`. This
+    /// includes product term clocks, which are not derived from `gck_mhz`.
+    pub default_toggle_mhz: f32,
+    /// Toggle rate, in MHz, of individual signals (e.g. measured in simulation)
+    pub toggle_mhz: BTreeMap<PowerSignal, f32>,
+    /// IO bank of each pad, indexed by (FB, MC). Only needed on devices with more than one IO
+    /// bank, where every driven output must have an entry.
+    pub pad_bank: BTreeMap<(u8, u8), u8>,
+    /// External load capacitance on each output, in pF
+    pub load_pf: f32,
+    /// Fraction of time that DataGate is asserted (and gated inputs are therefore held)
+    pub data_gate_duty: f32,
+}
+impl PowerSettings {
+    pub fn new(device: XC2Device) -> Self {
+        Self {
+            vccint: 1.8,
+            vccio: vec![3.3; device.num_io_banks()],
+            gck_mhz: [0.0; NUM_BUFG_CLK],
+            default_toggle_mhz: 0.0,
+            toggle_mhz: BTreeMap::new(),
+            pad_bank: BTreeMap::new(),
+            load_pf: 10.0,
+            data_gate_duty: 0.0,
+        }
+    }
+
+    fn toggle(&self, sig: PowerSignal) -> f32 {
+        *self.toggle_mhz.get(&sig).unwrap_or(&self.default_toggle_mhz)
+    }
+}
+
+/// A signal whose toggle rate can be specified
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum PowerSignal {
+    /// The output of a macrocell
+    Macrocell { fb: u8, mc: u8 },
+    /// The signal on an IO pad
+    Pad { fb: u8, mc: u8 },
+    /// The clock of a register clocked by PTC or CTC
+    ProductTermClock { fb: u8, mc: u8 },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PowerError {
+    /// The IO banks could not be used to summarize the resources used
+    Utilization(UtilizationError),
+    /// A driven output has no entry in [PowerSettings::pad_bank] on a device with several banks
+    UnknownBank { fb: u8, mc: u8 },
+    /// `vccio` does not have one entry per IO bank
+    VccioCount { expected: usize, found: usize },
+}
+impl Display for PowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerError::Utilization(e) => write!(f, "{}", e),
+            PowerError::UnknownBank { fb, mc } => {
+                write!(f, "the IO bank of FB{} IO{} is not known", fb, mc)
+            }
+            PowerError::VccioCount { expected, found } => write!(
+                f,
+                "{} VCCIO voltages were given but the device has {} IO banks",
+                found, expected
+            ),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for PowerError {}
+impl From<UtilizationError> for PowerError {
+    fn from(e: UtilizationError) -> Self {
+        PowerError::Utilization(e)
+    }
+}
+
+/// Result of the power estimate. Currents are in mA, power in mW.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PowerEstimate {
+    pub icc_static_ma: f32,
+    pub icc_dynamic_ma: f32,
+    /// Output current drawn from each IO bank
+    pub icco_ma: Vec<f32>,
+    pub used_product_terms: usize,
+    pub used_macrocells: usize,
+    pub total_mw: f32,
+}
+
+fn mc_pterms<B: BitHolder>(bitstream: &Coolrunner2<B>, fb: u8, mc: u8) -> usize {
+    let or_term = bitstream.fb(fb).or_term(mc);
+    let mut n = (0..ANDTERMS_PER_FB as u8)
+        .filter(|&pterm_i| bitstream.get_prop(&or_term.inp(pterm_i)))
+        .count();
+    let x = bitstream.fb(fb).mc(mc);
+    // PTA, PTC
+    if bitstream.get_prop(&x.r_src()) == RegResetSrc::PTA
+        || bitstream.get_prop(&x.s_src()) == RegSetSrc::PTA
+    {
+        n += 1;
+    }
+    if bitstream.get_prop(&x.clk_src()) == RegClkSrc::PTC
+        || matches!(bitstream.get_prop(&x.xor_mode()), XorMode::PTC | XorMode::PTCB)
+    {
+        n += 1;
+    }
+    n
+}
+
+fn output_drives(mode: OutputMode) -> bool {
+    !matches!(
+        mode,
+        OutputMode::Disabled | OutputMode::CGND | OutputMode::Invalid { .. }
+    )
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Estimate the current and power consumption of this bitstream
+    pub fn estimate_power(
+        &self,
+        settings: &PowerSettings,
+        coeffs: &PowerCoefficients,
+    ) -> Result<PowerEstimate, PowerError> {
+        let device = self.part.device;
+        if settings.vccio.len() != device.num_io_banks() {
+            return Err(PowerError::VccioCount {
+                expected: device.num_io_banks(),
+                found: settings.vccio.len(),
+            });
+        }
+
+        let mut icc_static_ma = coeffs.static_ma;
+        let mut icc_dynamic_ma = 0.0;
+        let mut icco_ma = vec![0.0; device.num_io_banks()];
+
+        // Effective global clock frequencies, taking the divider on GCK2 into account
+        let mut gck_mhz = settings.gck_mhz;
+        if device.has_large_macrocells() && self.get_prop(&self.clock_divider().enabled()) {
+            let ratio = self.get_prop(&self.clock_divider().ratio()).divisor() as f32;
+            icc_dynamic_ma += coeffs.clk_div_ma_per_mhz * gck_mhz[2];
+            gck_mhz[2] /= ratio;
+        }
+
+        if device.has_large_macrocells() && self.get_prop(&self.use_vref()) {
+            icc_static_ma += coeffs.vref_static_ma;
+        }
+
+        let data_gate = device.has_large_macrocells() && self.get_prop(&self.data_gate_enabled());
+
+        let utilization = self.utilization_with_banks(&settings.pad_bank)?;
+        for fb in 0..device.num_fbs() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                if utilization.fbs[fb as usize].macrocell_used(mc) {
+                    let f = settings.toggle(PowerSignal::Macrocell { fb, mc });
+                    let n_pt = mc_pterms(self, fb, mc) as f32;
+                    icc_dynamic_ma += f * (coeffs.mc_ma_per_mhz + n_pt * coeffs.pt_ma_per_mhz);
+
+                    let f_clk = match self.get_prop(&self.fb(fb).mc(mc).clk_src()) {
+                        RegClkSrc::GCK0 => gck_mhz[0],
+                        RegClkSrc::GCK1 => gck_mhz[1],
+                        RegClkSrc::GCK2 => gck_mhz[2],
+                        RegClkSrc::PTC | RegClkSrc::CTC => {
+                            settings.toggle(PowerSignal::ProductTermClock { fb, mc })
+                        }
+                    };
+                    icc_dynamic_ma += f_clk * coeffs.clk_load_ma_per_mhz;
+                }
+
+                if !device.has_io_at(fb, mc) {
+                    continue;
+                }
+                let io = self.fb(fb).io(mc);
+                let f = settings.toggle(PowerSignal::Pad { fb, mc });
+
+                if device.has_large_macrocells() {
+                    if matches!(
+                        self.get_prop(&io.input_pad_mode()),
+                        InputBufMode::NoVrefSt | InputBufMode::UsesVref
+                    ) {
+                        icc_static_ma += coeffs.schmitt_static_ma;
+                    }
+                } else if self.get_prop(&io.schmitt_trigger()) {
+                    icc_static_ma += coeffs.schmitt_static_ma;
+                }
+
+                let mode = self.get_prop(&io.output_pad_mode());
+                if output_drives(mode) {
+                    let bank = match settings.pad_bank.get(&(fb, mc)) {
+                        Some(&bank) => bank as usize,
+                        None if device.num_io_banks() == 1 => 0,
+                        None => return Err(PowerError::UnknownBank { fb, mc }),
+                    };
+                    // C * V * f, in pF * V * MHz = uA
+                    icco_ma[bank] += settings.load_pf * settings.vccio[bank] * f / 1000.0;
+                } else {
+                    let mut f_in = f;
+                    if data_gate && self.get_prop(&io.use_data_gate()) {
+                        f_in *= 1.0 - settings.data_gate_duty;
+                    }
+                    icc_dynamic_ma += f_in * coeffs.ibuf_ma_per_mhz;
+                }
+            }
+        }
+
+        let mut total_mw = (icc_static_ma + icc_dynamic_ma) * settings.vccint;
+        for (bank, icco) in icco_ma.iter().enumerate() {
+            total_mw += icco * settings.vccio[bank];
+        }
+
+        Ok(PowerEstimate {
+            icc_static_ma,
+            icc_dynamic_ma,
+            icco_ma,
+            used_product_terms: utilization.num_product_terms(),
+            used_macrocells: utilization.num_macrocells(),
+            total_mw,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::{IoFeedbackSource, PinOutputSrc},
+        partdb::XC2Part,
+        zia::{ZIARow, ZIATableEntry},
+    };
+    use bitvec::boxed::BitBox;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    /// The pad at FB0 MC5 inverted onto the pad at FB0 MC3
+    fn inverter() -> Coolrunner2<BitBox> {
+        let device = XC2Device::XC2C32A;
+        let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        let zia = device.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        bitstream.set_prop(&bitstream.fb(0).io(5).fb_src(), IoFeedbackSource::IO);
+        bitstream.set_prop(&bitstream.fb(0).zia_row(zia.row), ZIARow::MuxChoice(zia.choice));
+        bitstream.set_prop(&bitstream.fb(0).and_term(0).inp_n(zia.row), true);
+        bitstream.set_prop(&bitstream.fb(0).or_term(3).inp(0), true);
+        bitstream.set_prop(&bitstream.fb(0).mc(3).xor_mode(), XorMode::_0);
+        bitstream.set_prop(&bitstream.fb(0).io(3).output_src(), PinOutputSrc::XorGate);
+        bitstream.set_prop(&bitstream.fb(0).io(3).output_pad_mode(), OutputMode::PushPull);
+        bitstream
+    }
+
+    #[test]
+    fn estimate() {
+        let bitstream = inverter();
+        let coeffs = PowerCoefficients::for_device(XC2Device::XC2C32A);
+        let mut settings = PowerSettings::new(XC2Device::XC2C32A);
+
+        // The output pad has to be given a bank on this device
+        assert_eq!(
+            bitstream.estimate_power(&settings, &coeffs),
+            Err(PowerError::UnknownBank { fb: 0, mc: 3 })
+        );
+        let fb_mc = (0, 3);
+        settings.pad_bank.insert(fb_mc, 0);
+
+        // Nothing toggles, so only static current is drawn
+        let x = bitstream.estimate_power(&settings, &coeffs).unwrap();
+        assert_eq!(x.used_macrocells, 1);
+        assert_eq!(x.used_product_terms, 1);
+        assert_eq!(x.icc_dynamic_ma, 0.0);
+        assert_eq!(x.icco_ma, [0.0, 0.0]);
+        assert!(x.icc_static_ma >= coeffs.static_ma);
+        assert!(close(x.total_mw, x.icc_static_ma * 1.8));
+
+        settings.toggle_mhz.insert(PowerSignal::Macrocell { fb: 0, mc: 3 }, 10.0);
+        settings.toggle_mhz.insert(PowerSignal::Pad { fb: 0, mc: 3 }, 10.0);
+        settings.pad_bank.insert(fb_mc, 1);
+        settings.vccio[1] = 1.8;
+        let y = bitstream.estimate_power(&settings, &coeffs).unwrap();
+        assert_eq!(y.icc_static_ma, x.icc_static_ma);
+        let mc_ma = 10.0 * (coeffs.mc_ma_per_mhz + coeffs.pt_ma_per_mhz);
+        assert!(close(y.icc_dynamic_ma, mc_ma));
+        // 10 pF at 1.8 V and 10 MHz
+        assert_eq!(y.icco_ma[0], 0.0);
+        assert!(close(y.icco_ma[1], 0.18));
+        assert!(close(y.total_mw, (x.icc_static_ma + mc_ma) * 1.8 + 0.18 * 1.8));
+
+        settings.pad_bank.insert(fb_mc, 2);
+        assert_eq!(
+            bitstream.estimate_power(&settings, &coeffs),
+            Err(PowerError::Utilization(UtilizationError::InvalidBank { fb: 0, mc: 3, bank: 2 }))
+        );
+        settings.vccio.pop();
+        assert_eq!(
+            bitstream.estimate_power(&settings, &coeffs),
+            Err(PowerError::VccioCount { expected: 2, found: 1 })
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Power-up and global set/reset behavior
+//!
+//! This describes the state of the device once configuration has finished and before any clock
+//! edge: what each register contains, what each pin is doing, and what changes when GSR is
+//! asserted. Pins that are not being driven are held by the weak pull-up or bus keeper selected
+//! by `global_termination`, if their termination is enabled, and float otherwise.
+
+use core::fmt::{self, Display};
+
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    global_bits_code::GlobalTermination,
+    io::PinOutputSrc,
+    netlist::{Netlist, NodeId},
+    tristate::PinEnable,
+    MCS_PER_FB,
+};
+
+/// What happens to a register when GSR is asserted
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum GsrAction {
+    Unaffected,
+    Set,
+    Reset,
+    /// Both the set and reset inputs come from GSR
+    SetAndReset,
+}
+impl GsrAction {
+    /// The value of the register while GSR is asserted, or `None` if it is not determined
+    pub fn value(self, current: Option<bool>) -> Option<bool> {
+        match self {
+            GsrAction::Unaffected => current,
+            GsrAction::Set => Some(true),
+            GsrAction::Reset => Some(false),
+            GsrAction::SetAndReset => None,
+        }
+    }
+}
+impl Display for GsrAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GsrAction::Unaffected => write!(f, "unaffected"),
+            GsrAction::Set => write!(f, "set"),
+            GsrAction::Reset => write!(f, "reset"),
+            GsrAction::SetAndReset => write!(f, "both set and reset"),
+        }
+    }
+}
+
+/// What holds a pin that is not being driven
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum WeakPull {
+    Floating,
+    Keeper,
+    PullUp,
+}
+impl Display for WeakPull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeakPull::Floating => write!(f, "floating"),
+            WeakPull::Keeper => write!(f, "bus keeper"),
+            WeakPull::PullUp => write!(f, "pull-up"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RegisterPowerUp {
+    pub fb: u8,
+    pub mc: u8,
+    pub init_state: bool,
+    pub gsr: GsrAction,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PinPowerUp {
+    pub fb: u8,
+    pub mc: u8,
+    pub enable: PinEnable,
+    /// The value being output after configuration, if it is known without evaluating any logic
+    pub value: Option<bool>,
+    /// The value being output while GSR is asserted, if it is known without evaluating any logic
+    pub gsr_value: Option<bool>,
+    /// What holds the pin when it is not being driven
+    pub pull: WeakPull,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PowerUpReport {
+    pub gsr_enabled: bool,
+    /// Whether GSR is asserted when its pin is low
+    pub gsr_invert: bool,
+    /// Every used register
+    pub registers: Vec<RegisterPowerUp>,
+    /// Every IO pad
+    pub pins: Vec<PinPowerUp>,
+    /// The input-only pin on the XC2C32(A)
+    pub dedicated_input_pull: Option<WeakPull>,
+}
+impl Display for PowerUpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.gsr_enabled {
+            writeln!(f, "GSR is not enabled and is never asserted")?;
+        } else if self.gsr_invert {
+            writeln!(f, "GSR is asserted while its pin is low")?;
+        } else {
+            writeln!(f, "GSR is asserted while its pin is high")?;
+        }
+
+        for x in &self.registers {
+            writeln!(
+                f,
+                "FB{} MC{} powers up as {}, on GSR {}",
+                x.fb, x.mc, x.init_state as u8, x.gsr
+            )?;
+        }
+
+        let fmt_value = |f: &mut fmt::Formatter<'_>, val: Option<bool>| match val {
+            Some(val) => write!(f, "{}", val as u8),
+            None => write!(f, "from logic"),
+        };
+        for x in &self.pins {
+            write!(f, "FB{} IO{} ", x.fb, x.mc)?;
+            match x.enable {
+                PinEnable::Off => write!(f, "tristated")?,
+                PinEnable::Invalid => write!(f, "has an invalid output mode")?,
+                enable => {
+                    write!(f, "output enable {}, value ", enable)?;
+                    fmt_value(f, x.value)?;
+                    if self.gsr_enabled && x.gsr_value != x.value {
+                        write!(f, ", on GSR ")?;
+                        fmt_value(f, x.gsr_value)?;
+                    }
+                }
+            }
+            writeln!(f, ", {} when not driven", x.pull)?;
+        }
+        if let Some(pull) = self.dedicated_input_pull {
+            writeln!(f, "dedicated input {}", pull)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Netlist {
+    /// Power-up value and GSR behavior of every used register
+    pub fn register_power_up(&self) -> Vec<RegisterPowerUp> {
+        let mut ret = Vec::new();
+        for fb in 0..self.fbs.len() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                if !self.reg_is_used(fb, mc) {
+                    continue;
+                }
+                let reg = &self.fbs[fb as usize].registers[mc as usize];
+                let gsr = match (
+                    reg.set == Some(NodeId::GlobalSetReset),
+                    reg.reset == Some(NodeId::GlobalSetReset),
+                ) {
+                    (false, false) => GsrAction::Unaffected,
+                    (true, false) => GsrAction::Set,
+                    (false, true) => GsrAction::Reset,
+                    (true, true) => GsrAction::SetAndReset,
+                };
+                ret.push(RegisterPowerUp {
+                    fb,
+                    mc,
+                    init_state: reg.init_state,
+                    gsr,
+                });
+            }
+        }
+        ret
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Describe what every register and pin does at power-up and when GSR is asserted
+    pub fn power_up_report(&self) -> PowerUpReport {
+        let netlist = self.netlist();
+        let global_pull = match self.get_prop(&self.global_termination()) {
+            GlobalTermination::Keeper => WeakPull::Keeper,
+            GlobalTermination::PullUp => WeakPull::PullUp,
+        };
+        let pull_for = |enabled| if enabled { global_pull } else { WeakPull::Floating };
+
+        let registers = netlist.register_power_up();
+        let pins = netlist
+            .pin_output_enables()
+            .into_iter()
+            .map(|x| {
+                let io = netlist.fbs[x.fb as usize].io_pads[x.mc as usize]
+                    .as_ref()
+                    .unwrap();
+                let (value, gsr_value) = match x.enable {
+                    PinEnable::Ground => (Some(false), Some(false)),
+                    _ if io.output_src == PinOutputSrc::FlipFlop => {
+                        let reg = &netlist.fbs[x.fb as usize].registers[x.mc as usize];
+                        let gsr = registers
+                            .iter()
+                            .find(|r| (r.fb, r.mc) == (x.fb, x.mc))
+                            .map_or(GsrAction::Unaffected, |r| r.gsr);
+                        (Some(reg.init_state), gsr.value(Some(reg.init_state)))
+                    }
+                    _ => (None, None),
+                };
+                let pull = pull_for(self.get_prop(&self.fb(x.fb).io(x.mc).termination_enabled()));
+                PinPowerUp {
+                    fb: x.fb,
+                    mc: x.mc,
+                    enable: x.enable,
+                    value,
+                    gsr_value,
+                    pull,
+                }
+            })
+            .collect();
+
+        let dedicated_input_pull = if netlist.dedicated_input.is_some() {
+            Some(pull_for(
+                self.get_prop(&self.extra_dedicated_input().termination_enabled()),
+            ))
+        } else {
+            None
+        };
+
+        PowerUpReport {
+            gsr_enabled: netlist.gsr.enabled,
+            gsr_invert: netlist.gsr.invert,
+            registers,
+            pins,
+            dedicated_input_pull,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::OutputMode,
+        mc::{MacrocellFeedbackSrc, RegResetSrc},
+        partdb::{XC2Device, XC2Part},
+    };
+
+    #[test]
+    fn gsr_reset() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let mc = bitstream.fb(0).mc(2);
+        bitstream.set_prop(&mc.fb_src(), MacrocellFeedbackSrc::FlipFlop);
+        bitstream.set_prop(&mc.init_state(), true);
+        bitstream.set_prop(&mc.r_src(), RegResetSrc::GSR);
+        let io = bitstream.fb(0).io(2);
+        bitstream.set_prop(&io.output_src(), PinOutputSrc::FlipFlop);
+        bitstream.set_prop(&io.output_pad_mode(), OutputMode::PushPull);
+        bitstream.set_prop(&io.termination_enabled(), true);
+        bitstream.set_prop(&bitstream.global_termination(), GlobalTermination::Keeper);
+        bitstream.set_prop(&bitstream.gsr_enabled(), true);
+
+        let report = bitstream.power_up_report();
+        assert_eq!(report.registers.len(), 1);
+        assert_eq!(report.registers[0].gsr, GsrAction::Reset);
+        let pin = report.pins.iter().find(|x| x.mc == 2).unwrap();
+        assert_eq!(pin.enable, PinEnable::Always);
+        assert_eq!(pin.value, Some(true));
+        assert_eq!(pin.gsr_value, Some(false));
+        assert_eq!(pin.pull, WeakPull::Keeper);
+    }
+}
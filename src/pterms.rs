@@ -0,0 +1,231 @@
+//! Product term allocation within a function block
+//!
+//! Each function block has [ANDTERMS_PER_FB] AND terms, any of which can be ORed into any
+//! macrocell. Some of them also have a dedicated use which only works at a fixed position:
+//! PTA/PTB/PTC for each macrocell and the CTC/CTR/CTS/CTE control terms shared by the whole
+//! function block. The allocator places the dedicated terms first and then fits the remaining
+//! terms into whatever is left, reusing an existing AND term wherever an identical one is needed.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    fb, ANDTERMS_PER_FB, MCS_PER_FB,
+};
+
+/// The inputs of one AND term, as ZIA row numbers. A term with no inputs is always true.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+pub struct AndTerm {
+    /// Rows used as they are
+    pub inp: BTreeSet<u8>,
+    /// Rows used complemented, through `inp_n`
+    pub inp_n: BTreeSet<u8>,
+}
+impl AndTerm {
+    /// Add `row`, or its complement, as an input
+    pub fn add(&mut self, row: u8, negated: bool) {
+        if negated {
+            self.inp_n.insert(row);
+        } else {
+            self.inp.insert(row);
+        }
+    }
+
+    /// Whether the term uses both a row and its complement, which makes it always false
+    pub fn is_contradiction(&self) -> bool {
+        self.inp.intersection(&self.inp_n).next().is_some()
+    }
+}
+
+/// The product terms needed by one macrocell
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MacrocellTerms {
+    /// Terms ORed together into the macrocell
+    pub sum: Vec<AndTerm>,
+    /// Set or reset
+    pub pta: Option<AndTerm>,
+    /// Output enable
+    pub ptb: Option<AndTerm>,
+    /// Clock, clock enable, or XOR input
+    pub ptc: Option<AndTerm>,
+}
+
+/// The product terms needed by a whole function block
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct FunctionBlockTerms {
+    pub mcs: [MacrocellTerms; MCS_PER_FB],
+    pub ctc: Option<AndTerm>,
+    pub ctr: Option<AndTerm>,
+    pub cts: Option<AndTerm>,
+    pub cte: Option<AndTerm>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PTermAllocation {
+    /// The AND term at each position, if used
+    pub terms: [Option<AndTerm>; ANDTERMS_PER_FB],
+    /// Positions ORed into each macrocell
+    pub or_terms: [BTreeSet<u8>; MCS_PER_FB],
+}
+impl PTermAllocation {
+    pub fn num_used(&self) -> usize {
+        self.terms.iter().filter(|x| x.is_some()).count()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PTermAllocError {
+    /// More distinct AND terms are needed than fit in the function block
+    OutOfTerms { needed: usize },
+}
+impl Display for PTermAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PTermAllocError::OutOfTerms { needed } => write!(
+                f,
+                "{} product terms are needed but only {} are available",
+                needed, ANDTERMS_PER_FB
+            ),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for PTermAllocError {}
+
+/// Place the product terms of a function block
+pub fn allocate_pterms(terms: &FunctionBlockTerms) -> Result<PTermAllocation, PTermAllocError> {
+    let mut ret = PTermAllocation {
+        terms: core::array::from_fn(|_| None),
+        or_terms: core::array::from_fn(|_| BTreeSet::new()),
+    };
+    // Where each distinct term has been placed
+    let mut placed: BTreeMap<&AndTerm, u8> = BTreeMap::new();
+
+    let mut dedicated = alloc::vec![
+        (fb::CTC, &terms.ctc),
+        (fb::CTR, &terms.ctr),
+        (fb::CTS, &terms.cts),
+        (fb::CTE, &terms.cte),
+    ];
+    for (mc, x) in terms.mcs.iter().enumerate() {
+        let mc = mc as u8;
+        dedicated.push((fb::pta(mc), &x.pta));
+        dedicated.push((fb::ptb(mc), &x.ptb));
+        dedicated.push((fb::ptc(mc), &x.ptc));
+    }
+    for (idx, x) in dedicated {
+        if let Some(x) = x {
+            ret.terms[idx as usize] = Some(x.clone());
+            placed.entry(x).or_insert(idx);
+        }
+    }
+
+    // Everything else goes in the lowest free position. This uses the general-purpose terms and
+    // unused control terms before taking positions from other macrocells.
+    let mut free = (0..ANDTERMS_PER_FB as u8).filter(|&i| ret.terms[i as usize].is_none());
+    let mut new_terms = Vec::new();
+    let mut needed = ret.num_used();
+    for (mc, x) in terms.mcs.iter().enumerate() {
+        for term in &x.sum {
+            let idx = match placed.get(term) {
+                Some(&idx) => Some(idx),
+                None => {
+                    needed += 1;
+                    let idx = free.next();
+                    if let Some(idx) = idx {
+                        placed.insert(term, idx);
+                        new_terms.push((idx, term));
+                    }
+                    idx
+                }
+            };
+            if let Some(idx) = idx {
+                ret.or_terms[mc].insert(idx);
+            }
+        }
+    }
+    drop(free);
+
+    if needed > ANDTERMS_PER_FB {
+        return Err(PTermAllocError::OutOfTerms { needed });
+    }
+    for (idx, term) in new_terms {
+        ret.terms[idx as usize] = Some(term.clone());
+    }
+    Ok(ret)
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Write the AND and OR array connections for a function block. Positions that are not used
+    /// are not changed.
+    pub fn set_pterm_allocation(&mut self, fb: u8, alloc: &PTermAllocation) {
+        for (idx, term) in alloc.terms.iter().enumerate() {
+            let Some(term) = term else {
+                continue;
+            };
+            let and_term = self.fb(fb).and_term(idx as u8);
+            for &row in &term.inp {
+                self.set_prop(&and_term.inp(row), true);
+            }
+            for &row in &term.inp_n {
+                self.set_prop(&and_term.inp_n(row), true);
+            }
+        }
+        for (mc, idxs) in alloc.or_terms.iter().enumerate() {
+            for &idx in idxs {
+                self.set_prop(&self.fb(fb).or_term(mc as u8).inp(idx), true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(lits: &[(u8, bool)]) -> AndTerm {
+        let mut ret = AndTerm::default();
+        for &(row, negated) in lits {
+            ret.add(row, negated);
+        }
+        ret
+    }
+
+    #[test]
+    fn sharing() {
+        let a_nb = term(&[(0, false), (1, true)]);
+        let c = term(&[(2, false)]);
+        let mut terms = FunctionBlockTerms::default();
+        terms.mcs[0].sum = alloc::vec![a_nb.clone(), c.clone()];
+        terms.mcs[1].sum = alloc::vec![c.clone()];
+        terms.mcs[1].pta = Some(a_nb.clone());
+        terms.ctr = Some(term(&[(3, true)]));
+
+        let alloc = allocate_pterms(&terms).unwrap();
+        assert_eq!(alloc.num_used(), 3);
+        assert_eq!(alloc.terms[fb::pta(1) as usize], Some(a_nb));
+        assert_eq!(alloc.terms[fb::CTR as usize].as_ref().unwrap().inp_n.len(), 1);
+        // `c` takes the first general-purpose term and is shared by both macrocells
+        assert_eq!(alloc.or_terms[1], BTreeSet::from([0]));
+        assert_eq!(alloc.or_terms[0], BTreeSet::from([0, fb::pta(1)]));
+    }
+
+    #[test]
+    fn out_of_terms() {
+        let mut terms = FunctionBlockTerms::default();
+        for i in 0..ANDTERMS_PER_FB as u8 + 1 {
+            terms.mcs[0].sum.push(term(&[(i % 40, i >= 40)]));
+        }
+        assert_eq!(
+            allocate_pterms(&terms),
+            Err(PTermAllocError::OutOfTerms { needed: ANDTERMS_PER_FB + 1 })
+        );
+    }
+}
@@ -0,0 +1,628 @@
+//! Cycle-based logic simulation of a bitstream
+//!
+//! The simulator evaluates the decoded [Netlist] directly. Inputs (pads and the global pins) are
+//! set with the `set_*` functions, and [Simulator::step] then propagates them through the
+//! combinational logic and clocks any registers that see an active clock edge.
+//!
+//! Since the crate does not know which package pin is which, the global clock, set/reset and
+//! tristate pins are driven with their own functions rather than through [Simulator::set_pin].
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    mc::{FlipFlopMode, XorMode},
+    netlist::{IoPadNode, Netlist, NodeId, NodeRef, OutputEnable, ZIAInput},
+    MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS,
+};
+
+/// How many times combinational logic is re-evaluated before giving up on it settling
+const MAX_SETTLE_ITERATIONS: usize = 64;
+
+/// What the device is doing to a pad
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PinState {
+    HighZ,
+    Driven(bool),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SimError {
+    /// The combinational logic (including transparent latches) did not settle
+    Oscillation,
+}
+impl Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::Oscillation => write!(f, "combinational logic does not settle"),
+        }
+    }
+}
+
+struct ClockDividerState {
+    prev_pin: bool,
+    count: u32,
+    out: bool,
+}
+
+pub struct Simulator {
+    netlist: Netlist,
+    gck: [bool; NUM_BUFG_CLK],
+    gsr: bool,
+    gts: [bool; NUM_BUFG_GTS],
+    dedicated_input: bool,
+    /// Values driven onto each pad from outside the device
+    pads: Vec<[bool; MCS_PER_FB]>,
+    regs: Vec<[bool; MCS_PER_FB]>,
+    /// Clock value seen by each register at the end of the previous step
+    prev_clk: Vec<[bool; MCS_PER_FB]>,
+    divider: ClockDividerState,
+    values: BTreeMap<NodeId, bool>,
+}
+
+/// Evaluates every node once, given the current state
+struct Evaluator<'a> {
+    sim: &'a Simulator,
+    memo: BTreeMap<NodeId, bool>,
+    in_progress: BTreeSet<NodeId>,
+}
+impl<'a> Evaluator<'a> {
+    fn eval(&mut self, id: NodeId) -> bool {
+        if let Some(&x) = self.memo.get(&id) {
+            return x;
+        }
+        if !self.in_progress.insert(id) {
+            // Feedback loop: use the value from the previous iteration
+            return *self.sim.values.get(&id).unwrap_or(&false);
+        }
+
+        let sim = self.sim;
+        let ret = match sim.netlist.node(id).unwrap() {
+            NodeRef::GlobalClock(x) => {
+                let NodeId::GlobalClock(i) = id else {
+                    unreachable!()
+                };
+                if !x.enabled {
+                    false
+                } else if x.divider.is_some() {
+                    sim.divider.out
+                } else {
+                    sim.gck[i as usize]
+                }
+            }
+            NodeRef::GlobalSetReset(x) => x.enabled && (sim.gsr ^ x.invert),
+            NodeRef::GlobalTristate(x) => {
+                let NodeId::GlobalTristate(i) = id else {
+                    unreachable!()
+                };
+                !x.enabled || !(sim.gts[i as usize] ^ x.invert)
+            }
+            NodeRef::DedicatedInput(_) => sim.dedicated_input,
+            NodeRef::IoPad(_) => {
+                let NodeId::IoPad { fb, mc } = id else {
+                    unreachable!()
+                };
+                match self.pin_state(fb, mc) {
+                    PinState::Driven(val) => val,
+                    PinState::HighZ => sim.pads[fb as usize][mc as usize],
+                }
+            }
+            NodeRef::IoFeedback(x) => x.input.is_some_and(|input| self.eval(input)),
+            NodeRef::McFeedback(x) => x.input.is_some_and(|input| self.eval(input)),
+            NodeRef::ZIARow(x) => match x.input {
+                ZIAInput::Const(val) => val,
+                ZIAInput::Node(n) => self.eval(n),
+                ZIAInput::Invalid => false,
+            },
+            NodeRef::AndTerm(x) => x
+                .literals
+                .iter()
+                .all(|lit| self.eval(lit.input) ^ lit.negated),
+            NodeRef::OrTerm(x) => x.inputs.iter().any(|&input| self.eval(input)),
+            NodeRef::Xor(x) => {
+                let or = self.eval(x.or);
+                match x.mode {
+                    XorMode::_0 => or,
+                    XorMode::_1 => !or,
+                    XorMode::PTC => or ^ self.eval(x.ptc),
+                    XorMode::PTCB => !(or ^ self.eval(x.ptc)),
+                }
+            }
+            NodeRef::Register(_) => {
+                let NodeId::Register { fb, mc } = id else {
+                    unreachable!()
+                };
+                sim.regs[fb as usize][mc as usize]
+            }
+        };
+
+        self.in_progress.remove(&id);
+        self.memo.insert(id, ret);
+        ret
+    }
+
+    fn pin_state(&mut self, fb: u8, mc: u8) -> PinState {
+        let sim = self.sim;
+        let io = sim.netlist.fbs[fb as usize].io_pads[mc as usize]
+            .as_ref()
+            .unwrap();
+        drive_state(io, |id| self.eval(id))
+    }
+}
+
+/// What the output driver of a pad is doing, given the values of the nodes controlling it
+fn drive_state(io: &IoPadNode, mut eval: impl FnMut(NodeId) -> bool) -> PinState {
+    match io.oe {
+        OutputEnable::Disabled | OutputEnable::Invalid => PinState::HighZ,
+        OutputEnable::Always => PinState::Driven(eval(io.output_data)),
+        OutputEnable::OpenDrain => {
+            if eval(io.output_data) {
+                PinState::HighZ
+            } else {
+                PinState::Driven(false)
+            }
+        }
+        OutputEnable::Node(oe) => {
+            if eval(oe) {
+                PinState::Driven(eval(io.output_data))
+            } else {
+                PinState::HighZ
+            }
+        }
+        OutputEnable::Ground => PinState::Driven(false),
+    }
+}
+
+impl Simulator {
+    /// Create a simulator in the power-up state, with all inputs low
+    pub fn new(netlist: Netlist) -> Result<Self, SimError> {
+        let num_fbs = netlist.fbs.len();
+        let mut ret = Self {
+            netlist,
+            gck: [false; NUM_BUFG_CLK],
+            gsr: false,
+            gts: [false; NUM_BUFG_GTS],
+            dedicated_input: false,
+            pads: vec![[false; MCS_PER_FB]; num_fbs],
+            regs: vec![[false; MCS_PER_FB]; num_fbs],
+            prev_clk: vec![[false; MCS_PER_FB]; num_fbs],
+            divider: ClockDividerState {
+                prev_pin: false,
+                count: 0,
+                out: false,
+            },
+            values: BTreeMap::new(),
+        };
+        ret.power_up()?;
+        Ok(ret)
+    }
+
+    pub fn netlist(&self) -> &Netlist {
+        &self.netlist
+    }
+
+    /// Return every register to its `init_state` and restart the clock divider
+    pub fn power_up(&mut self) -> Result<(), SimError> {
+        for (fb, fb_nodes) in self.netlist.fbs.iter().enumerate() {
+            for (mc, reg) in fb_nodes.registers.iter().enumerate() {
+                self.regs[fb][mc] = reg.init_state;
+            }
+        }
+        self.divider = ClockDividerState {
+            prev_pin: self.gck[2],
+            count: 0,
+            out: false,
+        };
+        self.values.clear();
+        let ret = self.settle();
+        self.latch_clocks();
+        ret
+    }
+
+    /// Set the value driven onto a pad from outside. This is ignored while the device drives it.
+    ///
+    /// Like the accessors on [Coolrunner2], this and the other `set_*` functions panic if the
+    /// index is out of range for the device.
+    pub fn set_pin(&mut self, fb: u8, mc: u8, val: bool) {
+        self.pads[fb as usize][mc as usize] = val;
+    }
+    pub fn set_gck(&mut self, gck_idx: u8, val: bool) {
+        self.gck[gck_idx as usize] = val;
+    }
+    pub fn set_gsr(&mut self, val: bool) {
+        self.gsr = val;
+    }
+    pub fn set_gts(&mut self, gts_idx: u8, val: bool) {
+        self.gts[gts_idx as usize] = val;
+    }
+    /// Only meaningful on the XC2C32(A)
+    pub fn set_dedicated_input(&mut self, val: bool) {
+        self.dedicated_input = val;
+    }
+
+    /// Value on a global clock pin, before the enable and the clock divider
+    pub fn gck_pin(&self, gck_idx: u8) -> bool {
+        self.gck[gck_idx as usize]
+    }
+    /// Value on the global set/reset pin, before the enable and inversion
+    pub fn gsr_pin(&self) -> bool {
+        self.gsr
+    }
+    /// Value on a global tristate pin, before the enable and inversion
+    pub fn gts_pin(&self, gts_idx: u8) -> bool {
+        self.gts[gts_idx as usize]
+    }
+    pub fn dedicated_input_pin(&self) -> bool {
+        self.dedicated_input
+    }
+
+    /// What the device is currently doing to a pad
+    pub fn pin_state(&self, fb: u8, mc: u8) -> PinState {
+        let io = self.netlist.fbs[fb as usize].io_pads[mc as usize]
+            .as_ref()
+            .unwrap();
+        // Every node was evaluated the last time the logic settled
+        drive_state(io, |id| self.value(id))
+    }
+
+    /// The value on a pad, i.e. the driven value if the device is driving it and the external
+    /// value otherwise
+    pub fn pin(&self, fb: u8, mc: u8) -> bool {
+        *self
+            .values
+            .get(&NodeId::IoPad { fb, mc })
+            .unwrap_or(&self.pads[fb as usize][mc as usize])
+    }
+
+    pub fn register(&self, fb: u8, mc: u8) -> bool {
+        self.regs[fb as usize][mc as usize]
+    }
+
+    /// The current value of any node. Global tristate nodes are true when outputs are enabled.
+    pub fn value(&self, id: NodeId) -> bool {
+        *self.values.get(&id).unwrap_or(&false)
+    }
+
+    /// Propagate the current inputs, clocking every register that sees an active edge
+    pub fn step(&mut self) -> Result<(), SimError> {
+        self.step_divider();
+        self.settle()?;
+
+        // All registers sample at the same time
+        let mut next = self.regs.clone();
+        for (fb, fb_nodes) in self.netlist.fbs.iter().enumerate() {
+            for (mc, reg) in fb_nodes.registers.iter().enumerate() {
+                let clk = self.value(reg.clk) ^ reg.clk_inv;
+                let prev_clk = self.prev_clk[fb][mc];
+                let edge = if reg.is_ddr {
+                    clk != prev_clk
+                } else {
+                    clk && !prev_clk
+                };
+                if !edge {
+                    continue;
+                }
+                let d = self.value(reg.d);
+                match reg.mode {
+                    FlipFlopMode::DFF => next[fb][mc] = d,
+                    FlipFlopMode::TFF => next[fb][mc] ^= d,
+                    FlipFlopMode::DFFCE => {
+                        if reg.ce.is_some_and(|ce| self.value(ce)) {
+                            next[fb][mc] = d;
+                        }
+                    }
+                    // Handled while settling
+                    FlipFlopMode::LATCH => {}
+                }
+            }
+        }
+        self.regs = next;
+        self.latch_clocks();
+        self.settle()
+    }
+
+    /// Pulse a global clock high and then low again
+    pub fn clock(&mut self, gck_idx: u8) -> Result<(), SimError> {
+        self.set_gck(gck_idx, true);
+        self.step()?;
+        self.set_gck(gck_idx, false);
+        self.step()
+    }
+
+    fn step_divider(&mut self) {
+        let Some(div) = self.netlist.gck[2].divider else {
+            return;
+        };
+        let pin = self.gck[2];
+        if pin && !self.divider.prev_pin {
+            self.divider.count += 1;
+            // With the delay bit set, the first output edge comes one full period later
+            let half = div.ratio.divisor() / 2;
+            let first = if div.delay { half * 2 } else { half };
+            if self.divider.count >= first {
+                self.divider.out = !self.divider.out;
+                self.divider.count = first - half;
+            }
+        }
+        self.divider.prev_pin = pin;
+    }
+
+    fn latch_clocks(&mut self) {
+        for (fb, fb_nodes) in self.netlist.fbs.iter().enumerate() {
+            for (mc, reg) in fb_nodes.registers.iter().enumerate() {
+                self.prev_clk[fb][mc] = self.value(reg.clk) ^ reg.clk_inv;
+            }
+        }
+    }
+
+    /// Evaluate combinational logic, transparent latches and asynchronous set/reset until
+    /// nothing changes
+    fn settle(&mut self) -> Result<(), SimError> {
+        for _ in 0..MAX_SETTLE_ITERATIONS {
+            let mut e = Evaluator {
+                sim: self,
+                memo: BTreeMap::new(),
+                in_progress: BTreeSet::new(),
+            };
+            for id in self.netlist.node_ids() {
+                e.eval(id);
+            }
+            let values = e.memo;
+
+            let mut regs = self.regs.clone();
+            for (fb, fb_nodes) in self.netlist.fbs.iter().enumerate() {
+                for (mc, reg) in fb_nodes.registers.iter().enumerate() {
+                    if reg.mode == FlipFlopMode::LATCH && (values[&reg.clk] ^ reg.clk_inv) {
+                        regs[fb][mc] = values[&reg.d];
+                    }
+                    // Reset wins if both are asserted
+                    if reg.reset.is_some_and(|x| values[&x]) {
+                        regs[fb][mc] = false;
+                    } else if reg.set.is_some_and(|x| values[&x]) {
+                        regs[fb][mc] = true;
+                    }
+                }
+            }
+
+            let stable = regs == self.regs && values == self.values;
+            self.regs = regs;
+            self.values = values;
+            if stable {
+                return Ok(());
+            }
+        }
+        Err(SimError::Oscillation)
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Create a simulator for this bitstream. See [Simulator].
+    pub fn simulator(&self) -> Result<Simulator, SimError> {
+        Simulator::new(self.netlist())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fb,
+        global_bits_code::ClockDivRatio,
+        io::{IoFeedbackSource, OutputMode, PinOutputSrc},
+        mc::{RegClkSrc, RegResetSrc},
+        partdb::{XC2Device, XC2Part},
+        zia::{ZIARow, ZIATableEntry},
+    };
+    use bitvec::boxed::BitBox;
+
+    /// FB0 MC3 registers pad FB0 IO5 on GCK0 and drives the result onto pad FB0 IO3.
+    /// Pad FB0 IO6 is also routed into the ZIA, and its row is returned.
+    fn register(mode: FlipFlopMode) -> (Coolrunner2<BitBox>, u8) {
+        let device = XC2Device::XC2C32A;
+        let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        let row_a = device.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        let row_b = *device
+            .zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 6 })
+            .iter()
+            .find(|x| x.row != row_a.row)
+            .unwrap();
+        for (mc, x) in [(5, row_a), (6, row_b)] {
+            bitstream.set_prop(&bitstream.fb(0).io(mc).fb_src(), IoFeedbackSource::IO);
+            bitstream.set_prop(&bitstream.fb(0).io(mc).output_pad_mode(), OutputMode::Disabled);
+            bitstream.set_prop(&bitstream.fb(0).zia_row(x.row), ZIARow::MuxChoice(x.choice));
+        }
+
+        let mc = bitstream.fb(0).mc(3);
+        let io = bitstream.fb(0).io(3);
+        bitstream.set_prop(&bitstream.fb(0).and_term(0).inp(row_a.row), true);
+        bitstream.set_prop(&bitstream.fb(0).or_term(3).inp(0), true);
+        bitstream.set_prop(&mc.use_iob(), false);
+        bitstream.set_prop(&mc.ff_mode(), mode);
+        bitstream.set_prop(&mc.clk_src(), RegClkSrc::GCK0);
+        bitstream.set_prop(&mc.init_state(), false);
+        bitstream.set_prop(&bitstream.gck_enabled(0), true);
+        bitstream.set_prop(&io.output_src(), PinOutputSrc::FlipFlop);
+        bitstream.set_prop(&io.output_pad_mode(), OutputMode::PushPull);
+        (bitstream, row_b.row)
+    }
+
+    #[test]
+    fn toggle_flip_flop() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let mc = bitstream.fb(0).mc(3);
+        let io = bitstream.fb(0).io(3);
+        // An AND term with no inputs is constant one, so T is always 1
+        bitstream.set_prop(&bitstream.fb(0).or_term(3).inp(20), true);
+        bitstream.set_prop(&mc.use_iob(), false);
+        bitstream.set_prop(&mc.ff_mode(), FlipFlopMode::TFF);
+        bitstream.set_prop(&mc.clk_src(), RegClkSrc::GCK0);
+        bitstream.set_prop(&mc.init_state(), false);
+        bitstream.set_prop(&bitstream.gck_enabled(0), true);
+        bitstream.set_prop(&io.output_src(), PinOutputSrc::FlipFlop);
+        bitstream.set_prop(&io.output_pad_mode(), OutputMode::PushPull);
+
+        let mut sim = bitstream.simulator().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        sim.clock(0).unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(true));
+        sim.clock(0).unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        assert!(!sim.pin(0, 3));
+    }
+
+    #[test]
+    fn ddr() {
+        let (mut bitstream, _) = register(FlipFlopMode::DFF);
+        bitstream.set_prop(&bitstream.fb(0).mc(3).is_ddr(), true);
+
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.set_gck(0, true);
+        sim.step().unwrap();
+        assert!(sim.register(0, 3));
+        // The falling edge samples as well
+        sim.set_pin(0, 5, false);
+        sim.set_gck(0, false);
+        sim.step().unwrap();
+        assert!(!sim.register(0, 3));
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+    }
+
+    #[test]
+    fn inverted_clock() {
+        let (mut bitstream, _) = register(FlipFlopMode::DFF);
+        bitstream.set_prop(&bitstream.fb(0).mc(3).clk_inv(), true);
+
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.set_gck(0, true);
+        sim.step().unwrap();
+        assert!(!sim.register(0, 3));
+        sim.set_gck(0, false);
+        sim.step().unwrap();
+        assert!(sim.register(0, 3));
+    }
+
+    #[test]
+    fn latch() {
+        let (bitstream, _) = register(FlipFlopMode::LATCH);
+
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        // Transparent while the clock is high
+        sim.set_gck(0, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(true));
+        sim.set_pin(0, 5, false);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        // Holds while the clock is low
+        sim.set_gck(0, false);
+        sim.step().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+    }
+
+    #[test]
+    fn clock_enable() {
+        let (mut bitstream, row_b) = register(FlipFlopMode::DFFCE);
+        bitstream.set_prop(&bitstream.fb(0).and_term(fb::ptc(3)).inp(row_b), true);
+
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.clock(0).unwrap();
+        assert!(!sim.register(0, 3));
+        sim.set_pin(0, 6, true);
+        sim.clock(0).unwrap();
+        assert!(sim.register(0, 3));
+    }
+
+    #[test]
+    fn global_set_reset() {
+        let (mut bitstream, _) = register(FlipFlopMode::DFF);
+        bitstream.set_prop(&bitstream.fb(0).mc(3).r_src(), RegResetSrc::GSR);
+        bitstream.set_prop(&bitstream.gsr_enabled(), true);
+
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.clock(0).unwrap();
+        assert!(sim.register(0, 3));
+        sim.set_gsr(true);
+        sim.step().unwrap();
+        assert!(!sim.register(0, 3));
+        // Reset overrides the clock
+        sim.clock(0).unwrap();
+        assert!(!sim.register(0, 3));
+        sim.set_gsr(false);
+        sim.clock(0).unwrap();
+        assert!(sim.register(0, 3));
+
+        // With the inversion, the reset is asserted while the pin is low
+        bitstream.set_prop(&bitstream.gsr_invert(), true);
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_pin(0, 5, true);
+        sim.clock(0).unwrap();
+        assert!(!sim.register(0, 3));
+        sim.set_gsr(true);
+        sim.clock(0).unwrap();
+        assert!(sim.register(0, 3));
+    }
+
+    #[test]
+    fn global_tristate() {
+        let (mut bitstream, _) = register(FlipFlopMode::DFF);
+        let io = bitstream.fb(0).io(3);
+        bitstream.set_prop(&io.output_pad_mode(), OutputMode::TriStateGTS0);
+        bitstream.set_prop(&bitstream.gts_enabled(0), true);
+        bitstream.set_prop(&bitstream.gts_invert(0), false);
+
+        let mut sim = bitstream.simulator().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+        sim.set_gts(0, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::HighZ);
+
+        bitstream.set_prop(&bitstream.gts_enabled(0), false);
+        let mut sim = bitstream.simulator().unwrap();
+        sim.set_gts(0, true);
+        sim.step().unwrap();
+        assert_eq!(sim.pin_state(0, 3), PinState::Driven(false));
+    }
+
+    #[test]
+    fn clock_divider() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C128, None, None).unwrap());
+        bitstream.set_prop(&bitstream.gck_enabled(2), true);
+        bitstream.set_prop(&bitstream.clock_divider().enabled(), true);
+        bitstream.set_prop(&bitstream.clock_divider().ratio(), ClockDivRatio::_4);
+        bitstream.set_prop(&bitstream.clock_divider().delay(), false);
+
+        let mut sim = bitstream.simulator().unwrap();
+        let mut out = Vec::new();
+        for _ in 0..8 {
+            sim.clock(2).unwrap();
+            out.push(sim.value(NodeId::GlobalClock(2)));
+        }
+        assert_eq!(out, [false, true, true, false, false, true, true, false]);
+
+        // The delay holds off the first edge by another period
+        bitstream.set_prop(&bitstream.clock_divider().delay(), true);
+        let mut sim = bitstream.simulator().unwrap();
+        let mut out = Vec::new();
+        for _ in 0..8 {
+            sim.clock(2).unwrap();
+            out.push(sim.value(NodeId::GlobalClock(2)));
+        }
+        assert_eq!(out, [false, false, false, true, true, false, false, true]);
+    }
+}
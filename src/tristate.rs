@@ -0,0 +1,348 @@
+//! Output enable and tristate analysis for each pin
+//!
+//! Each pad's [OutputMode] is decoded into what actually turns its output driver on, including
+//! the state of the global tristate nets. Given a list of which pads are wired together on the
+//! board, pads that can drive different values onto the same net at the same time are found
+//! using the same BDD evaluation as [check_equivalence](crate::equivalence::check_equivalence).
+//! Pad inputs and register states are treated as free, so a reported conflict may be prevented
+//! by the design in ways that cannot be seen from the logic alone.
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{
+    bdd::{Bdd, BddRef},
+    bitstream::{BitHolder, Coolrunner2},
+    equations::{EquationBuilder, EquationOptions, Expr},
+    equivalence::{EquivalenceError, Evaluator, Signal, Vars},
+    fb,
+    io::OutputMode,
+    netlist::{Netlist, NodeId, OutputEnable},
+    MCS_PER_FB,
+};
+
+/// What turns the output driver of a pad on
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum PinEnable {
+    /// The output driver is never on
+    Off,
+    /// The output driver is always on
+    Always,
+    /// The output driver is on while the output data is 0
+    OpenDrain,
+    /// One of the global tristate nets. `enabled` and `invert` are the
+    /// `gts_enabled` and `gts_invert` settings of that net.
+    GlobalTristate { index: u8, enabled: bool, invert: bool },
+    /// Product term B of the macrocell
+    ProductTerm { fb: u8, mc: u8 },
+    /// The function block's CTE
+    ControlTerm { fb: u8 },
+    /// Always driving 0, as a "virtual ground"
+    Ground,
+    /// The output mode bits do not correspond to any known mode
+    Invalid,
+}
+impl Display for PinEnable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinEnable::Off => write!(f, "off"),
+            PinEnable::Always => write!(f, "always on"),
+            PinEnable::OpenDrain => write!(f, "open drain"),
+            PinEnable::GlobalTristate {
+                index,
+                enabled,
+                invert,
+            } => {
+                write!(f, "GTS{index}")?;
+                if !*enabled {
+                    write!(f, " (not enabled, so always on)")
+                } else if *invert {
+                    write!(f, " (on while the pin is high)")
+                } else {
+                    write!(f, " (on while the pin is low)")
+                }
+            }
+            PinEnable::ProductTerm { fb, mc } => write!(f, "FB{fb} MC{mc} PTB"),
+            PinEnable::ControlTerm { fb } => write!(f, "FB{fb} CTE"),
+            PinEnable::Ground => write!(f, "CGND"),
+            PinEnable::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// The decoded output enable of one pad
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PinOutputEnable {
+    pub fb: u8,
+    pub mc: u8,
+    pub mode: OutputMode,
+    pub enable: PinEnable,
+    /// When the output driver is on, or `None` if the mode is invalid
+    pub equation: Option<Expr>,
+}
+
+/// A way in which outputs can end up fighting each other
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ContentionRisk {
+    /// Two pads on the same board net can drive different values at the same time.
+    /// The assignment is one set of inputs under which this happens.
+    SharedNet {
+        net: String,
+        a: (u8, u8),
+        b: (u8, u8),
+        assignment: Vec<(Signal, bool)>,
+    },
+    /// A pad on a board net could not be analysed
+    Undetermined {
+        net: String,
+        pad: (u8, u8),
+        error: EquivalenceError,
+    },
+    /// Pads are tristated by a GTS net that is not enabled, so they are always driving
+    GlobalTristateNeverEnabled { index: u8, pads: Vec<(u8, u8)> },
+}
+impl Display for ContentionRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentionRisk::SharedNet {
+                net,
+                a,
+                b,
+                assignment,
+            } => {
+                write!(
+                    f,
+                    "FB{} IO{} and FB{} IO{} can drive net {} at the same time",
+                    a.0, a.1, b.0, b.1, net
+                )?;
+                if !assignment.is_empty() {
+                    write!(f, " when")?;
+                }
+                for (i, (sig, val)) in assignment.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}={}", sig, *val as u8)?;
+                }
+                Ok(())
+            }
+            ContentionRisk::Undetermined { net, pad, error } => {
+                write!(f, "cannot check FB{} IO{} on net {}: {}", pad.0, pad.1, net, error)
+            }
+            ContentionRisk::GlobalTristateNeverEnabled { index, pads } => {
+                write!(f, "GTS{index} is not enabled but tristates")?;
+                for (fb, mc) in pads {
+                    write!(f, " FB{fb} IO{mc}")?;
+                }
+                write!(f, ", which are therefore always driving")
+            }
+        }
+    }
+}
+
+impl Netlist {
+    /// Decode the output enable of every pad
+    pub fn pin_output_enables(&self) -> Vec<PinOutputEnable> {
+        let options = EquationOptions::default();
+        let b = EquationBuilder::new(self, &options);
+        let mut ret = Vec::new();
+        for fb in 0..self.fbs.len() as u8 {
+            for mc in 0..MCS_PER_FB as u8 {
+                let Some(io) = &self.fbs[fb as usize].io_pads[mc as usize] else {
+                    continue;
+                };
+                let enable = match io.oe {
+                    OutputEnable::Disabled => PinEnable::Off,
+                    OutputEnable::Always => PinEnable::Always,
+                    OutputEnable::OpenDrain => PinEnable::OpenDrain,
+                    OutputEnable::Node(NodeId::GlobalTristate(index)) => {
+                        let gts = &self.gts[index as usize];
+                        PinEnable::GlobalTristate {
+                            index,
+                            enabled: gts.enabled,
+                            invert: gts.invert,
+                        }
+                    }
+                    OutputEnable::Node(NodeId::AndTerm { pterm, .. }) if pterm == fb::CTE => {
+                        PinEnable::ControlTerm { fb }
+                    }
+                    OutputEnable::Node(_) => PinEnable::ProductTerm { fb, mc },
+                    OutputEnable::Ground => PinEnable::Ground,
+                    OutputEnable::Invalid => PinEnable::Invalid,
+                };
+                let equation = match (enable, io.oe) {
+                    (PinEnable::Off, _) => Some(Expr::Const(false)),
+                    (PinEnable::Always | PinEnable::Ground, _) => Some(Expr::Const(true)),
+                    (PinEnable::OpenDrain, _) => {
+                        Some(Expr::Not(b.signal(io.output_data).into()))
+                    }
+                    (PinEnable::GlobalTristate { enabled: false, .. }, _) => {
+                        Some(Expr::Const(true))
+                    }
+                    (PinEnable::GlobalTristate { index, invert, .. }, _) => {
+                        // Outputs are enabled when the (possibly inverted) pin is low
+                        let pin = Expr::Var(format!("GTS{index}_PIN"));
+                        Some(if invert { pin } else { Expr::Not(pin.into()) })
+                    }
+                    (_, OutputEnable::Node(oe)) => Some(b.signal(oe)),
+                    _ => None,
+                };
+                ret.push(PinOutputEnable {
+                    fb,
+                    mc,
+                    mode: io.output_mode,
+                    enable,
+                    equation,
+                });
+            }
+        }
+        ret
+    }
+
+    /// Find outputs that can fight each other.
+    ///
+    /// `board_nets` maps the name of each net on the board to the pads, as (fb, mc), that are
+    /// connected to it. Pads that are not listed are assumed to be on nets of their own.
+    pub fn contention_risks(
+        &self,
+        board_nets: &BTreeMap<String, Vec<(u8, u8)>>,
+    ) -> Vec<ContentionRisk> {
+        let mut ret = Vec::new();
+
+        let mut gts_pads: BTreeMap<u8, Vec<(u8, u8)>> = BTreeMap::new();
+        for x in self.pin_output_enables() {
+            if let PinEnable::GlobalTristate {
+                index,
+                enabled: false,
+                ..
+            } = x.enable
+            {
+                gts_pads.entry(index).or_default().push((x.fb, x.mc));
+            }
+        }
+        for (index, pads) in gts_pads {
+            ret.push(ContentionRisk::GlobalTristateNeverEnabled { index, pads });
+        }
+
+        let mut bdd = Bdd::new();
+        let mut vars = Vars::new();
+        let mut eval = Evaluator::new(self);
+        for (net, pads) in board_nets {
+            // (pad, output enable, output value)
+            let mut drivers: Vec<((u8, u8), BddRef, BddRef)> = Vec::new();
+            for &(fb, mc) in pads {
+                if !self.device.has_io_at(fb, mc) {
+                    continue;
+                }
+                match eval.pad(&mut bdd, &mut vars, fb, mc) {
+                    Ok((oe, val)) => drivers.push(((fb, mc), oe, val)),
+                    Err(error) => ret.push(ContentionRisk::Undetermined {
+                        net: net.clone(),
+                        pad: (fb, mc),
+                        error,
+                    }),
+                }
+            }
+
+            for i in 0..drivers.len() {
+                for j in i + 1..drivers.len() {
+                    let (a, oe_a, val_a) = drivers[i];
+                    let (b, oe_b, val_b) = drivers[j];
+                    let both = bdd.and(oe_a, oe_b);
+                    let differ = bdd.xor(val_a, val_b);
+                    let conflict = bdd.and(both, differ);
+                    if let Some(sat) = bdd.sat_one(conflict) {
+                        ret.push(ContentionRisk::SharedNet {
+                            net: net.clone(),
+                            a,
+                            b,
+                            assignment: sat
+                                .iter()
+                                .map(|&(var, val)| (vars.list[var as usize], val))
+                                .collect(),
+                        });
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// See [Netlist::pin_output_enables]
+    pub fn pin_output_enables(&self) -> Vec<PinOutputEnable> {
+        self.netlist().pin_output_enables()
+    }
+
+    /// See [Netlist::contention_risks]
+    pub fn contention_risks(
+        &self,
+        board_nets: &BTreeMap<String, Vec<(u8, u8)>>,
+    ) -> Vec<ContentionRisk> {
+        self.netlist().contention_risks(board_nets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::PinOutputSrc,
+        mc::XorMode,
+        partdb::{XC2Device, XC2Part},
+    };
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn shared_net() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        // FB0 IO0 outputs 1 and FB0 IO1 outputs 0
+        for (mc, mode) in [(0, XorMode::_1), (1, XorMode::_0)] {
+            bitstream.set_prop(&bitstream.fb(0).mc(mc).xor_mode(), mode);
+            bitstream.set_prop(&bitstream.fb(0).io(mc).output_src(), PinOutputSrc::XorGate);
+            bitstream.set_prop(
+                &bitstream.fb(0).io(mc).output_pad_mode(),
+                OutputMode::OpenDrain,
+            );
+        }
+        let nets = BTreeMap::from([("bus".to_string(), vec![(0, 0), (0, 1)])]);
+
+        // Open drain outputs never fight
+        assert_eq!(bitstream.contention_risks(&nets), []);
+
+        bitstream.set_prop(
+            &bitstream.fb(0).io(0).output_pad_mode(),
+            OutputMode::PushPull,
+        );
+        let risks = bitstream.contention_risks(&nets);
+        assert_eq!(risks.len(), 1);
+        assert_eq!(
+            risks[0].to_string(),
+            "FB0 IO0 and FB0 IO1 can drive net bus at the same time"
+        );
+
+        // Tristated by GTS0, which is not enabled
+        bitstream.set_prop(&bitstream.gts_enabled(0), false);
+        bitstream.set_prop(
+            &bitstream.fb(0).io(0).output_pad_mode(),
+            OutputMode::TriStateGTS0,
+        );
+        let oe = &bitstream.pin_output_enables()[0];
+        assert_eq!(oe.equation, Some(Expr::Const(true)));
+        assert_eq!(
+            bitstream.contention_risks(&BTreeMap::new()),
+            [ContentionRisk::GlobalTristateNeverEnabled {
+                index: 0,
+                pads: vec![(0, 0)]
+            }]
+        );
+    }
+}
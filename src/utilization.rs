@@ -0,0 +1,341 @@
+//! Resource utilization summary
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    io::IoFeedbackSource,
+    netlist::{Netlist, NodeId, NodeRef, OutputEnable, ZIAInput},
+    MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS,
+};
+
+/// Resources used in one function block. Each mask has one bit per resource.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct FunctionBlockUtilization {
+    pub product_terms: u64,
+    pub macrocells: u16,
+    pub zia_rows: u64,
+    pub io_pads: u16,
+}
+impl FunctionBlockUtilization {
+    pub fn num_product_terms(&self) -> usize {
+        self.product_terms.count_ones() as usize
+    }
+    pub fn num_macrocells(&self) -> usize {
+        self.macrocells.count_ones() as usize
+    }
+    pub fn num_zia_rows(&self) -> usize {
+        self.zia_rows.count_ones() as usize
+    }
+    pub fn num_io_pads(&self) -> usize {
+        self.io_pads.count_ones() as usize
+    }
+
+    pub fn product_term_used(&self, pterm: u8) -> bool {
+        self.product_terms & (1 << pterm) != 0
+    }
+    pub fn macrocell_used(&self, mc: u8) -> bool {
+        self.macrocells & (1 << mc) != 0
+    }
+    pub fn zia_row_used(&self, row: u8) -> bool {
+        self.zia_rows & (1 << row) != 0
+    }
+    pub fn io_pad_used(&self, mc: u8) -> bool {
+        self.io_pads & (1 << mc) != 0
+    }
+}
+
+/// Usage of one global net
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct GlobalUsage {
+    pub enabled: bool,
+    /// Number of used registers or pads connected to this net
+    pub users: usize,
+}
+
+/// Resources used by a whole device.
+///
+/// The bank of each pad is not part of the bitstream, so on devices with more than one IO bank
+/// `io_pads_by_bank` is only filled in when the caller supplies a bank for every used pad, e.g.
+/// through [Coolrunner2::utilization_with_banks]. Otherwise it is `None`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Utilization {
+    pub fbs: Vec<FunctionBlockUtilization>,
+    pub gck: [GlobalUsage; NUM_BUFG_CLK],
+    pub gts: [GlobalUsage; NUM_BUFG_GTS],
+    pub gsr: GlobalUsage,
+    pub clock_divider: bool,
+    /// DataGate is enabled, and `users` pads are gated by it
+    pub data_gate: GlobalUsage,
+    /// Number of used pads in each IO bank, if the bank of every used pad is known
+    pub io_pads_by_bank: Option<Vec<usize>>,
+}
+impl Utilization {
+    pub fn num_product_terms(&self) -> usize {
+        self.fbs.iter().map(|x| x.num_product_terms()).sum()
+    }
+    pub fn num_macrocells(&self) -> usize {
+        self.fbs.iter().map(|x| x.num_macrocells()).sum()
+    }
+    pub fn num_zia_rows(&self) -> usize {
+        self.fbs.iter().map(|x| x.num_zia_rows()).sum()
+    }
+    pub fn num_io_pads(&self) -> usize {
+        self.fbs.iter().map(|x| x.num_io_pads()).sum()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UtilizationError {
+    /// A pad was given an IO bank that does not exist on the device
+    InvalidBank { fb: u8, mc: u8, bank: u8 },
+}
+impl Display for UtilizationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtilizationError::InvalidBank { fb, mc, bank } => {
+                write!(f, "FB{} IO{} is assigned to bank {}, which does not exist", fb, mc, bank)
+            }
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for UtilizationError {}
+
+impl Netlist {
+    /// Compute which resources are used, i.e. can affect a pad or a register.
+    ///
+    /// `pad_bank` maps `(fb, mc)` of each pad to its IO bank. It is only needed on devices
+    /// with more than one bank. DataGate is not part of the netlist and is left unused here.
+    pub fn utilization(
+        &self,
+        pad_bank: &BTreeMap<(u8, u8), u8>,
+    ) -> Result<Utilization, UtilizationError> {
+        for (&(fb, mc), &bank) in pad_bank {
+            if bank as usize >= self.device.num_io_banks() {
+                return Err(UtilizationError::InvalidBank { fb, mc, bank });
+            }
+        }
+
+        let mut fbs = vec![FunctionBlockUtilization::default(); self.fbs.len()];
+        let mut gck = [GlobalUsage::default(); NUM_BUFG_CLK];
+        let mut gts = [GlobalUsage::default(); NUM_BUFG_GTS];
+        let mut gsr = GlobalUsage::default();
+        for i in 0..NUM_BUFG_CLK {
+            gck[i].enabled = self.gck[i].enabled;
+        }
+        for i in 0..NUM_BUFG_GTS {
+            gts[i].enabled = self.gts[i].enabled;
+        }
+        gsr.enabled = self.gsr.enabled;
+
+        // Start from every used macrocell and driving pad and walk backwards,
+        // stopping at the ZIA (whatever is on the other side is already accounted for)
+        let mut stack = Vec::new();
+        for (fb, fb_nodes) in self.fbs.iter().enumerate() {
+            let fb = fb as u8;
+            for mc in 0..MCS_PER_FB as u8 {
+                let comb_used = self.comb_is_used(fb, mc);
+                let reg_used = self.reg_is_used(fb, mc);
+                if comb_used || reg_used {
+                    fbs[fb as usize].macrocells |= 1 << mc;
+                }
+                if comb_used {
+                    stack.push(NodeId::Xor { fb, mc });
+                }
+                if reg_used {
+                    stack.push(NodeId::Register { fb, mc });
+                    let reg = &fb_nodes.registers[mc as usize];
+                    if let NodeId::GlobalClock(i) = reg.clk {
+                        gck[i as usize].users += 1;
+                    }
+                    if reg.set == Some(NodeId::GlobalSetReset)
+                        || reg.reset == Some(NodeId::GlobalSetReset)
+                    {
+                        gsr.users += 1;
+                    }
+                }
+
+                if let Some(io) = &fb_nodes.io_pads[mc as usize] {
+                    if io.oe != OutputEnable::Disabled {
+                        fbs[fb as usize].io_pads |= 1 << mc;
+                        stack.push(NodeId::IoPad { fb, mc });
+                    }
+                    if let OutputEnable::Node(NodeId::GlobalTristate(i)) = io.oe {
+                        gts[i as usize].users += 1;
+                    }
+                }
+            }
+        }
+
+        let mut visited = BTreeSet::new();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            match id {
+                NodeId::AndTerm { fb, pterm } => fbs[fb as usize].product_terms |= 1 << pterm,
+                NodeId::ZIARow { fb, row } => {
+                    fbs[fb as usize].zia_rows |= 1 << row;
+                    // The other side of the ZIA is only used if it's a pad input
+                    if let Some(NodeRef::ZIARow(x)) = self.node(id) {
+                        if let ZIAInput::Node(NodeId::IoFeedback { fb, mc }) = x.input {
+                            let io_fb = self.fbs[fb as usize].io_feedback[mc as usize].as_ref();
+                            if io_fb.is_some_and(|x| x.setting == IoFeedbackSource::IO) {
+                                fbs[fb as usize].io_pads |= 1 << mc;
+                            }
+                        }
+                    }
+                    continue;
+                }
+                NodeId::IoPad { fb, mc } => fbs[fb as usize].io_pads |= 1 << mc,
+                _ => {}
+            }
+            stack.extend(self.fanin(id).into_iter().filter(|x| {
+                matches!(
+                    x,
+                    NodeId::AndTerm { .. }
+                        | NodeId::OrTerm { .. }
+                        | NodeId::ZIARow { .. }
+                        | NodeId::Xor { .. }
+                        | NodeId::IoPad { .. }
+                )
+            }));
+        }
+
+        let io_pads_by_bank = if self.device.num_io_banks() == 1 {
+            Some(vec![fbs.iter().map(|x| x.num_io_pads()).sum()])
+        } else {
+            let mut banks = vec![0; self.device.num_io_banks()];
+            let mut all_known = true;
+            for (fb, x) in fbs.iter().enumerate() {
+                for mc in 0..MCS_PER_FB as u8 {
+                    if x.io_pad_used(mc) {
+                        match pad_bank.get(&(fb as u8, mc)) {
+                            Some(&bank) => banks[bank as usize] += 1,
+                            None => all_known = false,
+                        }
+                    }
+                }
+            }
+            if all_known {
+                Some(banks)
+            } else {
+                None
+            }
+        };
+
+        Ok(Utilization {
+            fbs,
+            gck,
+            gts,
+            gsr,
+            clock_divider: self.gck[2].divider.is_some(),
+            data_gate: GlobalUsage::default(),
+            io_pads_by_bank,
+        })
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// Summarize the resources used by this bitstream.
+    ///
+    /// Pads are only counted per bank on devices with a single IO bank.
+    /// Use [Coolrunner2::utilization_with_banks] to supply the bank of each pad.
+    pub fn utilization(&self) -> Utilization {
+        // With no banks given, none of them can be invalid
+        self.utilization_with_banks(&BTreeMap::new()).unwrap()
+    }
+
+    /// Summarize the resources used by this bitstream, given the IO bank of each `(fb, mc)` pad
+    pub fn utilization_with_banks(
+        &self,
+        pad_bank: &BTreeMap<(u8, u8), u8>,
+    ) -> Result<Utilization, UtilizationError> {
+        let mut ret = self.netlist().utilization(pad_bank)?;
+        if self.part.device.has_large_macrocells() {
+            ret.data_gate.enabled = self.get_prop(&self.data_gate_enabled());
+            for (fb, x) in ret.fbs.iter().enumerate() {
+                for mc in 0..MCS_PER_FB as u8 {
+                    if x.io_pad_used(mc)
+                        && self.part.device.has_io_at(fb as u8, mc)
+                        && self.get_prop(&self.fb(fb as u8).io(mc).use_data_gate())
+                    {
+                        ret.data_gate.users += 1;
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::{OutputMode, PinOutputSrc},
+        mc::XorMode,
+        partdb::{XC2Device, XC2Part},
+        zia::{ZIARow, ZIATableEntry},
+    };
+    use bitvec::boxed::BitBox;
+
+    /// Two input pads, FB0 MC5 and MC6, ANDed onto the pad at FB0 MC3
+    fn and_gate(device: XC2Device) -> Coolrunner2<BitBox> {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        let row_a = device.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        let row_b = *device
+            .zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 6 })
+            .iter()
+            .find(|x| x.row != row_a.row)
+            .unwrap();
+        let fb = bitstream.fb(0);
+        for (mc, x) in [(5, row_a), (6, row_b)] {
+            bitstream.set_prop(&fb.io(mc).fb_src(), IoFeedbackSource::IO);
+            bitstream.set_prop(&fb.zia_row(x.row), ZIARow::MuxChoice(x.choice));
+            bitstream.set_prop(&fb.and_term(0).inp(x.row), true);
+        }
+        bitstream.set_prop(&fb.or_term(3).inp(0), true);
+        bitstream.set_prop(&fb.mc(3).xor_mode(), XorMode::_0);
+        bitstream.set_prop(&fb.io(3).output_src(), PinOutputSrc::XorGate);
+        bitstream.set_prop(&fb.io(3).output_pad_mode(), OutputMode::PushPull);
+        bitstream
+    }
+
+    #[test]
+    fn single_bank() {
+        let x = and_gate(XC2Device::XC2C32).utilization();
+        assert_eq!(x.num_macrocells(), 1);
+        assert_eq!(x.num_product_terms(), 1);
+        assert_eq!(x.num_io_pads(), 3);
+        assert_eq!(x.io_pads_by_bank, Some(vec![3]));
+    }
+
+    #[test]
+    fn multi_bank() {
+        let bitstream = and_gate(XC2Device::XC2C32A);
+        assert_eq!(bitstream.utilization().io_pads_by_bank, None);
+
+        let mut pad_bank = BTreeMap::from([((0, 3), 0), ((0, 5), 1)]);
+        let x = bitstream.utilization_with_banks(&pad_bank).unwrap();
+        assert_eq!(x.io_pads_by_bank, None);
+        pad_bank.insert((0, 6), 1);
+        let x = bitstream.utilization_with_banks(&pad_bank).unwrap();
+        assert_eq!(x.io_pads_by_bank, Some(vec![1, 2]));
+
+        pad_bank.insert((0, 6), 2);
+        assert_eq!(
+            bitstream.utilization_with_banks(&pad_bank),
+            Err(UtilizationError::InvalidBank { fb: 0, mc: 6, bank: 2 })
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! VCD waveform output from the simulator
+//!
+//! Signals are named after the string form of [ZIATableEntry](crate::zia::ZIATableEntry),
+//! without the space since VCD names cannot contain whitespace. For example the register in
+//! macrocell 3 of function block 1 is `Macrocell(FB1,MC3)` and its XOR gate output is
+//! `Macrocell(FB1,MC3).xor`. Each pad has the value on the pad, `InputPin(FB1,MC3)`, and what the
+//! device is driving onto it, `OutputDriver(FB1,MC3)`, which is `z` while the output is off.
+
+use core::fmt::{self, Write};
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{
+    netlist::NodeId,
+    sim::{PinState, Simulator},
+    MCS_PER_FB, NUM_BUFG_CLK, NUM_BUFG_GTS, ZIA_ROWS,
+};
+
+#[derive(Copy, Clone, Debug)]
+enum Probe {
+    Node(NodeId),
+    PinOutput { fb: u8, mc: u8 },
+}
+
+struct Var {
+    probe: Probe,
+    code: String,
+    last: Option<char>,
+}
+
+/// Records the state of a [Simulator] as a VCD file
+pub struct VcdWriter<W: Write> {
+    w: W,
+    vars: Vec<Var>,
+    time: Option<u64>,
+}
+
+/// Short identifier made of printable characters
+fn id_code(mut i: usize) -> String {
+    let mut ret = String::new();
+    loop {
+        ret.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return ret;
+        }
+        i -= 1;
+    }
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Write the header, declaring every signal of the simulated device.
+    /// `timescale` is written as-is, e.g. `"1 ns"`.
+    pub fn new(mut w: W, sim: &Simulator, timescale: &str) -> Result<Self, fmt::Error> {
+        let netlist = sim.netlist();
+        let mut vars = Vec::new();
+        let mut declare = |w: &mut W, probe: Probe, name: &str| -> fmt::Result {
+            let code = id_code(vars.len());
+            writeln!(w, "$var wire 1 {} {} $end", code, name)?;
+            vars.push(Var {
+                probe,
+                code,
+                last: None,
+            });
+            Ok(())
+        };
+
+        writeln!(w, "$timescale {} $end", timescale)?;
+        writeln!(w, "$scope module {} $end", netlist.device)?;
+        for i in 0..NUM_BUFG_CLK as u8 {
+            declare(&mut w, Probe::Node(NodeId::GlobalClock(i)), &format!("GCK{i}"))?;
+        }
+        declare(&mut w, Probe::Node(NodeId::GlobalSetReset), "GSR")?;
+        for i in 0..NUM_BUFG_GTS as u8 {
+            declare(
+                &mut w,
+                Probe::Node(NodeId::GlobalTristate(i)),
+                &format!("GTS{i}"),
+            )?;
+        }
+        if netlist.dedicated_input.is_some() {
+            declare(
+                &mut w,
+                Probe::Node(NodeId::DedicatedInput),
+                "DedicatedInputPin",
+            )?;
+        }
+
+        for fb in 0..netlist.fbs.len() as u8 {
+            writeln!(w, "$scope module FB{} $end", fb)?;
+            for mc in 0..MCS_PER_FB as u8 {
+                if netlist.fbs[fb as usize].io_pads[mc as usize].is_some() {
+                    declare(
+                        &mut w,
+                        Probe::Node(NodeId::IoPad { fb, mc }),
+                        &format!("InputPin(FB{fb},MC{mc})"),
+                    )?;
+                    declare(
+                        &mut w,
+                        Probe::PinOutput { fb, mc },
+                        &format!("OutputDriver(FB{fb},MC{mc})"),
+                    )?;
+                }
+                declare(
+                    &mut w,
+                    Probe::Node(NodeId::Register { fb, mc }),
+                    &format!("Macrocell(FB{fb},MC{mc})"),
+                )?;
+                declare(
+                    &mut w,
+                    Probe::Node(NodeId::Xor { fb, mc }),
+                    &format!("Macrocell(FB{fb},MC{mc}).xor"),
+                )?;
+            }
+            for row in 0..ZIA_ROWS as u8 {
+                declare(
+                    &mut w,
+                    Probe::Node(NodeId::ZIARow { fb, row }),
+                    &format!("zia_row{row}"),
+                )?;
+            }
+            writeln!(w, "$upscope $end")?;
+        }
+        writeln!(w, "$upscope $end")?;
+        writeln!(w, "$enddefinitions $end")?;
+
+        Ok(Self {
+            w,
+            vars,
+            time: None,
+        })
+    }
+
+    /// Record the current state of the simulator at the given time.
+    /// Only signals that changed since the previous sample are written.
+    pub fn sample(&mut self, sim: &Simulator, time: u64) -> fmt::Result {
+        let first = self.time.is_none();
+        let mut wrote_time = false;
+        for var in &mut self.vars {
+            let val = match var.probe {
+                Probe::Node(id) => {
+                    if sim.value(id) {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                }
+                Probe::PinOutput { fb, mc } => match sim.pin_state(fb, mc) {
+                    PinState::HighZ => 'z',
+                    PinState::Driven(false) => '0',
+                    PinState::Driven(true) => '1',
+                },
+            };
+            if var.last == Some(val) {
+                continue;
+            }
+            if !wrote_time {
+                writeln!(self.w, "#{}", time)?;
+                if first {
+                    writeln!(self.w, "$dumpvars")?;
+                }
+                wrote_time = true;
+            }
+            writeln!(self.w, "{}{}", val, var.code)?;
+            var.last = Some(val);
+        }
+        if first {
+            writeln!(self.w, "$end")?;
+        }
+        self.time = Some(time);
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bitstream::Coolrunner2,
+        io::OutputMode,
+        partdb::{XC2Device, XC2Part},
+    };
+
+    #[test]
+    fn id_codes_are_unique() {
+        let codes: Vec<_> = (0..10000).map(id_code).collect();
+        let mut sorted = codes.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), codes.len());
+    }
+
+    #[test]
+    fn header_and_changes() {
+        let mut bitstream =
+            Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        bitstream.set_prop(&bitstream.fb(0).io(2).output_pad_mode(), OutputMode::Disabled);
+        let mut sim = bitstream.simulator().unwrap();
+        let mut vcd = VcdWriter::new(String::new(), &sim, "1 ns").unwrap();
+        vcd.sample(&sim, 0).unwrap();
+        sim.set_pin(0, 2, true);
+        sim.step().unwrap();
+        vcd.sample(&sim, 10).unwrap();
+        vcd.sample(&sim, 20).unwrap();
+
+        // 3 GCKs, GSR, 4 GTSs and the dedicated input come first, then each macrocell of FB0
+        // has its pad, pad driver, register and XOR gate, followed by the ZIA rows of FB0
+        let out = vcd.into_inner();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "$timescale 1 ns $end");
+        assert_eq!(lines[1], "$scope module XC2C32A $end");
+        for (i, name) in [
+            (11, "Macrocell(FB0,MC0)"),
+            (12, "Macrocell(FB0,MC0).xor"),
+            (17, "InputPin(FB0,MC2)"),
+            (18, "OutputDriver(FB0,MC2)"),
+            (73, "zia_row0"),
+        ] {
+            let var = format!("$var wire 1 {} {} $end", id_code(i), name);
+            assert!(lines.contains(&var.as_str()), "{}", var);
+        }
+        assert_eq!(id_code(17), "2");
+        assert!(lines.contains(&"$enddefinitions $end"));
+
+        // Only the pad that was driven from outside changed
+        let t10 = lines.iter().position(|&x| x == "#10").unwrap();
+        assert_eq!(lines[t10 + 1], "12");
+        assert_eq!(lines.len(), t10 + 2);
+        // Nothing changed
+        assert!(!lines.contains(&"#20"));
+    }
+}
@@ -0,0 +1,506 @@
+//! JEDEC test vectors
+//!
+//! Test vectors are stored in `V` fields, one character per package pin, with the number of
+//! pins given by the `QP` field and the meaning of `X` given by the `X` field. The characters
+//! understood here are:
+//!
+//! * `0`, `1`: drive the input low/high
+//! * `C`, `K`: pulse the input low-high-low / high-low-high
+//! * `L`, `H`, `Z`: expect the output to be low/high/off
+//! * `X`: drive the default value from the `X` field, or don't care for outputs
+//! * `N`, `F`: not tested (power pins, floating inputs)
+//!
+//! The crate does not know the pinout of any package, so a pin map has to be supplied to
+//! connect vector positions to pads and global pins.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display, Write};
+
+use alloc::vec::Vec;
+
+use crate::sim::{PinState, SimError, Simulator};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TestVector {
+    pub number: u32,
+    /// One character per package pin, starting with pin 1
+    pub pins: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TestVectors {
+    /// From the `QP` field
+    pub num_pins: Option<u32>,
+    /// From the `X` field, the value driven for `X` inputs
+    pub default_x: Option<bool>,
+    pub vectors: Vec<TestVector>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VectorParseError {
+    InvalidNumber,
+    InvalidPinCharacter(char),
+    InvalidDefault,
+}
+impl Display for VectorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorParseError::InvalidNumber => write!(f, "invalid number in vector field"),
+            VectorParseError::InvalidPinCharacter(c) => {
+                write!(f, "invalid test condition '{}' in vector", c)
+            }
+            VectorParseError::InvalidDefault => write!(f, "invalid X field"),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for VectorParseError {}
+
+fn parse_num(s: &[u8]) -> Result<u32, VectorParseError> {
+    let s = core::str::from_utf8(s).map_err(|_| VectorParseError::InvalidNumber)?;
+    s.trim()
+        .parse()
+        .map_err(|_| VectorParseError::InvalidNumber)
+}
+
+impl TestVectors {
+    /// Extract the vector related fields from an entire JED file
+    ///
+    /// The text between STX and the first `*` is either a design specification or, in a file
+    /// written with `no_design_spec(true)`, the first field. It is only used if it is a valid
+    /// `QP`, `X` or `V` field, so a design specification that happens to look exactly like one
+    /// of these is misread.
+    pub fn parse(jed: &[u8]) -> Result<Self, VectorParseError> {
+        let start = jed.iter().position(|&x| x == 0x02).map_or(0, |x| x + 1);
+        let end = jed.iter().position(|&x| x == 0x03).unwrap_or(jed.len());
+        let body = &jed[start.min(end)..end];
+
+        let mut ret = Self::default();
+        let mut fields = body.split(|&x| x == b'*');
+        if let Some(first) = fields.next() {
+            // Fields are only updated once they have been parsed successfully
+            let _ = ret.parse_field(first);
+        }
+        for field in fields {
+            ret.parse_field(field)?;
+        }
+        Ok(ret)
+    }
+
+    /// Handle one field, ignoring the ones unrelated to vectors
+    fn parse_field(&mut self, field: &[u8]) -> Result<(), VectorParseError> {
+        let field = field.trim_ascii_start();
+        match field.first() {
+            Some(b'V') => {
+                let field = &field[1..];
+                let num_end = field
+                    .iter()
+                    .position(|x| !x.is_ascii_digit())
+                    .unwrap_or(field.len());
+                let number = parse_num(&field[..num_end])?;
+                let mut pins = Vec::new();
+                for &c in &field[num_end..] {
+                    if c.is_ascii_whitespace() {
+                        continue;
+                    }
+                    let c = c.to_ascii_uppercase();
+                    if !b"01CKLHZXNFPB".contains(&c) {
+                        return Err(VectorParseError::InvalidPinCharacter(c as char));
+                    }
+                    pins.push(c);
+                }
+                self.vectors.push(TestVector { number, pins });
+            }
+            Some(b'X') => {
+                self.default_x = match field[1..].trim_ascii() {
+                    b"0" => Some(false),
+                    b"1" => Some(true),
+                    _ => return Err(VectorParseError::InvalidDefault),
+                };
+            }
+            Some(b'Q') if field.get(1) == Some(&b'P') => {
+                self.num_pins = Some(parse_num(&field[2..])?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Write the `QP`, `QV`, `X` and `V` fields
+    pub fn write_fields<W: Write>(&self, w: &mut W) -> fmt::Result {
+        if let Some(num_pins) = self.num_pins {
+            writeln!(w, "QP{}*", num_pins)?;
+        }
+        writeln!(w, "QV{}*", self.vectors.len())?;
+        if let Some(default_x) = self.default_x {
+            writeln!(w, "X{}*", default_x as u8)?;
+        }
+        for v in &self.vectors {
+            write!(w, "V{:04} ", v.number)?;
+            for &c in &v.pins {
+                w.write_char(c as char)?;
+            }
+            writeln!(w, "*")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a package pin is connected to
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PinFunction {
+    Pad { fb: u8, mc: u8 },
+    GlobalClock(u8),
+    GlobalSetReset,
+    GlobalTristate(u8),
+    DedicatedInput,
+}
+
+/// Pairs of (1-based package pin number, function).
+/// A pin may be listed more than once, e.g. for a pad that is also a global clock input.
+pub type PinMap = [(u32, PinFunction)];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct VectorMismatch {
+    pub vector: u32,
+    pub pin: u32,
+    /// One of `L`, `H` or `Z`
+    pub expected: char,
+    pub actual: PinState,
+}
+impl Display for VectorMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let actual = match self.actual {
+            PinState::HighZ => 'Z',
+            PinState::Driven(false) => 'L',
+            PinState::Driven(true) => 'H',
+        };
+        write!(
+            f,
+            "vector {} pin {}: expected {}, got {}",
+            self.vector, self.pin, self.expected, actual
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VectorError {
+    /// Preload (`P`, `B`) conditions are not supported
+    Unsupported { vector: u32, pin: u32, c: char },
+    Sim(SimError),
+}
+impl Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::Unsupported { vector, pin, c } => write!(
+                f,
+                "vector {} pin {}: test condition '{}' is not supported",
+                vector, pin, c
+            ),
+            VectorError::Sim(e) => e.fmt(f),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for VectorError {}
+impl From<SimError> for VectorError {
+    fn from(value: SimError) -> Self {
+        Self::Sim(value)
+    }
+}
+
+fn drive(sim: &mut Simulator, func: PinFunction, val: bool) {
+    match func {
+        PinFunction::Pad { fb, mc } => sim.set_pin(fb, mc, val),
+        PinFunction::GlobalClock(i) => sim.set_gck(i, val),
+        PinFunction::GlobalSetReset => sim.set_gsr(val),
+        PinFunction::GlobalTristate(i) => sim.set_gts(i, val),
+        PinFunction::DedicatedInput => sim.set_dedicated_input(val),
+    }
+}
+
+impl Simulator {
+    /// Apply every vector in order and compare the outputs afterwards.
+    ///
+    /// Pins that are not in the pin map are ignored, and expected values are only checked
+    /// on pads.
+    pub fn run_vectors(
+        &mut self,
+        vectors: &TestVectors,
+        pin_map: &PinMap,
+    ) -> Result<Vec<VectorMismatch>, VectorError> {
+        let default_x = vectors.default_x.unwrap_or(false);
+        let mut mismatches = Vec::new();
+
+        for v in &vectors.vectors {
+            let mut pulses = Vec::new();
+            for &(pin, func) in pin_map {
+                let Some(&c) = pin.checked_sub(1).and_then(|i| v.pins.get(i as usize)) else {
+                    continue;
+                };
+                match c {
+                    b'0' => drive(self, func, false),
+                    b'1' => drive(self, func, true),
+                    b'X' => drive(self, func, default_x),
+                    b'C' => {
+                        drive(self, func, false);
+                        pulses.push((func, true));
+                    }
+                    b'K' => {
+                        drive(self, func, true);
+                        pulses.push((func, false));
+                    }
+                    b'P' | b'B' => {
+                        return Err(VectorError::Unsupported {
+                            vector: v.number,
+                            pin,
+                            c: c as char,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            self.step()?;
+            if !pulses.is_empty() {
+                for &(func, val) in &pulses {
+                    drive(self, func, val);
+                }
+                self.step()?;
+                for &(func, val) in &pulses {
+                    drive(self, func, !val);
+                }
+                self.step()?;
+            }
+
+            for &(pin, func) in pin_map {
+                let PinFunction::Pad { fb, mc } = func else {
+                    continue;
+                };
+                let Some(&c) = pin.checked_sub(1).and_then(|i| v.pins.get(i as usize)) else {
+                    continue;
+                };
+                let expected = match c {
+                    b'L' => PinState::Driven(false),
+                    b'H' => PinState::Driven(true),
+                    b'Z' => PinState::HighZ,
+                    _ => continue,
+                };
+                let actual = self.pin_state(fb, mc);
+                if actual != expected {
+                    mismatches.push(VectorMismatch {
+                        vector: v.number,
+                        pin,
+                        expected: c as char,
+                        actual,
+                    });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+/// Builds test vectors from the state of a simulation
+pub struct VectorRecorder<'a> {
+    pin_map: &'a PinMap,
+    pub vectors: TestVectors,
+}
+impl<'a> VectorRecorder<'a> {
+    pub fn new(num_pins: u32, pin_map: &'a PinMap) -> Self {
+        Self {
+            pin_map,
+            vectors: TestVectors {
+                num_pins: Some(num_pins),
+                default_x: None,
+                vectors: Vec::new(),
+            },
+        }
+    }
+
+    /// Append a vector with the current inputs and outputs of the simulator.
+    /// Call this after [Simulator::step].
+    pub fn record(&mut self, sim: &Simulator) {
+        let num_pins = self.vectors.num_pins.unwrap_or(0) as usize;
+        let mut pins = Vec::new();
+        pins.resize(num_pins, b'N');
+        for &(pin, func) in self.pin_map {
+            let Some(c) = pin.checked_sub(1).and_then(|i| pins.get_mut(i as usize)) else {
+                continue;
+            };
+            let input = |val: bool| if val { b'1' } else { b'0' };
+            *c = match func {
+                PinFunction::Pad { fb, mc } => match sim.pin_state(fb, mc) {
+                    PinState::Driven(false) => b'L',
+                    PinState::Driven(true) => b'H',
+                    PinState::HighZ => input(sim.pin(fb, mc)),
+                },
+                // A pad that is also a global input is recorded as the pad
+                _ if *c != b'N' => continue,
+                PinFunction::GlobalClock(i) => input(sim.gck_pin(i)),
+                PinFunction::GlobalSetReset => input(sim.gsr_pin()),
+                PinFunction::GlobalTristate(i) => input(sim.gts_pin(i)),
+                PinFunction::DedicatedInput => input(sim.dedicated_input_pin()),
+            };
+        }
+        let number = self.vectors.vectors.len() as u32 + 1;
+        self.vectors.vectors.push(TestVector { number, pins });
+    }
+
+    pub fn into_vectors(self) -> TestVectors {
+        self.vectors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bitstream::Coolrunner2,
+        io::{IoFeedbackSource, OutputMode, PinOutputSrc},
+        mc::{FlipFlopMode, RegClkSrc},
+        partdb::{XC2Device, XC2Part},
+        zia::{ZIARow, ZIATableEntry},
+    };
+    use alloc::string::String;
+    use alloc::vec;
+    use bitvec::boxed::BitBox;
+
+    /// GCK0 on pin 1, the pad FB0 IO5 on pin 2 and the pad FB0 IO3 on pin 3. Pin 4 is unused.
+    const PINS: &PinMap = &[
+        (1, PinFunction::GlobalClock(0)),
+        (2, PinFunction::Pad { fb: 0, mc: 5 }),
+        (3, PinFunction::Pad { fb: 0, mc: 3 }),
+    ];
+
+    /// FB0 MC3 registers pad FB0 IO5 on GCK0 and drives the result onto pad FB0 IO3
+    fn flip_flop() -> Coolrunner2<BitBox> {
+        let device = XC2Device::XC2C32A;
+        let mut bitstream = Coolrunner2::new(XC2Part::new(device, None, None).unwrap());
+        let zia = device.zia_rows_for(ZIATableEntry::InputPin { fb: 0, mc: 5 })[0];
+        let fb = bitstream.fb(0);
+        bitstream.set_prop(&fb.io(5).fb_src(), IoFeedbackSource::IO);
+        bitstream.set_prop(&fb.io(5).output_pad_mode(), OutputMode::Disabled);
+        bitstream.set_prop(&fb.zia_row(zia.row), ZIARow::MuxChoice(zia.choice));
+        bitstream.set_prop(&fb.and_term(0).inp(zia.row), true);
+        bitstream.set_prop(&fb.or_term(3).inp(0), true);
+        bitstream.set_prop(&fb.mc(3).use_iob(), false);
+        bitstream.set_prop(&fb.mc(3).ff_mode(), FlipFlopMode::DFF);
+        bitstream.set_prop(&fb.mc(3).clk_src(), RegClkSrc::GCK0);
+        bitstream.set_prop(&fb.mc(3).init_state(), false);
+        bitstream.set_prop(&bitstream.gck_enabled(0), true);
+        bitstream.set_prop(&fb.io(3).output_src(), PinOutputSrc::FlipFlop);
+        bitstream.set_prop(&fb.io(3).output_pad_mode(), OutputMode::PushPull);
+        bitstream
+    }
+
+    #[test]
+    fn parse_and_write() {
+        let jed = b"\x02design*\nQF100*\nQP4*\nX1*\nV0001 01HL*\nV0002 C X Z N*\n\x030000";
+        let vectors = TestVectors::parse(jed).unwrap();
+        assert_eq!(vectors.num_pins, Some(4));
+        assert_eq!(vectors.default_x, Some(true));
+        assert_eq!(vectors.vectors.len(), 2);
+        assert_eq!(vectors.vectors[1].pins, b"CXZN");
+
+        let mut s = String::new();
+        vectors.write_fields(&mut s).unwrap();
+        assert_eq!(s, "QP4*\nQV2*\nX1*\nV0001 01HL*\nV0002 CXZN*\n");
+    }
+
+    #[test]
+    fn no_design_spec() {
+        // The first field comes straight after STX
+        let vectors = TestVectors::parse(b"\x02QP4*\nV0001 01HL*\n\x030000").unwrap();
+        assert_eq!(vectors.num_pins, Some(4));
+        assert_eq!(vectors.vectors.len(), 1);
+        let vectors = TestVectors::parse(b"\x02X0*\n\x030000").unwrap();
+        assert_eq!(vectors.default_x, Some(false));
+        let vectors = TestVectors::parse(b"\x02V0001 01HL*\n\x030000").unwrap();
+        assert_eq!(vectors.vectors[0].pins, b"01HL");
+
+        // Design specifications that are not fields
+        let vectors = TestVectors::parse(b"\x02Version 2*\nX1*\n\x030000").unwrap();
+        assert_eq!(vectors.vectors, []);
+        assert_eq!(vectors.default_x, Some(true));
+        assert_eq!(
+            TestVectors::parse(b"\x02design*\nV0001 01Q*\n\x030000"),
+            Err(VectorParseError::InvalidPinCharacter('Q'))
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let bitstream = flip_flop();
+        let mut sim = bitstream.simulator().unwrap();
+        let mut recorder = VectorRecorder::new(4, PINS);
+        sim.set_pin(0, 5, true);
+        sim.step().unwrap();
+        recorder.record(&sim);
+        for (gck, pad) in [(true, true), (false, true), (false, false), (true, false)] {
+            sim.set_gck(0, gck);
+            sim.set_pin(0, 5, pad);
+            sim.step().unwrap();
+            recorder.record(&sim);
+        }
+        let mut vectors = recorder.into_vectors();
+        let pins: Vec<&[u8]> = vectors.vectors.iter().map(|x| &x.pins[..]).collect();
+        assert_eq!(pins, [b"01LN", b"11HN", b"01HN", b"00HN", b"10LN"]);
+
+        // Round trip through the JED fields, which have no design specification in front
+        let mut jed = String::from("\x02");
+        vectors.write_fields(&mut jed).unwrap();
+        jed.push('\x03');
+        assert_eq!(TestVectors::parse(jed.as_bytes()).unwrap(), vectors);
+
+        let mut sim = bitstream.simulator().unwrap();
+        assert_eq!(sim.run_vectors(&vectors, PINS), Ok(vec![]));
+
+        vectors.vectors[2].pins[2] = b'L';
+        let mut sim = bitstream.simulator().unwrap();
+        assert_eq!(
+            sim.run_vectors(&vectors, PINS),
+            Ok(vec![VectorMismatch {
+                vector: 3,
+                pin: 3,
+                expected: 'L',
+                actual: PinState::Driven(true),
+            }])
+        );
+    }
+
+    #[test]
+    fn pulses() {
+        let bitstream = flip_flop();
+        let jed = b"\x02QP4*\nV0001 C1HN*\nV0002 C0LN*\nV0003 K1HN*\nV0004 C0HN*\n\x03";
+        let vectors = TestVectors::parse(jed).unwrap();
+        let mut sim = bitstream.simulator().unwrap();
+        assert_eq!(
+            sim.run_vectors(&vectors, PINS),
+            Ok(vec![VectorMismatch {
+                vector: 4,
+                pin: 3,
+                expected: 'H',
+                actual: PinState::Driven(false),
+            }])
+        );
+        assert!(!sim.gck_pin(0));
+
+        // Preloading registers is not supported
+        for c in ["P", "B"] {
+            let jed = alloc::format!("\x02QP4*\nV0001 C1HN*\nV0002 0{}LN*\n\x03", c);
+            let vectors = TestVectors::parse(jed.as_bytes()).unwrap();
+            let mut sim = bitstream.simulator().unwrap();
+            assert_eq!(
+                sim.run_vectors(&vectors, PINS),
+                Err(VectorError::Unsupported {
+                    vector: 2,
+                    pin: 2,
+                    c: c.chars().next().unwrap(),
+                })
+            );
+        }
+    }
+}
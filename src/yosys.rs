@@ -0,0 +1,1161 @@
+//! Reading Yosys JSON netlists
+//!
+//! This reads the output of Yosys' `write_json` after the design has been mapped to single-bit
+//! gates, and turns it into a [Design] with no locations, ready for the fitter. The supported
+//! cells are:
+//!
+//! * `$_AND_`, `$_OR_`, `$_XOR_` and `$_NOT_`
+//! * `$_DFF_[NP]_` and `$_DFF_[NP][NP][01]_`
+//! * `$_DLATCH_[NP]_` and `$_DLATCH_[NP][NP][01]_`
+//! * `$_TBUF_`, which must drive an output port directly
+//! * `IOBUF`, with ports `I`, `O`, `T` (tristate while high) and `IO`, which must be connected to
+//!   an inout port
+//!
+//! `$scopeinfo` cells only carry debug information and are ignored. Anything else is rejected.
+//!
+//! Combinational logic is collapsed into a sum of products for every register and every output.
+//! Clocks, asynchronous set/reset and output enables must each collapse to a single product term
+//! unless they come from a global net. Single-bit input ports are connected to the global nets by
+//! attributes on their wire: `GCK` and `GTS` with the index of the net, and `GSR`. The power-up
+//! value of registers comes from the `init` attribute of their output wire.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::{
+    bdd::{Bdd, BddRef},
+    design::{
+        AsyncInput, ClockInput, Design, InputDef, Literal, MacrocellDef, OutputDef,
+        OutputEnableDef, ProductTerm, RegisterDef, SumOfProducts,
+    },
+    mc::FlipFlopMode,
+    partdb::XC2Part,
+    NUM_BUFG_CLK, NUM_BUFG_GTS,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum YosysError {
+    /// The input is not valid JSON. The message includes the line and column.
+    Json(String),
+    /// The JSON does not have the structure written by Yosys
+    Malformed(String),
+    ModuleNotFound(String),
+    /// There are several modules and none of them is marked as the top module
+    NoTopModule,
+    UnsupportedCell { cell: String, cell_type: String },
+    /// A global net attribute is not valid for its wire, or gives a global net that another
+    /// wire already has
+    InvalidAttribute { wire: String, attr: &'static str },
+    MultipleDrivers(String),
+    Undriven(String),
+    /// A cell input or output port is connected to an `x` or `z` constant
+    Undefined(String),
+    CombinationalLoop(String),
+    /// A cell input that has to be implemented by a single product term is too complicated
+    NotAProductTerm { cell: String, port: &'static str },
+    /// The output of a tristate buffer is used by anything other than an output port
+    TristateNotAtPort(String),
+    /// A global set/reset or tristate net is used as both active high and active low
+    ConflictingGlobalPolarity(String),
+    /// An output port is never enabled
+    NeverEnabled(String),
+}
+impl Display for YosysError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YosysError::Json(msg) => write!(f, "invalid JSON: {}", msg),
+            YosysError::Malformed(msg) => write!(f, "not a Yosys netlist: {}", msg),
+            YosysError::ModuleNotFound(name) => write!(f, "there is no module {}", name),
+            YosysError::NoTopModule => write!(f, "no top module is marked and there are several"),
+            YosysError::UnsupportedCell { cell, cell_type } => write!(
+                f,
+                "cell {} has unsupported type {}; only $_AND_, $_OR_, $_XOR_, $_NOT_, $_DFF_*, \
+                 $_DLATCH_*, $_TBUF_ and IOBUF can be used",
+                cell, cell_type
+            ),
+            YosysError::InvalidAttribute { wire, attr } => {
+                write!(f, "attribute {} on wire {} is not valid", attr, wire)
+            }
+            YosysError::MultipleDrivers(net) => write!(f, "net {} has more than one driver", net),
+            YosysError::Undriven(net) => write!(f, "net {} is used but never driven", net),
+            YosysError::Undefined(cell) => {
+                write!(f, "{} is connected to an undefined (x or z) value", cell)
+            }
+            YosysError::CombinationalLoop(net) => {
+                write!(f, "net {} is part of a combinational loop", net)
+            }
+            YosysError::NotAProductTerm { cell, port } => write!(
+                f,
+                "port {} of cell {} must be driven by a single product term",
+                port, cell
+            ),
+            YosysError::TristateNotAtPort(net) => {
+                write!(f, "tristate net {} is used by something other than a port", net)
+            }
+            YosysError::ConflictingGlobalPolarity(net) => {
+                write!(f, "global net {} is used as both active high and active low", net)
+            }
+            YosysError::NeverEnabled(port) => write!(f, "output {} is never enabled", port),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for YosysError {}
+
+fn malformed(msg: String) -> YosysError {
+    YosysError::Malformed(msg)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        self.as_object()?.iter().find(|x| x.0 == key).map(|x| &x.1)
+    }
+
+    fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// An attribute or parameter value as an integer. Yosys writes these as strings of binary
+    /// digits, but decimal strings and plain numbers are accepted too.
+    fn as_int(&self) -> Option<u64> {
+        match self {
+            Json::Bool(x) => Some(*x as u64),
+            Json::Number(x) => (*x).try_into().ok(),
+            Json::String(x) if !x.is_empty() && x.bytes().all(|c| c == b'0' || c == b'1') => {
+                let x = x.trim_start_matches('0');
+                if x.len() > 64 {
+                    return None;
+                }
+                u64::from_str_radix(if x.is_empty() { "0" } else { x }, 2).ok()
+            }
+            Json::String(x) => x.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a [Json] tree. Objects are read into a [Vec] rather than a map, because the order of
+/// ports decides the order of inputs and macrocells in the design.
+struct JsonVisitor;
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, x: bool) -> Result<Json, E> {
+        Ok(Json::Bool(x))
+    }
+
+    fn visit_i64<E: de::Error>(self, x: i64) -> Result<Json, E> {
+        Ok(Json::Number(x))
+    }
+
+    fn visit_u64<E: de::Error>(self, x: u64) -> Result<Json, E> {
+        x.try_into()
+            .map(Json::Number)
+            .map_err(|_| E::custom("integer out of range"))
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Json, E> {
+        Err(E::custom("only integers are supported"))
+    }
+
+    fn visit_str<E: de::Error>(self, x: &str) -> Result<Json, E> {
+        Ok(Json::String(x.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, x: String) -> Result<Json, E> {
+        Ok(Json::String(x))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut ret = Vec::new();
+        while let Some(x) = seq.next_element()? {
+            ret.push(x);
+        }
+        Ok(Json::Array(ret))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+        let mut ret = Vec::new();
+        while let Some(x) = map.next_entry()? {
+            ret.push(x);
+        }
+        Ok(Json::Object(ret))
+    }
+}
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(JsonVisitor)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Bit {
+    Net(i64),
+    Const(bool),
+    Undef,
+}
+
+fn parse_bits(v: &Json, what: &str) -> Result<Vec<Bit>, YosysError> {
+    let bits = v
+        .as_array()
+        .ok_or_else(|| malformed(format!("{} is not an array of bits", what)))?;
+    bits.iter()
+        .map(|x| match x {
+            Json::Number(n) => Ok(Bit::Net(*n)),
+            Json::String(s) if s == "0" => Ok(Bit::Const(false)),
+            Json::String(s) if s == "1" => Ok(Bit::Const(true)),
+            Json::String(s) if s == "x" || s == "z" => Ok(Bit::Undef),
+            _ => Err(malformed(format!("{} contains an invalid bit", what))),
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Gate {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Register<'a> {
+    cell: &'a str,
+    latch: bool,
+    clk: Bit,
+    /// Clocked on the rising edge, or transparent while the enable is high
+    clk_pos: bool,
+    d: Bit,
+    q: i64,
+    /// (net, active high, value)
+    arst: Option<(Bit, bool, bool)>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Driver<'a> {
+    InputPort,
+    Gate { cell: &'a str, gate: Gate, a: Bit, b: Bit },
+    Register(usize),
+    Tristate,
+    /// The `O` port of an IOBUF
+    PadFeedback,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Enable {
+    Always,
+    ActiveHigh(Bit),
+    ActiveLow(Bit),
+}
+
+struct Reader<'a> {
+    names: BTreeMap<i64, String>,
+    drivers: BTreeMap<i64, Driver<'a>>,
+    registers: Vec<Register<'a>>,
+    /// Indexed by output net: (cell, data, enable)
+    tbufs: BTreeMap<i64, (&'a str, Bit, Enable)>,
+    /// Indexed by pad net: (cell, data, enable, feedback)
+    iobufs: BTreeMap<i64, (&'a str, Bit, Enable, Bit)>,
+    init: BTreeMap<i64, bool>,
+    gck: BTreeMap<i64, u8>,
+    gsr: Option<i64>,
+    gts: BTreeMap<i64, u8>,
+
+    bdd: Bdd,
+    vars: Vec<String>,
+    funcs: BTreeMap<i64, BddRef>,
+    visiting: BTreeSet<i64>,
+}
+impl<'a> Reader<'a> {
+    fn name(&self, net: i64) -> String {
+        match self.names.get(&net) {
+            Some(x) => x.clone(),
+            None => format!("${}", net),
+        }
+    }
+
+    fn drive(&mut self, bit: Bit, driver: Driver<'a>, cell: &str) -> Result<i64, YosysError> {
+        match bit {
+            Bit::Net(n) => {
+                if self.drivers.insert(n, driver).is_some() {
+                    return Err(YosysError::MultipleDrivers(self.name(n)));
+                }
+                Ok(n)
+            }
+            Bit::Const(_) => Err(malformed(format!("output of {} is a constant", cell))),
+            Bit::Undef => Err(YosysError::Undefined(cell.to_string())),
+        }
+    }
+
+    /// The function of a net in terms of inputs and registers
+    fn func(&mut self, bit: Bit, user: &str) -> Result<BddRef, YosysError> {
+        let net = match bit {
+            Bit::Const(x) => return Ok(BddRef::constant(x)),
+            Bit::Undef => return Err(YosysError::Undefined(user.to_string())),
+            Bit::Net(n) => n,
+        };
+        if let Some(&f) = self.funcs.get(&net) {
+            return Ok(f);
+        }
+
+        let f = match self.drivers.get(&net).copied() {
+            None => return Err(YosysError::Undriven(self.name(net))),
+            Some(Driver::Tristate) => return Err(YosysError::TristateNotAtPort(self.name(net))),
+            Some(Driver::InputPort | Driver::Register(_) | Driver::PadFeedback) => {
+                let var = self.vars.len() as u32;
+                self.vars.push(self.name(net));
+                self.bdd.var(var)
+            }
+            Some(Driver::Gate { cell, gate, a, b }) => {
+                if !self.visiting.insert(net) {
+                    return Err(YosysError::CombinationalLoop(self.name(net)));
+                }
+                let fa = self.func(a, cell)?;
+                let f = if gate == Gate::Not {
+                    self.bdd.not(fa)
+                } else {
+                    let fb = self.func(b, cell)?;
+                    match gate {
+                        Gate::And => self.bdd.and(fa, fb),
+                        Gate::Or => self.bdd.or(fa, fb),
+                        Gate::Xor => self.bdd.xor(fa, fb),
+                        Gate::Not => unreachable!(),
+                    }
+                };
+                self.visiting.remove(&net);
+                f
+            }
+        };
+        self.funcs.insert(net, f);
+        Ok(f)
+    }
+
+    fn product_term(&self, cube: Vec<(u32, bool)>) -> ProductTerm {
+        ProductTerm(
+            cube.into_iter()
+                .map(|(var, val)| Literal {
+                    signal: self.vars[var as usize].clone(),
+                    negated: !val,
+                })
+                .collect(),
+        )
+    }
+
+    /// A sum of products for `f` or its complement, whichever is smaller, and whether it is the
+    /// complement
+    fn sop(&mut self, f: BddRef) -> (SumOfProducts, bool) {
+        let pos = self.bdd.isop(f);
+        let nf = self.bdd.not(f);
+        let neg = self.bdd.isop(nf);
+        let (cubes, invert) = if neg.len() < pos.len() {
+            (neg, true)
+        } else {
+            (pos, false)
+        };
+        let terms = cubes.into_iter().map(|x| self.product_term(x)).collect();
+        (SumOfProducts(terms), invert)
+    }
+
+    /// `f` as a single product term, if possible
+    fn single_term(&mut self, f: BddRef) -> Option<ProductTerm> {
+        let mut cubes = self.bdd.isop(f);
+        if cubes.len() == 1 {
+            Some(self.product_term(cubes.pop().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// `f` if active high, or its complement, as a single product term
+    fn control_term(
+        &mut self,
+        bit: Bit,
+        active_high: bool,
+        cell: &str,
+        port: &'static str,
+    ) -> Result<Option<ProductTerm>, YosysError> {
+        let mut f = self.func(bit, cell)?;
+        if !active_high {
+            f = self.bdd.not(f);
+        }
+        if f == BddRef::FALSE {
+            return Ok(None);
+        }
+        match self.single_term(f) {
+            Some(x) => Ok(Some(x)),
+            None => Err(YosysError::NotAProductTerm {
+                cell: cell.to_string(),
+                port,
+            }),
+        }
+    }
+}
+
+/// Check that a global net is always used with the same polarity
+fn set_polarity(current: &mut Option<bool>, val: bool, net: String) -> Result<(), YosysError> {
+    match *current {
+        Some(x) if x != val => Err(YosysError::ConflictingGlobalPolarity(net)),
+        _ => {
+            *current = Some(val);
+            Ok(())
+        }
+    }
+}
+
+impl Design {
+    /// Read a Yosys JSON netlist. If `top` is not given, the module with the `top` attribute is
+    /// used, or the only module if there is just one.
+    pub fn from_yosys_json(
+        json: &str,
+        part: XC2Part,
+        top: Option<&str>,
+    ) -> Result<Self, YosysError> {
+        let root: Json =
+            serde_json::from_str(json).map_err(|e| YosysError::Json(e.to_string()))?;
+        let modules = root
+            .get("modules")
+            .and_then(Json::as_object)
+            .ok_or_else(|| malformed("there is no modules object".into()))?;
+        let attr = |x: &Json, name| x.get("attributes").and_then(|x| x.get(name)).cloned();
+        let module = match top {
+            Some(top) => modules
+                .iter()
+                .find(|x| x.0 == top)
+                .ok_or_else(|| YosysError::ModuleNotFound(top.to_string()))?,
+            None => {
+                let candidates: Vec<_> = modules
+                    .iter()
+                    .filter(|x| attr(&x.1, "blackbox").and_then(|x| x.as_int()).unwrap_or(0) == 0)
+                    .collect();
+                let marked = candidates
+                    .iter()
+                    .find(|x| attr(&x.1, "top").and_then(|x| x.as_int()).unwrap_or(0) != 0);
+                match (marked, &candidates[..]) {
+                    (Some(x), _) => *x,
+                    (None, [x]) => *x,
+                    _ => return Err(YosysError::NoTopModule),
+                }
+            }
+        };
+        let (module_name, module) = (&module.0, &module.1);
+        let section = |name: &str| match module.get(name) {
+            None => Ok(&[][..]),
+            Some(x) => x.as_object().ok_or_else(|| {
+                malformed(format!("{} of module {} is not an object", name, module_name))
+            }),
+        };
+        let ports = section("ports")?;
+        let cells = section("cells")?;
+        let netnames = section("netnames")?;
+
+        let mut r = Reader {
+            names: BTreeMap::new(),
+            drivers: BTreeMap::new(),
+            registers: Vec::new(),
+            tbufs: BTreeMap::new(),
+            iobufs: BTreeMap::new(),
+            init: BTreeMap::new(),
+            gck: BTreeMap::new(),
+            gsr: None,
+            gts: BTreeMap::new(),
+            bdd: Bdd::new(),
+            vars: Vec::new(),
+            funcs: BTreeMap::new(),
+            visiting: BTreeSet::new(),
+        };
+
+        // Net names, preferring ports and then wires the user named
+        let bit_name = |name: &str, i: usize, width: usize| {
+            if width == 1 {
+                name.to_string()
+            } else {
+                format!("{}[{}]", name, i)
+            }
+        };
+        let mut port_bits = Vec::new();
+        for (name, port) in ports {
+            let direction = port
+                .get("direction")
+                .and_then(Json::as_str)
+                .ok_or_else(|| malformed(format!("port {} has no direction", name)))?;
+            let bits = parse_bits(port.get("bits").unwrap_or(&Json::Null), name)?;
+            for (i, &bit) in bits.iter().enumerate() {
+                if let Bit::Net(n) = bit {
+                    r.names.entry(n).or_insert_with(|| bit_name(name, i, bits.len()));
+                }
+                port_bits.push((bit_name(name, i, bits.len()), direction, bit));
+            }
+        }
+        for hidden in [false, true] {
+            for (name, wire) in netnames {
+                if (wire.get("hide_name").and_then(Json::as_int).unwrap_or(0) != 0) != hidden {
+                    continue;
+                }
+                let bits = parse_bits(wire.get("bits").unwrap_or(&Json::Null), name)?;
+                for (i, &bit) in bits.iter().enumerate() {
+                    if let Bit::Net(n) = bit {
+                        r.names.entry(n).or_insert_with(|| bit_name(name, i, bits.len()));
+                    }
+                }
+            }
+        }
+
+        // Attributes on wires
+        for (name, wire) in netnames {
+            let bits = parse_bits(wire.get("bits").unwrap_or(&Json::Null), name)?;
+            if let Some(init) = attr(wire, "init").and_then(|x| x.as_str().map(String::from)) {
+                for (&bit, c) in bits.iter().zip(init.bytes().rev()) {
+                    if let Bit::Net(n) = bit {
+                        r.init.insert(n, c == b'1');
+                    }
+                }
+            }
+            for (attr_name, max) in [("GCK", NUM_BUFG_CLK), ("GSR", 1), ("GTS", NUM_BUFG_GTS)] {
+                let Some(val) = attr(wire, attr_name) else {
+                    continue;
+                };
+                let invalid = || YosysError::InvalidAttribute {
+                    wire: name.clone(),
+                    attr: attr_name,
+                };
+                let (&[Bit::Net(n)], Some(idx)) = (&bits[..], val.as_int()) else {
+                    return Err(invalid());
+                };
+                // Each global net can only be given to one wire, and each wire only one of them
+                let indexed = |x: &mut BTreeMap<i64, u8>| {
+                    let conflict = x.iter().any(|(&k, &v)| (k == n) != (v as u64 == idx));
+                    if (idx as usize) >= max || conflict {
+                        return false;
+                    }
+                    x.insert(n, idx as u8);
+                    true
+                };
+                let ok = match attr_name {
+                    "GCK" => indexed(&mut r.gck),
+                    "GTS" => indexed(&mut r.gts),
+                    _ => *r.gsr.get_or_insert(n) == n,
+                };
+                if !ok {
+                    return Err(invalid());
+                }
+            }
+        }
+
+        for &(_, direction, bit) in &port_bits {
+            if direction == "input" {
+                r.drive(bit, Driver::InputPort, "an input port")?;
+            }
+        }
+
+        // Cells
+        for (cell, x) in cells {
+            let cell = cell.as_str();
+            let cell_type = x
+                .get("type")
+                .and_then(Json::as_str)
+                .ok_or_else(|| malformed(format!("cell {} has no type", cell)))?;
+            let conns = x.get("connections");
+            let port = |port: &str| -> Result<Bit, YosysError> {
+                let bits = conns
+                    .and_then(|x| x.get(port))
+                    .ok_or_else(|| malformed(format!("cell {} has no port {}", cell, port)))?;
+                match parse_bits(bits, cell)?[..] {
+                    [bit] => Ok(bit),
+                    _ => Err(malformed(format!("port {} of cell {} is not one bit", port, cell))),
+                }
+            };
+            let unsupported = || YosysError::UnsupportedCell {
+                cell: cell.to_string(),
+                cell_type: cell_type.to_string(),
+            };
+
+            let gate = match cell_type {
+                "$_AND_" => Some(Gate::And),
+                "$_OR_" => Some(Gate::Or),
+                "$_XOR_" => Some(Gate::Xor),
+                "$_NOT_" => Some(Gate::Not),
+                _ => None,
+            };
+            if let Some(gate) = gate {
+                let a = port("A")?;
+                let b = if gate == Gate::Not { a } else { port("B")? };
+                r.drive(port("Y")?, Driver::Gate { cell, gate, a, b }, cell)?;
+                continue;
+            }
+
+            let ff = cell_type
+                .strip_prefix("$_DFF_")
+                .map(|x| (false, x))
+                .or_else(|| cell_type.strip_prefix("$_DLATCH_").map(|x| (true, x)));
+            if let Some((latch, params)) = ff {
+                let params = params.strip_suffix('_').ok_or_else(unsupported)?.as_bytes();
+                let pol = |c: u8| match c {
+                    b'P' | b'1' => Ok(true),
+                    b'N' | b'0' => Ok(false),
+                    _ => Err(unsupported()),
+                };
+                let (clk_pos, arst) = match *params {
+                    [c] if c == b'P' || c == b'N' => (pol(c)?, None),
+                    [c, r_pol, val] if [c, r_pol].iter().all(|x| *x == b'P' || *x == b'N') => {
+                        if val != b'0' && val != b'1' {
+                            return Err(unsupported());
+                        }
+                        (pol(c)?, Some((port("R")?, pol(r_pol)?, pol(val)?)))
+                    }
+                    _ => return Err(unsupported()),
+                };
+                let reg = Register {
+                    cell,
+                    latch,
+                    clk: port(if latch { "E" } else { "C" })?,
+                    clk_pos,
+                    d: port("D")?,
+                    q: 0,
+                    arst,
+                };
+                let idx = r.registers.len();
+                let q = r.drive(port("Q")?, Driver::Register(idx), cell)?;
+                r.registers.push(Register { q, ..reg });
+                continue;
+            }
+
+            match cell_type {
+                "$_TBUF_" => {
+                    let y = r.drive(port("Y")?, Driver::Tristate, cell)?;
+                    r.tbufs.insert(y, (cell, port("A")?, Enable::ActiveHigh(port("E")?)));
+                }
+                "IOBUF" => {
+                    let Bit::Net(pad) = port("IO")? else {
+                        return Err(malformed(format!("IO of cell {} is a constant", cell)));
+                    };
+                    let o = match port("O") {
+                        Ok(o) => {
+                            r.drive(o, Driver::PadFeedback, cell)?;
+                            o
+                        }
+                        Err(_) => Bit::Undef,
+                    };
+                    let enable = Enable::ActiveLow(port("T")?);
+                    if r.iobufs.insert(pad, (cell, port("I")?, enable, o)).is_some() {
+                        return Err(YosysError::MultipleDrivers(r.name(pad)));
+                    }
+                }
+                "$scopeinfo" => {}
+                _ => return Err(unsupported()),
+            }
+        }
+
+        let mut design = Design::new(part);
+        let mut gsr_active_low = None;
+        let mut gts_active_low = [None; NUM_BUFG_GTS];
+
+        // A macrocell for every register
+        let registers = core::mem::take(&mut r.registers);
+        for reg in &registers {
+            let f = r.func(reg.d, reg.cell)?;
+            let (logic, invert) = r.sop(f);
+
+            let (clock, clock_invert) = match reg.clk {
+                Bit::Net(n) if r.gck.contains_key(&n) => {
+                    (ClockInput::Global(r.gck[&n]), !reg.clk_pos)
+                }
+                clk => {
+                    let f = r.func(clk, reg.cell)?;
+                    let nf = r.bdd.not(f);
+                    let port = if reg.latch { "E" } else { "C" };
+                    match (r.single_term(f), r.single_term(nf)) {
+                        (Some(pt), _) => (ClockInput::ProductTerm(pt), !reg.clk_pos),
+                        (None, Some(pt)) => (ClockInput::ProductTerm(pt), reg.clk_pos),
+                        _ => {
+                            return Err(YosysError::NotAProductTerm {
+                                cell: reg.cell.to_string(),
+                                port,
+                            })
+                        }
+                    }
+                }
+            };
+
+            let mut set = None;
+            let mut reset = None;
+            if let Some((bit, active_high, val)) = reg.arst {
+                let input = match bit {
+                    Bit::Net(n) if r.gsr == Some(n) => {
+                        set_polarity(&mut gsr_active_low, !active_high, r.name(n))?;
+                        Some(AsyncInput::GlobalSetReset)
+                    }
+                    _ => r
+                        .control_term(bit, active_high, reg.cell, "R")?
+                        .map(AsyncInput::ProductTerm),
+                };
+                if val {
+                    set = input;
+                } else {
+                    reset = input;
+                }
+            }
+
+            design.macrocells.push(MacrocellDef {
+                name: r.name(reg.q),
+                loc: None,
+                logic,
+                invert,
+                register: Some(RegisterDef {
+                    mode: if reg.latch {
+                        FlipFlopMode::LATCH
+                    } else {
+                        FlipFlopMode::DFF
+                    },
+                    clock,
+                    clock_invert,
+                    ddr: false,
+                    clock_enable: None,
+                    set,
+                    reset,
+                    init_state: r.init.get(&reg.q).copied().unwrap_or(false),
+                }),
+                output: None,
+            });
+        }
+
+        // A macrocell for every output, reusing the register if it drives the output directly
+        let mut used_names: BTreeSet<String> =
+            design.macrocells.iter().map(|x| x.name.clone()).collect();
+        for (name, direction, bit) in port_bits {
+            if direction == "input" {
+                continue;
+            }
+            let (data, enable, feedback, cell, cell_port) = match bit {
+                Bit::Net(n) if r.tbufs.contains_key(&n) => {
+                    let (cell, data, enable) = r.tbufs[&n];
+                    (data, enable, None, cell, "E")
+                }
+                Bit::Net(n) if r.iobufs.contains_key(&n) => {
+                    let (cell, data, enable, o) = r.iobufs[&n];
+                    let feedback = match o {
+                        Bit::Net(o) => Some(r.name(o)),
+                        _ => None,
+                    };
+                    (data, enable, feedback, cell, "T")
+                }
+                Bit::Undef => return Err(YosysError::Undefined(name)),
+                _ if direction == "inout" => {
+                    return Err(malformed(format!("inout port {} has no IOBUF", name)))
+                }
+                _ => (bit, Enable::Always, None, "", ""),
+            };
+
+            let enable = match enable {
+                Enable::Always => OutputEnableDef::Always,
+                Enable::ActiveHigh(Bit::Net(n)) | Enable::ActiveLow(Bit::Net(n))
+                    if r.gts.contains_key(&n) =>
+                {
+                    let idx = r.gts[&n];
+                    let active_low = matches!(enable, Enable::ActiveLow(_));
+                    set_polarity(&mut gts_active_low[idx as usize], active_low, r.name(n))?;
+                    OutputEnableDef::GlobalTristate(idx)
+                }
+                Enable::ActiveHigh(e) | Enable::ActiveLow(e) => {
+                    let active_high = matches!(enable, Enable::ActiveHigh(_));
+                    if r.func(e, cell)? == BddRef::constant(active_high) {
+                        OutputEnableDef::Always
+                    } else {
+                        match r.control_term(e, active_high, cell, cell_port)? {
+                            Some(pt) => OutputEnableDef::ProductTerm(pt),
+                            None => return Err(YosysError::NeverEnabled(name)),
+                        }
+                    }
+                }
+            };
+
+            let mut output = OutputDef::new(false);
+            output.enable = enable;
+            output.feedback = feedback;
+
+            let reg_mc = match data {
+                Bit::Net(n) => match r.drivers.get(&n) {
+                    Some(&Driver::Register(i)) if design.macrocells[i].output.is_none() => Some(i),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(i) = reg_mc {
+                output.registered = true;
+                design.macrocells[i].output = Some(output);
+                continue;
+            }
+
+            let f = r.func(data, &name)?;
+            let (logic, invert) = r.sop(f);
+            let mut mc_name = name.clone();
+            while used_names.contains(&mc_name) {
+                mc_name.push_str("$out");
+            }
+            used_names.insert(mc_name.clone());
+            design.macrocells.push(MacrocellDef {
+                name: mc_name,
+                loc: None,
+                logic,
+                invert,
+                register: None,
+                output: Some(output),
+            });
+        }
+
+        design.gsr_invert = gsr_active_low.unwrap_or(false);
+        for (i, x) in gts_active_low.iter().enumerate() {
+            design.gts_invert[i] = x.unwrap_or(false);
+        }
+
+        // Only inputs that are actually used need a pad
+        let used: BTreeSet<&str> = design
+            .macrocells
+            .iter()
+            .flat_map(Design::product_terms)
+            .flat_map(|x| x.0.iter().map(|x| x.signal.as_str()))
+            .collect();
+        let mut inputs = Vec::new();
+        for (&net, driver) in &r.drivers {
+            let name = r.name(net);
+            if *driver == Driver::InputPort && used.contains(name.as_str()) {
+                inputs.push(InputDef {
+                    name,
+                    loc: None,
+                    standard: None,
+                    termination: false,
+                });
+            }
+        }
+        design.inputs = inputs;
+
+        Ok(design)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partdb::XC2Device;
+
+    const COUNTER: &str = r#"{
+      "creator": "Yosys",
+      "modules": {
+        "top": {
+          "attributes": { "top": "00000000000000000000000000000001" },
+          "ports": {
+            "clk": { "direction": "input", "bits": [ 2 ] },
+            "a": { "direction": "input", "bits": [ 3 ] },
+            "b": { "direction": "input", "bits": [ 4 ] },
+            "q": { "direction": "output", "bits": [ 5 ] },
+            "y": { "direction": "output", "bits": [ 6 ] }
+          },
+          "cells": {
+            "$1": { "type": "$_NOT_", "connections": { "A": [ 4 ], "Y": [ 7 ] } },
+            "$2": { "type": "$_AND_", "connections": { "A": [ 3 ], "B": [ 7 ], "Y": [ 8 ] } },
+            "$3": {
+              "type": "$_DFF_P_",
+              "connections": { "C": [ 2 ], "D": [ 8 ], "Q": [ 5 ] }
+            },
+            "$4": { "type": "$_XOR_", "connections": { "A": [ 3 ], "B": [ 5 ], "Y": [ 6 ] } }
+          },
+          "netnames": {
+            "clk": { "hide_name": 0, "bits": [ 2 ], "attributes": { "GCK": "10" } },
+            "q": { "hide_name": 0, "bits": [ 5 ], "attributes": { "init": "1" } },
+            "$n": { "hide_name": 1, "bits": [ 7 ], "attributes": { } }
+          }
+        }
+      }
+    }"#;
+
+    fn part() -> XC2Part {
+        XC2Part::new(XC2Device::XC2C32A, None, None).unwrap()
+    }
+
+    #[test]
+    fn read() {
+        let design = Design::from_yosys_json(COUNTER, part(), None).unwrap();
+        let names: Vec<&str> = design.inputs.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+
+        let q = &design.macrocells[0];
+        assert_eq!(q.name, "q");
+        assert_eq!(q.logic.to_string(), "a & !b");
+        let reg = q.register.as_ref().unwrap();
+        assert_eq!(reg.clock, ClockInput::Global(2));
+        assert!(reg.init_state);
+        assert!(q.output.as_ref().unwrap().registered);
+
+        let y = &design.macrocells[1];
+        assert_eq!(y.name, "y");
+        assert_eq!(y.logic.0.len(), 2);
+        assert!(y.register.is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let mux = COUNTER.replace("$_XOR_", "$_MUX_");
+        assert_eq!(
+            Design::from_yosys_json(&mux, part(), None).unwrap_err(),
+            YosysError::UnsupportedCell {
+                cell: "$4".into(),
+                cell_type: "$_MUX_".into()
+            }
+        );
+        assert_eq!(
+            Design::from_yosys_json("{\"modules\": [}", part(), None)
+                .unwrap_err()
+                .to_string(),
+            "invalid JSON: expected value at line 1 column 14"
+        );
+        assert_eq!(
+            Design::from_yosys_json(COUNTER, part(), Some("foo")).unwrap_err(),
+            YosysError::ModuleNotFound("foo".into())
+        );
+    }
+
+    /// A single module with the given ports, cells and netnames
+    fn module(ports: &str, cells: &str, netnames: &str) -> String {
+        format!(
+            r#"{{ "modules": {{ "top": {{
+                "ports": {{ {} }}, "cells": {{ {} }}, "netnames": {{ {} }}
+            }} }} }}"#,
+            ports, cells, netnames
+        )
+    }
+
+    #[test]
+    fn product_terms() {
+        let json = module(
+            r#""a": { "direction": "input", "bits": [ 2 ] },
+               "b": { "direction": "input", "bits": [ 3 ] },
+               "c": { "direction": "input", "bits": [ 4 ] },
+               "d": { "direction": "input", "bits": [ 5 ] },
+               "y": { "direction": "output", "bits": [ 6 ] }"#,
+            r#""$1": { "type": "$_AND_", "connections": { "A": [ 2 ], "B": [ 3 ], "Y": [ 7 ] } },
+               "$2": { "type": "$_AND_", "connections": { "A": [ 4 ], "B": [ 5 ], "Y": [ 8 ] } },
+               "$3": { "type": "$_OR_", "connections": { "A": [ 7 ], "B": [ 8 ], "Y": [ 6 ] } }"#,
+            "",
+        );
+        let design = Design::from_yosys_json(&json, part(), None).unwrap();
+        let y = &design.macrocells[0];
+        assert_eq!(y.logic.to_string(), "a & b | c & d");
+        assert!(!y.invert);
+    }
+
+    #[test]
+    fn latch() {
+        let json = module(
+            r#""en": { "direction": "input", "bits": [ 2 ] },
+               "a": { "direction": "input", "bits": [ 3 ] },
+               "q": { "direction": "output", "bits": [ 4 ] }"#,
+            r#""$1": {
+                 "type": "$_DLATCH_N_",
+                 "connections": { "E": [ 2 ], "D": [ 3 ], "Q": [ 4 ] }
+               }"#,
+            "",
+        );
+        let design = Design::from_yosys_json(&json, part(), None).unwrap();
+        let q = &design.macrocells[0];
+        assert_eq!(q.logic.to_string(), "a");
+        let reg = q.register.as_ref().unwrap();
+        assert_eq!(reg.mode, FlipFlopMode::LATCH);
+        assert_eq!(reg.clock, ClockInput::ProductTerm(ProductTerm::parse("en").unwrap()));
+        assert!(reg.clock_invert);
+        assert!(q.output.as_ref().unwrap().registered);
+    }
+
+    #[test]
+    fn global_set_reset() {
+        let ports = r#""clk": { "direction": "input", "bits": [ 2 ] },
+            "rst_n": { "direction": "input", "bits": [ 3 ] },
+            "a": { "direction": "input", "bits": [ 4 ] },
+            "s": { "direction": "input", "bits": [ 5 ] },
+            "q": { "direction": "output", "bits": [ 6 ] },
+            "p": { "direction": "output", "bits": [ 7 ] }"#;
+        let netnames = r#""clk": { "hide_name": 0, "bits": [ 2 ], "attributes": { "GCK": "0" } },
+            "rst_n": { "hide_name": 0, "bits": [ 3 ], "attributes": { "GSR": "1" } }"#;
+        let cells = |p_reset| {
+            format!(
+                r#""$1": {{ "type": "$_DFF_PN0_",
+                        "connections": {{ "C": [ 2 ], "R": [ 3 ], "D": [ 4 ], "Q": [ 6 ] }} }},
+                    "$2": {{ "type": "$_DFF_NP1_",
+                        "connections": {{ "C": [ 2 ], "R": [ {} ], "D": [ 4 ], "Q": [ 7 ] }} }}"#,
+                p_reset
+            )
+        };
+
+        let json = module(ports, &cells(5), netnames);
+        let design = Design::from_yosys_json(&json, part(), None).unwrap();
+        assert!(design.gsr_invert);
+        let q = design.macrocells[0].register.as_ref().unwrap();
+        assert_eq!(q.clock, ClockInput::Global(0));
+        assert!(!q.clock_invert);
+        assert_eq!(q.reset, Some(AsyncInput::GlobalSetReset));
+        assert_eq!(q.set, None);
+        let p = design.macrocells[1].register.as_ref().unwrap();
+        assert!(p.clock_invert);
+        assert_eq!(p.set, Some(AsyncInput::ProductTerm(ProductTerm::parse("s").unwrap())));
+        assert_eq!(p.reset, None);
+
+        // The second register would need the global set/reset to be active high
+        let json = module(ports, &cells(3), netnames);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            YosysError::ConflictingGlobalPolarity("rst_n".into())
+        );
+    }
+
+    #[test]
+    fn tristate() {
+        let json = module(
+            r#""a": { "direction": "input", "bits": [ 2 ] },
+               "en": { "direction": "input", "bits": [ 3 ] },
+               "t": { "direction": "input", "bits": [ 4 ] },
+               "b": { "direction": "input", "bits": [ 5 ] },
+               "oe": { "direction": "input", "bits": [ 6 ] },
+               "y": { "direction": "output", "bits": [ 7 ] },
+               "pad": { "direction": "inout", "bits": [ 8 ] },
+               "z": { "direction": "output", "bits": [ 9 ] },
+               "w": { "direction": "output", "bits": [ 10 ] }"#,
+            r#""$1": { "type": "$_TBUF_", "connections": { "A": [ 2 ], "E": [ 3 ], "Y": [ 7 ] } },
+               "$2": {
+                 "type": "IOBUF",
+                 "connections": { "I": [ 2 ], "T": [ 4 ], "O": [ 11 ], "IO": [ 8 ] }
+               },
+               "$3": { "type": "$_AND_", "connections": { "A": [ 11 ], "B": [ 5 ], "Y": [ 9 ] } },
+               "$4": { "type": "$_TBUF_", "connections": { "A": [ 5 ], "E": [ 6 ], "Y": [ 10 ] }
+               }"#,
+            r#""fb": { "hide_name": 0, "bits": [ 11 ], "attributes": { } },
+               "oe": { "hide_name": 0, "bits": [ 6 ], "attributes": { "GTS": "1" } }"#,
+        );
+        let design = Design::from_yosys_json(&json, part(), None).unwrap();
+        let names: Vec<&str> = design.inputs.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["a", "en", "t", "b"]);
+        assert!(!design.gts_invert[1]);
+
+        let mcs = &design.macrocells;
+        let names: Vec<&str> = mcs.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["y", "pad", "z", "w"]);
+        let output = |i: usize| mcs[i].output.as_ref().unwrap();
+        let pt = |s| OutputEnableDef::ProductTerm(ProductTerm::parse(s).unwrap());
+
+        assert_eq!(mcs[0].logic.to_string(), "a");
+        assert_eq!(output(0).enable, pt("en"));
+        assert_eq!(output(0).feedback, None);
+
+        // The IOBUF enable is active low and its output is read back from the pad
+        assert_eq!(mcs[1].logic.to_string(), "a");
+        assert_eq!(output(1).enable, pt("!t"));
+        assert_eq!(output(1).feedback.as_deref(), Some("fb"));
+        assert_eq!(mcs[2].logic.to_string(), "fb & b");
+        assert_eq!(output(2).enable, OutputEnableDef::Always);
+
+        assert_eq!(mcs[3].logic.to_string(), "b");
+        assert_eq!(output(3).enable, OutputEnableDef::GlobalTristate(1));
+    }
+
+    #[test]
+    fn structural_errors() {
+        // The clock is an XOR, which is not a single product term either way round
+        let json = COUNTER.replace(r#""C": [ 2 ]"#, r#""C": [ 6 ]"#);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            YosysError::NotAProductTerm {
+                cell: "$3".into(),
+                port: "C"
+            }
+        );
+
+        let json = COUNTER.replace(r#""A": [ 4 ], "Y": [ 7 ]"#, r#""A": [ 8 ], "Y": [ 7 ]"#);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            YosysError::CombinationalLoop("$8".into())
+        );
+    }
+
+    #[test]
+    fn invalid_attributes() {
+        let invalid = |wire: &str, attr| YosysError::InvalidAttribute {
+            wire: wire.into(),
+            attr,
+        };
+        let q_attrs = r#""attributes": { "init": "1" }"#;
+
+        // Out of range
+        let json = COUNTER.replace(r#""GCK": "10""#, r#""GCK": "11""#);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            invalid("clk", "GCK")
+        );
+
+        // Two wires with the same global clock
+        let json = COUNTER.replace(q_attrs, r#""attributes": { "init": "1", "GCK": "2" }"#);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            invalid("q", "GCK")
+        );
+
+        // Two global set/reset wires
+        let json = COUNTER
+            .replace(q_attrs, r#""attributes": { "init": "1", "GSR": "1" }"#)
+            .replace(r#""attributes": { } "#, r#""attributes": { "GSR": "1" } "#);
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            invalid("$n", "GSR")
+        );
+
+        // A wire on more than one global tristate
+        let json = COUNTER.replace(
+            r#""attributes": { } "#,
+            r#""attributes": { "GTS": "0" } },
+               "n2": { "bits": [ 7 ], "attributes": { "GTS": "1" } "#,
+        );
+        assert_eq!(
+            Design::from_yosys_json(&json, part(), None).unwrap_err(),
+            invalid("n2", "GTS")
+        );
+    }
+}
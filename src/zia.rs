@@ -262,6 +262,27 @@ const ZIA_PATS_512: &[&[bool]] = &[
     &[T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, F, F, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T, T],
 ];
 
+/// Bit patterns for each mux choice, followed by VCC and GND
+fn zia_pats(device: XC2Device) -> &'static [&'static [bool]] {
+    match device {
+        XC2Device::XC2C32 | XC2Device::XC2C32A => ZIA_PATS_32,
+        XC2Device::XC2C64 | XC2Device::XC2C64A => ZIA_PATS_64,
+        XC2Device::XC2C128 => ZIA_PATS_128,
+        XC2Device::XC2C256 => ZIA_PATS_256,
+        XC2Device::XC2C384 => ZIA_PATS_384,
+        XC2Device::XC2C512 => ZIA_PATS_512,
+    }
+}
+
+/// Whether an entry of [zia_pats] has been confirmed.
+/// These are the ones marked with a TODO above.
+const fn zia_pat_is_certain(device: XC2Device, pat_i: usize) -> bool {
+    match device {
+        XC2Device::XC2C32 | XC2Device::XC2C32A => true,
+        _ => pat_i != device.num_zia_choices() + 1,
+    }
+}
+
 #[bittwiddler_hierarchy_level(alloc_feature_gate = "alloc")]
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct ZIARowAccessor {
@@ -308,14 +329,7 @@ impl PropertyAccessor for ZIARowAccessor {
             bits[biti] = bitstream.get(c) ^ inv;
         }
 
-        let match_pats = match self.x.device {
-            XC2Device::XC2C32 | XC2Device::XC2C32A => ZIA_PATS_32,
-            XC2Device::XC2C64 | XC2Device::XC2C64A => ZIA_PATS_64,
-            XC2Device::XC2C128 => ZIA_PATS_128,
-            XC2Device::XC2C256 => ZIA_PATS_256,
-            XC2Device::XC2C384 => ZIA_PATS_384,
-            XC2Device::XC2C512 => ZIA_PATS_512,
-        };
+        let match_pats = zia_pats(self.x.device);
 
         for zia_row_i in 0..(self.x.device.num_zia_choices() + 2) {
             if &bits[..self.x.device.zia_width()] == match_pats[zia_row_i] {
@@ -333,14 +347,7 @@ impl PropertyAccessor for ZIARowAccessor {
     }
 
     fn set(&self, bitstream: &mut (impl BitArray + ?Sized), val: Self::Output) {
-        let match_pats = match self.x.device {
-            XC2Device::XC2C32 | XC2Device::XC2C32A => ZIA_PATS_32,
-            XC2Device::XC2C64 | XC2Device::XC2C64A => ZIA_PATS_64,
-            XC2Device::XC2C128 => ZIA_PATS_128,
-            XC2Device::XC2C256 => ZIA_PATS_256,
-            XC2Device::XC2C384 => ZIA_PATS_384,
-            XC2Device::XC2C512 => ZIA_PATS_512,
-        };
+        let match_pats = zia_pats(self.x.device);
 
         let bits = match val {
             ZIARow::VCC => match_pats[self.x.device.num_zia_choices()],
@@ -375,6 +382,18 @@ impl PropertyLeaf<[bool; 0]> for ZIARow {
         unreachable!()
     }
 }
+impl ZIARow {
+    /// Whether the bit pattern for this setting is known to be correct on the given device.
+    /// [ZIARow::Invalid] is never certain.
+    pub const fn is_certain(&self, device: XC2Device) -> bool {
+        match self {
+            ZIARow::MuxChoice(choice) => zia_pat_is_certain(device, *choice as usize),
+            ZIARow::VCC => zia_pat_is_certain(device, device.num_zia_choices()),
+            ZIARow::GND => zia_pat_is_certain(device, device.num_zia_choices() + 1),
+            ZIARow::Invalid(_) => false,
+        }
+    }
+}
 impl Default for ZIARow {
     fn default() -> Self {
         Self::VCC
@@ -386,20 +405,6 @@ impl PropertyLeafWithStringConv<[bool; 0], ZIARowAccessor> for ZIARow {
         let device = accessor.x.device;
         let zia_row = accessor.zia_row;
         match self {
-            ZIARow::VCC => "1".into(),
-            ZIARow::GND => "0".into(),
-            ZIARow::MuxChoice(zia_choice) => {
-                let zia_ent = device.zia_table_get_row(zia_row)[*zia_choice as usize];
-                match zia_ent {
-                    ZIATableEntry::Macrocell { fb, mc } => {
-                        alloc::format!("Macrocell(FB{fb}, MC{mc})").into()
-                    }
-                    ZIATableEntry::InputPin { fb, mc } => {
-                        alloc::format!("InputPin(FB{fb}, MC{mc})").into()
-                    }
-                    ZIATableEntry::DedicatedInputPin => "DedicatedInputPin".into(),
-                }
-            }
             ZIARow::Invalid(bits) => {
                 let mut ret = alloc::string::String::from("Invalid(");
                 for &bit in &bits[..device.zia_width()] {
@@ -409,9 +414,35 @@ impl PropertyLeafWithStringConv<[bool; 0], ZIARowAccessor> for ZIARow {
                         ret.push('0');
                     }
                 }
+
+                // Only informational, from_string ignores everything after the bits
+                let analysis = InvalidZIARowAnalysis::new(device, bits);
+                let name = |x: ZIARow| x.valid_to_string(device, zia_row);
+                if !analysis.active.is_empty() {
+                    ret.push_str(" active ");
+                    for (i, &x) in analysis.active.iter().enumerate() {
+                        if i != 0 {
+                            ret.push_str(" and ");
+                        }
+                        ret.push_str(&name(x));
+                    }
+                }
+                if !analysis.stray_bits.is_empty() {
+                    ret.push_str(" stray bits");
+                    for x in &analysis.stray_bits {
+                        ret.push_str(&alloc::format!(" {}", x));
+                    }
+                }
+                ret.push_str(&alloc::format!(
+                    ", nearest {} at distance {}",
+                    name(analysis.nearest),
+                    analysis.distance
+                ));
+
                 ret.push(')');
                 ret.into()
             }
+            x => x.valid_to_string(device, zia_row),
         }
     }
 
@@ -440,11 +471,19 @@ impl PropertyLeafWithStringConv<[bool; 0], ZIARowAccessor> for ZIARow {
                     return Err(());
                 }
             }
-            if chars.next().ok_or(())? != ')' {
-                return Err(());
-            }
-            if chars.next().is_some() {
-                return Err(());
+            match chars.next().ok_or(())? {
+                ')' => {
+                    if chars.next().is_some() {
+                        return Err(());
+                    }
+                }
+                // Annotation written by to_string
+                ' ' | ',' => {
+                    if !chars.as_str().ends_with(')') {
+                        return Err(());
+                    }
+                }
+                _ => return Err(()),
             }
             return Ok(Self::Invalid(bits));
         } else if let Some(s) = s.strip_prefix("Macrocell(") {
@@ -511,11 +550,153 @@ impl PropertyLeafWithStringConv<[bool; 0], ZIARowAccessor> for ZIARow {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+#[cfg(feature = "alloc")]
+impl ZIARow {
+    /// String form of anything other than [ZIARow::Invalid]
+    fn valid_to_string(&self, device: XC2Device, zia_row: u8) -> alloc::borrow::Cow<'static, str> {
+        match self {
+            ZIARow::VCC => "1".into(),
+            ZIARow::GND => "0".into(),
+            ZIARow::MuxChoice(zia_choice) => {
+                let zia_ent = device.zia_table_get_row(zia_row)[*zia_choice as usize];
+                alloc::format!("{}", zia_ent).into()
+            }
+            ZIARow::Invalid(_) => unreachable!(),
+        }
+    }
+}
+
+/// Explanation of the bits of a [ZIARow::Invalid]
+///
+/// Each valid setting clears a few bits. A setting is considered active when all of its cleared
+/// bits are cleared in the row, so more than one active setting means that several inputs are
+/// being connected to the row at the same time.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InvalidZIARowAnalysis {
+    /// Mux choices, and GND, that are selected by the row
+    pub active: alloc::vec::Vec<ZIARow>,
+    /// Cleared bits that are not part of any active setting
+    pub stray_bits: alloc::vec::Vec<usize>,
+    /// The closest valid setting by Hamming distance, preferring the lowest mux choice
+    pub nearest: ZIARow,
+    pub distance: usize,
+}
+#[cfg(feature = "alloc")]
+impl InvalidZIARowAnalysis {
+    pub fn new(device: XC2Device, bits: &[bool; 88]) -> Self {
+        let bits = &bits[..device.zia_width()];
+        let pats = zia_pats(device);
+        let num_choices = device.num_zia_choices();
+        let setting = |i: usize| {
+            if i == num_choices {
+                ZIARow::VCC
+            } else if i == num_choices + 1 {
+                ZIARow::GND
+            } else {
+                ZIARow::MuxChoice(i as u8)
+            }
+        };
+
+        let mut active = alloc::vec::Vec::new();
+        let mut explained = [false; 88];
+        for (i, pat) in pats.iter().enumerate() {
+            // VCC clears nothing and is therefore always "active"
+            if i == num_choices {
+                continue;
+            }
+            if pat.iter().zip(bits).all(|(&p, &b)| p || !b) {
+                active.push(setting(i));
+                for (biti, &p) in pat.iter().enumerate() {
+                    if !p {
+                        explained[biti] = true;
+                    }
+                }
+            }
+        }
+
+        let stray_bits = (0..bits.len())
+            .filter(|&i| !bits[i] && !explained[i])
+            .collect();
+
+        let (nearest, distance) = pats
+            .iter()
+            .map(|pat| pat.iter().zip(bits).filter(|(p, b)| p != b).count())
+            .enumerate()
+            .min_by_key(|&(_, d)| d)
+            .unwrap();
+
+        Self {
+            active,
+            stray_bits,
+            nearest: setting(nearest),
+            distance,
+        }
+    }
+
+    /// More than one input is connected to the row
+    pub fn is_conflicting(&self) -> bool {
+        self.active.len() > 1
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub enum ZIATableEntry {
     Macrocell { fb: u8, mc: u8 },
     InputPin { fb: u8, mc: u8 },
     DedicatedInputPin,
 }
+/// The same form as the string conversion of [ZIARow], e.g. `Macrocell(FB1, MC3)`
+impl core::fmt::Display for ZIATableEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ZIATableEntry::Macrocell { fb, mc } => write!(f, "Macrocell(FB{}, MC{})", fb, mc),
+            ZIATableEntry::InputPin { fb, mc } => write!(f, "InputPin(FB{}, MC{})", fb, mc),
+            ZIATableEntry::DedicatedInputPin => write!(f, "DedicatedInputPin"),
+        }
+    }
+}
+
+/// One way of getting a signal into a function block: setting ZIA row `row` to
+/// [ZIARow::MuxChoice]`(choice)`
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct ZIARowChoice {
+    pub row: u8,
+    pub choice: u8,
+}
 
 pub mod table;
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{bitstream::Coolrunner2, partdb::XC2Part};
+
+    #[test]
+    fn invalid_row_analysis() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let acc = bitstream.fb(0).zia_row(0);
+
+        // Choices 0 and 5 at the same time
+        let mut bits = [true; 88];
+        bits[0] = false;
+        bits[2] = false;
+        bits[7] = false;
+        bitstream.set_prop(&acc, ZIARow::Invalid(bits));
+
+        let analysis = InvalidZIARowAnalysis::new(XC2Device::XC2C32A, &bits);
+        assert_eq!(
+            analysis.active,
+            [ZIARow::MuxChoice(0), ZIARow::MuxChoice(5)]
+        );
+        assert!(analysis.is_conflicting());
+        assert_eq!(analysis.stray_bits, []);
+        assert_eq!(analysis.nearest, ZIARow::MuxChoice(0));
+        assert_eq!(analysis.distance, 1);
+
+        let val = bitstream.get_prop(&acc);
+        let s = val.to_string(&acc);
+        assert!(s.starts_with("Invalid(01011110 active InputPin(FB0, MC0) and "));
+        assert_eq!(ZIARow::from_string(&s, &acc), Ok(val));
+    }
+}
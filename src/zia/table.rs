@@ -1,8 +1,8 @@
 //! ZIA data tables
 
-use crate::ZIA_ROWS;
+use crate::{MCS_PER_FB, ZIA_ROWS};
 
-use super::ZIATableEntry;
+use super::{ZIARowChoice, ZIATableEntry};
 
 pub static ZIA_MAP_32: [[ZIATableEntry; 6]; ZIA_ROWS] = [
     // Row 0
@@ -9349,3 +9349,93 @@ pub static ZIA_MAP_512: [[ZIATableEntry; 78]; ZIA_ROWS] = [
         ZIATableEntry::Macrocell { fb: 31, mc: 5 },
     ],
 ];
+
+/// Inverse of a ZIA map, giving for each source every (row, choice) that selects it
+pub(crate) struct ZIAInverseTable<const E: usize, const K: usize> {
+    /// Index into `entries` where the choices for each source start
+    starts: [u16; K],
+    /// Grouped by source, then in order of row and choice
+    entries: [ZIARowChoice; E],
+}
+
+/// Dense index of a ZIA source, with macrocells first, then input pins, then the dedicated input
+pub(crate) const fn zia_source_index(ent: ZIATableEntry, num_fbs: usize) -> usize {
+    match ent {
+        ZIATableEntry::Macrocell { fb, mc } => fb as usize * MCS_PER_FB + mc as usize,
+        ZIATableEntry::InputPin { fb, mc } => (num_fbs + fb as usize) * MCS_PER_FB + mc as usize,
+        ZIATableEntry::DedicatedInputPin => num_fbs * 2 * MCS_PER_FB,
+    }
+}
+
+const fn invert_zia_map<const N: usize, const E: usize, const K: usize>(
+    map: &[[ZIATableEntry; N]; ZIA_ROWS],
+) -> ZIAInverseTable<E, K> {
+    assert!(E == N * ZIA_ROWS);
+    let num_fbs = (K - 1) / (2 * MCS_PER_FB);
+
+    // Counting sort by source
+    let mut counts = [0u16; K];
+    let mut row = 0;
+    while row < ZIA_ROWS {
+        let mut choice = 0;
+        while choice < N {
+            counts[zia_source_index(map[row][choice], num_fbs)] += 1;
+            choice += 1;
+        }
+        row += 1;
+    }
+
+    let mut starts = [0u16; K];
+    let mut k = 1;
+    while k < K {
+        starts[k] = starts[k - 1] + counts[k - 1];
+        k += 1;
+    }
+
+    let mut next = starts;
+    let mut entries = [ZIARowChoice { row: 0, choice: 0 }; E];
+    let mut row = 0;
+    while row < ZIA_ROWS {
+        let mut choice = 0;
+        while choice < N {
+            let k = zia_source_index(map[row][choice], num_fbs);
+            entries[next[k] as usize] = ZIARowChoice {
+                row: row as u8,
+                choice: choice as u8,
+            };
+            next[k] += 1;
+            choice += 1;
+        }
+        row += 1;
+    }
+
+    ZIAInverseTable { starts, entries }
+}
+
+impl<const E: usize, const K: usize> ZIAInverseTable<E, K> {
+    pub(crate) fn get(&self, k: usize) -> &[ZIARowChoice] {
+        if k >= K {
+            return &[];
+        }
+        let start = self.starts[k] as usize;
+        let end = if k + 1 < K {
+            self.starts[k + 1] as usize
+        } else {
+            E
+        };
+        &self.entries[start..end]
+    }
+}
+
+pub(crate) static ZIA_INV_32: ZIAInverseTable<{ 6 * ZIA_ROWS }, { 2 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_32);
+pub(crate) static ZIA_INV_64: ZIAInverseTable<{ 12 * ZIA_ROWS }, { 4 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_64);
+pub(crate) static ZIA_INV_128: ZIAInverseTable<{ 22 * ZIA_ROWS }, { 8 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_128);
+pub(crate) static ZIA_INV_256: ZIAInverseTable<{ 40 * ZIA_ROWS }, { 16 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_256);
+pub(crate) static ZIA_INV_384: ZIAInverseTable<{ 62 * ZIA_ROWS }, { 24 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_384);
+pub(crate) static ZIA_INV_512: ZIAInverseTable<{ 78 * ZIA_ROWS }, { 32 * 32 + 1 }> =
+    invert_zia_map(&ZIA_MAP_512);
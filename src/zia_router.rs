@@ -0,0 +1,314 @@
+//! Assigning signals to ZIA rows
+//!
+//! Each ZIA row of a function block can select one of a handful of sources, and each source is
+//! available on a handful of rows. Getting a set of signals into a function block is therefore a
+//! bipartite matching between signals and rows, which is solved here with augmenting paths.
+//!
+//! The same tables apply to every function block, so routing does not depend on which function
+//! block is being routed.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt::{self, Display};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    bitstream::{BitHolder, Coolrunner2},
+    partdb::XC2Device,
+    zia::{ZIARow, ZIARowChoice, ZIATableEntry},
+    ZIA_ROWS,
+};
+
+/// Which row, and which choice within that row, carries each signal
+pub type ZIARouting = BTreeMap<ZIATableEntry, ZIARowChoice>;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ZIARouteError {
+    /// The signals cannot all be routed at the same time. Removing any one of them makes the rest
+    /// routable.
+    Unroutable(Vec<ZIATableEntry>),
+    /// An existing assignment places a signal on a row that cannot select it
+    InvalidExisting(ZIATableEntry, u8),
+}
+impl Display for ZIARouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZIARouteError::Unroutable(signals) => {
+                write!(f, "these signals cannot share the ZIA:")?;
+                for x in signals {
+                    write!(f, " {}", x)?;
+                }
+                Ok(())
+            }
+            ZIARouteError::InvalidExisting(source, row) => {
+                write!(f, "ZIA row {} cannot select {}", row, source)
+            }
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for ZIARouteError {}
+
+/// Row matching state for one attempt
+struct Matcher {
+    /// Candidate rows of each signal, in order of preference
+    candidates: Vec<Vec<u8>>,
+    /// Which signal each row carries
+    row_owner: [Option<usize>; ZIA_ROWS],
+}
+impl Matcher {
+    /// Try to find a row for `sig`, moving other signals out of the way if needed
+    fn augment(&mut self, sig: usize, visited: &mut [bool; ZIA_ROWS]) -> bool {
+        for i in 0..self.candidates[sig].len() {
+            let row = self.candidates[sig][i] as usize;
+            if visited[row] {
+                continue;
+            }
+            visited[row] = true;
+            let free = match self.row_owner[row] {
+                None => true,
+                Some(other) => self.augment(other, visited),
+            };
+            if free {
+                self.row_owner[row] = Some(sig);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Find a row for every signal, or return the index of one that could not be placed
+    fn solve(candidates: Vec<Vec<u8>>) -> Result<[Option<usize>; ZIA_ROWS], usize> {
+        let mut m = Matcher {
+            candidates,
+            row_owner: [None; ZIA_ROWS],
+        };
+
+        // Give every signal its first choice if nobody else has it yet. Signals with only one
+        // candidate (existing assignments) go first so that they cannot be displaced.
+        let mut order: Vec<usize> = (0..m.candidates.len()).collect();
+        order.sort_by_key(|&i| m.candidates[i].len() != 1);
+        let mut unplaced = Vec::new();
+        for sig in order {
+            match m.candidates[sig].first() {
+                Some(&row) if m.row_owner[row as usize].is_none() => {
+                    m.row_owner[row as usize] = Some(sig)
+                }
+                _ => unplaced.push(sig),
+            }
+        }
+
+        for sig in unplaced {
+            if !m.augment(sig, &mut [false; ZIA_ROWS]) {
+                return Err(sig);
+            }
+        }
+        Ok(m.row_owner)
+    }
+}
+
+/// Route signals into a function block.
+///
+/// `signals` maps each source that is needed to an optional preferred row. Preferences are
+/// honored where they do not prevent a complete routing. Sources in `existing` that are also in
+/// `signals` keep their current row, and all other rows are free to be reused.
+pub fn route_zia(
+    device: XC2Device,
+    signals: &BTreeMap<ZIATableEntry, Option<u8>>,
+    existing: &ZIARouting,
+) -> Result<ZIARouting, ZIARouteError> {
+    let sources: Vec<ZIATableEntry> = signals.keys().copied().collect();
+    let mut candidates = Vec::with_capacity(sources.len());
+    for (&source, &preferred) in signals {
+        if let Some(x) = existing.get(&source) {
+            if device.zia_choice_for(x.row, source) != Some(x.choice) {
+                return Err(ZIARouteError::InvalidExisting(source, x.row));
+            }
+            candidates.push(vec![x.row]);
+            continue;
+        }
+
+        let mut rows: Vec<u8> = device.zia_rows_for(source).iter().map(|x| x.row).collect();
+        if let Some(pos) = preferred.and_then(|p| rows.iter().position(|&x| x == p)) {
+            let p = rows.remove(pos);
+            rows.insert(0, p);
+        }
+        candidates.push(rows);
+    }
+
+    match Matcher::solve(candidates.clone()) {
+        Ok(row_owner) => {
+            let mut ret = ZIARouting::new();
+            for (row, sig) in row_owner.iter().enumerate() {
+                if let Some(sig) = *sig {
+                    let source = sources[sig];
+                    let choice = device.zia_choice_for(row as u8, source).unwrap();
+                    ret.insert(source, ZIARowChoice { row: row as u8, choice });
+                }
+            }
+            Ok(ret)
+        }
+        Err(_) => {
+            // Drop signals one at a time as long as what is left is still unroutable. Adding
+            // signals can never make routing easier, so what remains is a minimal conflict.
+            let mut conflict: Vec<usize> = (0..sources.len()).collect();
+            let mut i = 0;
+            while i < conflict.len() {
+                let mut trial = conflict.clone();
+                trial.remove(i);
+                let trial_candidates = trial.iter().map(|&x| candidates[x].clone()).collect();
+                if Matcher::solve(trial_candidates).is_err() {
+                    conflict = trial;
+                } else {
+                    i += 1;
+                }
+            }
+            Err(ZIARouteError::Unroutable(
+                conflict.into_iter().map(|x| sources[x]).collect(),
+            ))
+        }
+    }
+}
+
+#[allow(private_bounds)]
+impl<B: BitHolder> Coolrunner2<B> {
+    /// The sources currently selected by the ZIA rows of a function block.
+    ///
+    /// Every row set to a mux choice is counted, so rows that are not meant to be used should be
+    /// set to [ZIARow::GND] or [ZIARow::VCC].
+    pub fn zia_routing(&self, fb: u8) -> ZIARouting {
+        let device = self.part.device;
+        let mut ret = ZIARouting::new();
+        for row in 0..ZIA_ROWS as u8 {
+            if let ZIARow::MuxChoice(choice) = self.get_prop(&self.fb(fb).zia_row(row)) {
+                let source = device.zia_table_get_row(row)[choice as usize];
+                ret.insert(source, ZIARowChoice { row, choice });
+            }
+        }
+        ret
+    }
+
+    /// Set the ZIA rows of a function block to carry the given signals. Other rows are not
+    /// changed.
+    pub fn set_zia_routing(&mut self, fb: u8, routing: &ZIARouting) {
+        for x in routing.values() {
+            self.set_prop(&self.fb(fb).zia_row(x.row), ZIARow::MuxChoice(x.choice));
+        }
+    }
+
+    /// Route signals into a function block and write the result.
+    ///
+    /// Every row that is already routed keeps its source, whether or not that source is in
+    /// `signals`, so existing logic is never disconnected. Rows can be released beforehand by
+    /// setting them to [ZIARow::GND] or [ZIARow::VCC]. The returned routing covers both the new
+    /// and the existing signals.
+    pub fn route_zia(
+        &mut self,
+        fb: u8,
+        signals: &BTreeMap<ZIATableEntry, Option<u8>>,
+    ) -> Result<ZIARouting, ZIARouteError> {
+        let existing = self.zia_routing(fb);
+        let mut all = signals.clone();
+        for &source in existing.keys() {
+            all.entry(source).or_insert(None);
+        }
+        let routing = route_zia(self.part.device, &all, &existing)?;
+        self.set_zia_routing(fb, &routing);
+        Ok(routing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partdb::XC2Part;
+
+    #[test]
+    fn preferred() {
+        let device = XC2Device::XC2C32A;
+        let a = ZIATableEntry::InputPin { fb: 0, mc: 5 };
+        let b = ZIATableEntry::Macrocell { fb: 1, mc: 2 };
+        let a_rows = device.zia_rows_for(a);
+        let preferred = a_rows[a_rows.len() - 1].row;
+
+        let signals = BTreeMap::from([(a, Some(preferred)), (b, None)]);
+        let routing = route_zia(device, &signals, &ZIARouting::new()).unwrap();
+        assert_eq!(routing[&a].row, preferred);
+        assert_ne!(routing[&a].row, routing[&b].row);
+        assert_eq!(
+            device.zia_choice_for(routing[&b].row, b),
+            Some(routing[&b].choice)
+        );
+
+        // Incremental routing keeps the existing row even against a preference
+        let signals = BTreeMap::from([(a, Some(a_rows[0].row)), (b, None)]);
+        assert_eq!(route_zia(device, &signals, &routing).unwrap(), routing);
+    }
+
+    #[test]
+    fn minimal_conflict() {
+        let device = XC2Device::XC2C32A;
+        let a = ZIATableEntry::InputPin { fb: 0, mc: 5 };
+
+        // Fill every row that can carry `a` with something else
+        let mut existing = ZIARouting::new();
+        for x in device.zia_rows_for(a) {
+            let (choice, &source) = device
+                .zia_table_get_row(x.row)
+                .iter()
+                .enumerate()
+                .find(|&(_, s)| *s != a && !existing.contains_key(s))
+                .unwrap();
+            existing.insert(source, ZIARowChoice { row: x.row, choice: choice as u8 });
+        }
+
+        let mut signals: BTreeMap<_, _> = existing.keys().map(|&x| (x, None)).collect();
+        signals.insert(a, None);
+
+        let Err(ZIARouteError::Unroutable(mut conflict)) = route_zia(device, &signals, &existing)
+        else {
+            panic!("routing should fail");
+        };
+        conflict.sort();
+        let mut expected: Vec<_> = existing.keys().copied().collect();
+        expected.push(a);
+        expected.sort();
+        assert_eq!(conflict, expected);
+    }
+
+    #[test]
+    fn keeps_unrelated_rows() {
+        let mut bitstream = Coolrunner2::new(XC2Part::new(XC2Device::XC2C32A, None, None).unwrap());
+        let device = bitstream.part.device;
+        let a = ZIATableEntry::InputPin { fb: 0, mc: 5 };
+        let b = ZIATableEntry::Macrocell { fb: 1, mc: 2 };
+
+        // Something already uses the preferred row of `b`
+        let row = device.zia_rows_for(b)[0].row;
+        let (choice, &other) = device
+            .zia_table_get_row(row)
+            .iter()
+            .enumerate()
+            .find(|&(_, s)| *s != a && *s != b)
+            .unwrap();
+        bitstream.set_prop(&bitstream.fb(0).zia_row(row), ZIARow::MuxChoice(choice as u8));
+
+        let signals = BTreeMap::from([(a, None), (b, Some(row))]);
+        let routing = bitstream.route_zia(0, &signals).unwrap();
+        let kept = ZIARowChoice { row, choice: choice as u8 };
+        assert_eq!(routing[&other], kept);
+        assert_ne!(routing[&b].row, row);
+        assert_eq!(bitstream.zia_routing(0), routing);
+
+        // Releasing the row makes it available again
+        bitstream.set_prop(&bitstream.fb(0).zia_row(row), ZIARow::GND);
+        bitstream.set_prop(&bitstream.fb(0).zia_row(routing[&b].row), ZIARow::GND);
+        let routing = bitstream.route_zia(0, &BTreeMap::from([(b, Some(row))])).unwrap();
+        assert!(!routing.contains_key(&other));
+        assert_eq!(routing[&b].row, row);
+    }
+}